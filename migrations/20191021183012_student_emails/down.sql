@@ -0,0 +1,4 @@
+DROP TABLE mails;
+
+ALTER TABLE students
+    DROP COLUMN email;
@@ -0,0 +1,15 @@
+ALTER TABLE students
+    ADD COLUMN email text NULL;
+
+-- outgoing mails, sent asynchronously by a background worker
+CREATE TABLE mails (
+    id SERIAL PRIMARY KEY,
+    created_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    year smallint NOT NULL REFERENCES years,
+    recipient text NOT NULL,
+    subject text NOT NULL,
+    body text NOT NULL,
+    sent_at timestamp with time zone NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    last_error text NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE years
+    DROP COLUMN anonymized;
@@ -0,0 +1,2 @@
+ALTER TABLE years
+    ADD COLUMN anonymized boolean NOT NULL DEFAULT false;
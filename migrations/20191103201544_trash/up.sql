@@ -0,0 +1,11 @@
+-- deleted students, experiments, tasks and days including everything that
+-- depended on them, serialized as json to be able to restore them
+CREATE TABLE trash (
+    id SERIAL PRIMARY KEY,
+    deleted_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    year smallint NOT NULL REFERENCES years,
+    author text NOT NULL,
+    kind text NOT NULL,
+    name text NOT NULL,
+    data text NOT NULL
+);
@@ -0,0 +1,7 @@
+DROP TABLE course_admins;
+
+ALTER TABLE years
+    DROP COLUMN course,
+    DROP COLUMN name;
+
+DROP TABLE courses;
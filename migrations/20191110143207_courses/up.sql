@@ -0,0 +1,30 @@
+-- courses group years of the same lab course, their id is used in urls
+CREATE TABLE courses (
+    id text PRIMARY KEY,
+    name text NOT NULL
+);
+
+INSERT INTO courses (id, name)
+    VALUES ('hwp', 'Hardwarepraktikum');
+
+-- the id of a year stays unique across all courses, while the name is the
+-- year shown to users and only unique per course
+ALTER TABLE years
+    ADD COLUMN course text NOT NULL DEFAULT 'hwp' REFERENCES courses,
+    ADD COLUMN name smallint NULL;
+
+UPDATE years SET
+    name = id;
+
+ALTER TABLE years
+    ALTER COLUMN course DROP DEFAULT,
+    ALTER COLUMN name SET NOT NULL,
+    ADD UNIQUE (course, name);
+
+-- course admins can manage all years of their course like site admins
+CREATE TABLE course_admins (
+    id SERIAL PRIMARY KEY,
+    course text NOT NULL REFERENCES courses,
+    username text NOT NULL,
+    UNIQUE (course, username)
+);
@@ -0,0 +1,16 @@
+ALTER TABLE years
+    ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE years_id_seq;
+
+ALTER TABLE years
+    ADD COLUMN name smallint NULL;
+
+UPDATE years SET
+    name = EXTRACT(YEAR FROM start_date);
+
+ALTER TABLE years
+    DROP COLUMN label,
+    DROP COLUMN start_date,
+    DROP COLUMN end_date,
+    ALTER COLUMN name SET NOT NULL,
+    ADD UNIQUE (course, name);
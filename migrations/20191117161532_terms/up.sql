@@ -0,0 +1,26 @@
+-- years become terms with a label and a duration, so a course can run
+-- several times per calendar year
+ALTER TABLE years
+    ADD COLUMN label text NULL,
+    ADD COLUMN start_date date NULL,
+    ADD COLUMN end_date date NULL;
+
+UPDATE years SET
+    label = name::text,
+    start_date = make_date(name, 1, 1),
+    end_date = make_date(name, 12, 31);
+
+-- also removes the unique constraint on course and name
+ALTER TABLE years
+    DROP COLUMN name,
+    ALTER COLUMN label SET NOT NULL,
+    ALTER COLUMN start_date SET NOT NULL,
+    ALTER COLUMN end_date SET NOT NULL,
+    ADD UNIQUE (course, label),
+    ADD CHECK (start_date <= end_date);
+
+-- the id is no longer a calendar year but only a surrogate key
+CREATE SEQUENCE years_id_seq OWNED BY years.id;
+SELECT setval('years_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM years;
+ALTER TABLE years
+    ALTER COLUMN id SET DEFAULT nextval('years_id_seq');
@@ -0,0 +1,10 @@
+ALTER TABLE tutors
+    ADD COLUMN is_admin boolean NOT NULL DEFAULT false;
+
+UPDATE tutors SET
+    is_admin = true
+    WHERE role = 'admin';
+
+ALTER TABLE tutors
+    ALTER COLUMN is_admin DROP DEFAULT,
+    DROP COLUMN role;
@@ -0,0 +1,11 @@
+-- the permissions of each role are defined in src/web/permissions.rs
+ALTER TABLE tutors
+    ADD COLUMN role text NOT NULL DEFAULT 'tutor'
+    CHECK (role IN ('observer', 'grader', 'event-tutor', 'student-manager', 'tutor', 'admin'));
+
+UPDATE tutors SET
+    role = 'admin'
+    WHERE is_admin;
+
+ALTER TABLE tutors
+    DROP COLUMN is_admin;
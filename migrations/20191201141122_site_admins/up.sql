@@ -0,0 +1,5 @@
+-- initially filled from the site_admins list of the configuration
+CREATE TABLE site_admins (
+    id SERIAL PRIMARY KEY,
+    username text NOT NULL UNIQUE
+);
@@ -0,0 +1,12 @@
+-- tutors may only record completions and elaborations on their days
+CREATE TABLE day_tutors (
+    day_id integer NOT NULL REFERENCES days,
+    tutor_id integer NOT NULL REFERENCES tutors,
+    PRIMARY KEY (day_id, tutor_id)
+);
+
+-- keep the previous behaviour by assigning all tutors to all days
+INSERT INTO day_tutors (day_id, tutor_id)
+    SELECT days.id, tutors.id
+    FROM days
+    INNER JOIN tutors ON days.year = tutors.year;
@@ -0,0 +1,9 @@
+DROP TABLE elaboration_submissions;
+
+ALTER TABLE elaborations
+    DROP COLUMN points,
+    DROP COLUMN grade,
+    DROP COLUMN feedback,
+    DROP COLUMN grader,
+    DROP COLUMN submitted_on,
+    DROP COLUMN rework_due;
@@ -0,0 +1,36 @@
+-- grading of the current state of an elaboration
+ALTER TABLE elaborations
+    ADD COLUMN points smallint CHECK (points >= 0),
+    ADD COLUMN grade text,
+    ADD COLUMN feedback text NOT NULL DEFAULT '',
+    ADD COLUMN grader text,
+    ADD COLUMN submitted_on date,
+    ADD COLUMN rework_due date;
+
+-- existing elaborations were submitted when their current status was set,
+-- those without such an audit log entry keep an unknown date
+UPDATE elaborations SET submitted_on = (
+    SELECT max(audit_logs.created_at)::date FROM audit_logs
+        WHERE audit_logs.affected_group = elaborations.group_id
+            AND audit_logs.change LIKE 'Mark elaboration of % (#' || elaborations.experiment_id || ') as %'
+);
+ALTER TABLE elaborations ALTER COLUMN submitted_on SET DEFAULT CURRENT_DATE;
+
+-- open reworks from before due dates existed are overdue right away, so they
+-- are still listed
+UPDATE elaborations SET rework_due = CURRENT_DATE - 1
+    WHERE rework_required AND NOT accepted;
+
+-- every change of an elaboration is kept as a submission
+CREATE TABLE elaboration_submissions (
+    id serial PRIMARY KEY,
+    group_id integer NOT NULL REFERENCES groups,
+    experiment_id integer NOT NULL REFERENCES experiments,
+    created_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    rework_required boolean NOT NULL,
+    accepted boolean NOT NULL,
+    points smallint,
+    grade text,
+    feedback text NOT NULL,
+    grader text NOT NULL
+);
@@ -0,0 +1 @@
+DROP TABLE elaboration_files;
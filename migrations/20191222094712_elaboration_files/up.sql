@@ -0,0 +1,12 @@
+-- uploaded elaborations, the files themselves are stored on disk by id
+CREATE TABLE elaboration_files (
+    id serial PRIMARY KEY,
+    group_id integer NOT NULL REFERENCES groups,
+    experiment_id integer NOT NULL REFERENCES experiments,
+    version integer NOT NULL CHECK (version > 0),
+    filename text NOT NULL,
+    size integer NOT NULL,
+    uploaded_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    uploader text NOT NULL,
+    UNIQUE (group_id, experiment_id, version)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE completions
+    DROP COLUMN points;
+
+ALTER TABLE tasks
+    DROP COLUMN points,
+    DROP COLUMN weight;
@@ -0,0 +1,9 @@
+-- tasks are worth a number of points, which are weighted in the score of the
+-- experiment, and weight 0 excludes a task from the score
+ALTER TABLE tasks
+    ADD COLUMN points smallint NOT NULL DEFAULT 1 CHECK (points > 0),
+    ADD COLUMN weight smallint NOT NULL DEFAULT 1 CHECK (weight >= 0);
+
+-- partial credit for a completed task, NULL means all points
+ALTER TABLE completions
+    ADD COLUMN points smallint CHECK (points >= 0);
@@ -0,0 +1,4 @@
+ALTER TABLE tasks
+    DROP COLUMN position;
+ALTER TABLE experiments
+    DROP COLUMN position;
@@ -0,0 +1,20 @@
+-- explicit order of experiments within a year and of tasks within an
+-- experiment, initialised with the previous order by name
+ALTER TABLE experiments
+    ADD COLUMN position integer NOT NULL DEFAULT 0;
+ALTER TABLE tasks
+    ADD COLUMN position integer NOT NULL DEFAULT 0;
+
+UPDATE experiments SET position = ordered.position
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY year ORDER BY name) AS position
+    FROM experiments
+) AS ordered
+WHERE experiments.id = ordered.id;
+
+UPDATE tasks SET position = ordered.position
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY experiment_id ORDER BY name) AS position
+    FROM tasks
+) AS ordered
+WHERE tasks.id = ordered.id;
@@ -0,0 +1,6 @@
+DROP TABLE attachments;
+
+ALTER TABLE tasks
+    DROP COLUMN description;
+ALTER TABLE experiments
+    DROP COLUMN description;
@@ -0,0 +1,19 @@
+-- markdown descriptions of what tutors have to check
+ALTER TABLE experiments
+    ADD COLUMN description text NOT NULL DEFAULT '';
+ALTER TABLE tasks
+    ADD COLUMN description text NOT NULL DEFAULT '';
+
+-- reference links and attached files of an experiment or one of its tasks
+CREATE TABLE attachments (
+    id serial PRIMARY KEY,
+    experiment_id integer NOT NULL REFERENCES experiments,
+    task_id integer REFERENCES tasks,
+    title text NOT NULL,
+    url text,
+    -- name of the file in the upload directory, which is shared by copies of
+    -- the attachment in later terms
+    file text,
+    content_type text,
+    CHECK ((url IS NULL) != (file IS NULL))
+);
@@ -0,0 +1,4 @@
+ALTER TABLE days
+    DROP COLUMN room_id;
+
+DROP TABLE rooms;
@@ -0,0 +1,13 @@
+-- rooms of a term with the layout of their desks, given as one line per row
+-- with desk numbers separated by spaces and dots for gaps
+CREATE TABLE rooms (
+    id serial PRIMARY KEY,
+    year smallint NOT NULL REFERENCES years,
+    name text NOT NULL,
+    layout text NOT NULL DEFAULT '',
+    UNIQUE (year, name)
+);
+
+-- groups of a day sit in its room
+ALTER TABLE days
+    ADD COLUMN room_id integer REFERENCES rooms ON DELETE SET NULL;
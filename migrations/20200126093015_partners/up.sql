@@ -0,0 +1,4 @@
+-- matrikel of the student someone wants to form a group with, as registered
+-- before the groups are formed
+ALTER TABLE students
+    ADD COLUMN partner text;
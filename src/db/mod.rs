@@ -1,18 +1,23 @@
 mod inet;
 mod models;
 mod schema;
+mod trashcan;
 
 pub use self::inet::{inet as to_inet, PgInetExpressionMethods};
 pub use self::models::*;
 pub use self::schema::*;
+pub use self::trashcan::{Impact, purge_trash, restore_trash, trash_day, trash_experiment,
+                         trash_entries_of_student, trash_student, trash_task,
+                         trashed_attachment_files, trashed_file_ids, trashed_students};
 
-use chrono::{Datelike, Utc};
+use chrono::{Datelike, NaiveDate, Utc};
 use crate::errors::*;
 use diesel::{delete, dsl::any, prelude::*};
 use diesel::r2d2::{self, ConnectionManager};
 use rocket::http::Status;
 use rocket::request::{self, FromRequest};
 use rocket::{Request, State, Outcome};
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 
 embed_migrations!();
@@ -42,7 +47,7 @@ pub fn init_year(truncate_database: bool, database_url: &str) -> Result<()> {
         };
 
         if num_years == 0 {
-            add_current_year(&conn)?;
+            add_current_term(DEFAULT_COURSE, &conn)?;
         }
 
         Ok(())
@@ -90,22 +95,60 @@ pub fn expect1(count: usize) -> QueryResult<usize> {
     }
 }
 
-/// Add the current year to the database
+/// Course created by the migrations that contains all years from before
+/// courses were introduced
+pub const DEFAULT_COURSE: &str = "hwp";
+
+/// Add the current semester to the given course and return its id
 ///
-/// This will fail if the year already exists.
+/// Summer semesters last from April to September, winter semesters from
+/// October to March. This will fail if the semester already exists.
 ///
 /// Should be run inside a transaction.
-pub fn add_current_year(conn: &PgConnection) -> Result<()> {
-    let year = Year {
-        id: Utc::today().year() as i16,
-        writable: true,
+pub fn add_current_term(course: &str, conn: &PgConnection) -> Result<i16> {
+    let today = Utc::today().naive_utc();
+    let year = today.year();
+
+    let (label, start_date, end_date) = match today.month() {
+        4..=9 => (
+            format!("Sommersemester {}", year),
+            NaiveDate::from_ymd(year, 4, 1),
+            NaiveDate::from_ymd(year, 9, 30),
+        ),
+        10..=12 => (
+            format!("Wintersemester {}/{:02}", year, (year + 1) % 100),
+            NaiveDate::from_ymd(year, 10, 1),
+            NaiveDate::from_ymd(year + 1, 3, 31),
+        ),
+        _ => (
+            format!("Wintersemester {}/{:02}", year - 1, year % 100),
+            NaiveDate::from_ymd(year - 1, 10, 1),
+            NaiveDate::from_ymd(year, 3, 31),
+        ),
     };
 
-    diesel::insert_into(years::table)
-        .values(&year)
-        .execute(conn)?;
+    add_term(&NewYear {
+        course: course.to_string(),
+        label: label,
+        start_date: start_date,
+        end_date: end_date,
+    }, conn)
+}
 
-    Ok(())
+/// Add a new term to a course and return its id
+///
+/// Terms are stored in the `years` table and referenced by their id
+/// everywhere, which is just a surrogate key. This will fail if the course
+/// already contains a term with the same label.
+///
+/// Should be run inside a transaction.
+pub fn add_term(term: &NewYear, conn: &PgConnection) -> Result<i16> {
+    let id = diesel::insert_into(years::table)
+        .values(term)
+        .returning(years::id)
+        .get_result(conn)?;
+
+    Ok(id)
 }
 
 /// Delete the group with the given id
@@ -119,7 +162,14 @@ pub fn delete_group(group: i32, conn: &PgConnection) -> Result<()> {
         .filter(completions::group_id.eq(group)))
         .execute(conn)?;
 
-    // Delete all elaborations of the group
+    // Delete all elaborations of the group, their history and files (which
+    // are removed from disk later)
+    diesel::delete(elaboration_files::table
+        .filter(elaboration_files::group_id.eq(group)))
+        .execute(conn)?;
+    diesel::delete(elaboration_submissions::table
+        .filter(elaboration_submissions::group_id.eq(group)))
+        .execute(conn)?;
     diesel::delete(elaborations::table
         .filter(elaborations::group_id.eq(group)))
         .execute(conn)?;
@@ -139,8 +189,8 @@ pub fn delete_group(group: i32, conn: &PgConnection) -> Result<()> {
 /// Delete the entire year with the given id
 ///
 /// Also deletes everything associated with the year, including groups,
-/// students, completions, elaborations, events, experiments, tasks,
-/// tutors and audit log entries.
+/// students, completions, elaborations, events, rooms, experiments, tasks,
+/// attachments, tutors, mails, trash and audit log entries.
 ///
 /// Should be run inside a transaction.
 pub fn delete_year(year: i16, conn: &PgConnection) -> Result<()> {
@@ -162,27 +212,571 @@ pub fn delete_year(year: i16, conn: &PgConnection) -> Result<()> {
 
     // Delete all events belonging to any of the days and the days
     delete(events::table.filter(events::day_id.eq(any(&days)))).execute(conn)?;
+    delete(day_tutors::table.filter(day_tutors::day_id.eq(any(&days)))).execute(conn)?;
     delete(days::table.filter(days::year.eq(year))).execute(conn)?;
+    delete(rooms::table.filter(rooms::year.eq(year))).execute(conn)?;
 
     // Load all experiments of the given year…
     let experiments = experiments::table
         .filter(experiments::year.eq(year))
         .select(experiments::id)
         .load::<i32>(conn)?;
-    // …and delete all tasks referencing any of them
+    // …and delete all attachments and tasks referencing any of them
+    diesel::delete(attachments::table
+        .filter(attachments::experiment_id.eq(any(&experiments))))
+        .execute(conn)?;
     diesel::delete(tasks::table
-        .filter(tasks::experiment_id.eq(any(experiments))))
+        .filter(tasks::experiment_id.eq(any(&experiments))))
         .execute(conn)?;
 
-    // Delete all experiments, students, tutors, and whitelist and audit log entries
+    // Delete all experiments, students, tutors, and whitelist, audit log, mail and trash entries
     delete(experiments::table.filter(experiments::year.eq(year))).execute(conn)?;
     delete(students::table.filter(students::year.eq(year))).execute(conn)?;
     delete(tutors::table.filter(tutors::year.eq(year))).execute(conn)?;
     delete(ip_whitelist::table.filter(ip_whitelist::year.eq(year))).execute(conn)?;
     delete(audit_logs::table.filter(audit_logs::year.eq(year))).execute(conn)?;
+    delete(mails::table.filter(mails::year.eq(year))).execute(conn)?;
+    delete(trash::table.filter(trash::year.eq(year))).execute(conn)?;
 
     // Delete the given year
     delete(years::table.find(year)).execute(conn)?;
 
     Ok(())
 }
+
+/// Copy the structure of the year `template` into the already existing year
+/// `year`
+///
+/// Copies experiments and tasks including their attachments, rooms, days,
+/// events, tutors including their days and ip whitelist entries, but no
+/// students or groups. The dates of all events are shifted by the given number
+/// of weeks, fails with `ErrorKind::DateOutOfRange` if a shifted date does not
+/// exist.
+///
+/// Should be run inside a transaction.
+pub fn copy_year(template: i16, year: i16, shift_weeks: i64, conn: &PgConnection) -> Result<()> {
+    let shift = shift_weeks.checked_mul(7 * 24 * 60 * 60 * 1000)
+        .map(chrono::Duration::milliseconds)
+        .ok_or(ErrorKind::DateOutOfRange)?;
+
+    // Copy experiments and their tasks and remember the new experiment ids
+    let mut experiment_ids = HashMap::new();
+    let experiments = experiments::table
+        .filter(experiments::year.eq(template))
+        .order(experiments::id.asc())
+        .load::<Experiment>(conn)?;
+    for experiment in experiments {
+        let id: i32 = diesel::insert_into(experiments::table)
+            .values((
+                experiments::name.eq(&experiment.name),
+                experiments::year.eq(year),
+                experiments::position.eq(experiment.position),
+                experiments::description.eq(&experiment.description),
+            ))
+            .returning(experiments::id)
+            .get_result(conn)?;
+        experiment_ids.insert(experiment.id, id);
+
+        let tasks = tasks::table
+            .filter(tasks::experiment_id.eq(experiment.id))
+            .order(tasks::id.asc())
+            .load::<Task>(conn)?;
+        let mut task_ids = HashMap::new();
+        for task in tasks {
+            let task_id: i32 = diesel::insert_into(tasks::table)
+                .values((
+                    tasks::experiment_id.eq(id),
+                    tasks::name.eq(task.name),
+                    tasks::points.eq(task.points),
+                    tasks::weight.eq(task.weight),
+                    tasks::position.eq(task.position),
+                    tasks::description.eq(task.description),
+                ))
+                .returning(tasks::id)
+                .get_result(conn)?;
+            task_ids.insert(task.id, task_id);
+        }
+
+        // Copies of attached files share the file on disk
+        let attachments = attachments::table
+            .filter(attachments::experiment_id.eq(experiment.id))
+            .order(attachments::id.asc())
+            .load::<Attachment>(conn)?;
+        for attachment in attachments {
+            diesel::insert_into(attachments::table)
+                .values(&NewAttachment {
+                    experiment_id: id,
+                    task_id: attachment.task_id.and_then(|task| task_ids.get(&task).cloned()),
+                    title: &attachment.title,
+                    url: attachment.url.as_ref().map(String::as_str),
+                    file: attachment.file.as_ref().map(String::as_str),
+                    content_type: attachment.content_type.as_ref().map(String::as_str),
+                })
+                .execute(conn)?;
+        }
+    }
+
+    // Copy rooms and remember the new room ids
+    let mut room_ids = HashMap::new();
+    let rooms = rooms::table
+        .filter(rooms::year.eq(template))
+        .order(rooms::id.asc())
+        .load::<Room>(conn)?;
+    for room in rooms {
+        let id: i32 = diesel::insert_into(rooms::table)
+            .values(&NewRoom {
+                year: year,
+                name: room.name,
+                layout: room.layout,
+            })
+            .returning(rooms::id)
+            .get_result(conn)?;
+        room_ids.insert(room.id, id);
+    }
+
+    // Copy days and their events and remember the new day ids
+    let mut day_ids = HashMap::new();
+    let days = days::table
+        .filter(days::year.eq(template))
+        .order(days::id.asc())
+        .load::<Day>(conn)?;
+    for day in days {
+        let id: i32 = diesel::insert_into(days::table)
+            .values((
+                days::name.eq(&day.name),
+                days::year.eq(year),
+                days::room_id.eq(match day.room_id {
+                    Some(room) => Some(*room_ids.get(&room).ok_or("Day in a room of another year")?),
+                    None => None,
+                }),
+            ))
+            .returning(days::id)
+            .get_result(conn)?;
+        day_ids.insert(day.id, id);
+
+        let events = events::table
+            .filter(events::day_id.eq(day.id))
+            .load::<Event>(conn)?;
+        for event in events {
+            diesel::insert_into(events::table)
+                .values(&Event {
+                    day_id: id,
+                    experiment_id: *experiment_ids.get(&event.experiment_id)
+                        .ok_or("Event of an experiment of another year")?,
+                    date: event.date.checked_add_signed(shift).ok_or(ErrorKind::DateOutOfRange)?,
+                })
+                .execute(conn)?;
+        }
+    }
+
+    let tutors = tutors::table
+        .filter(tutors::year.eq(template))
+        .order(tutors::id.asc())
+        .load::<Tutor>(conn)?;
+    for tutor in tutors {
+        let id: i32 = diesel::insert_into(tutors::table)
+            .values(&NewTutor {
+                username: tutor.username,
+                year: year,
+                role: tutor.role,
+            })
+            .returning(tutors::id)
+            .get_result(conn)?;
+
+        let days = day_tutors::table
+            .filter(day_tutors::tutor_id.eq(tutor.id))
+            .select(day_tutors::day_id)
+            .load::<i32>(conn)?;
+        for day in days {
+            diesel::insert_into(day_tutors::table)
+                .values(&DayTutor {
+                    day_id: *day_ids.get(&day).ok_or("Tutor of a day of another year")?,
+                    tutor_id: id,
+                })
+                .execute(conn)?;
+        }
+    }
+
+    let whitelist = ip_whitelist::table
+        .filter(ip_whitelist::year.eq(template))
+        .order(ip_whitelist::id.asc())
+        .select(ip_whitelist::ipnet.abbrev())
+        .load::<String>(conn)?;
+    for ipnet in whitelist {
+        diesel::insert_into(ip_whitelist::table)
+            .values((
+                ip_whitelist::ipnet.eq(to_inet(ipnet)),
+                ip_whitelist::year.eq(year),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Replace all personal data of the students of the given year by pseudonyms
+///
+/// Names, matrikel numbers, usernames and email addresses are replaced in the
+/// students table and where the audit log records students, while groups,
+/// completions and elaborations are kept, so statistics stay intact. This
+/// includes the former data of students that were deleted or changed, which is
+/// taken from the trash and the audit log. In group comments, elaboration
+/// feedback and other free text only full names and exact details are
+/// replaced, so a first name on its own is left as it is. Uploaded
+/// elaborations, whose file names and uploaders are personal data as well,
+/// queued and sent mails and the trash of the year are deleted. The files of
+/// the uploaded elaborations are left on disk for `Uploads::purge`. Returns the
+/// number of anonymized students.
+///
+/// Should be run inside a transaction.
+pub fn anonymize_year(year: i16, conn: &PgConnection) -> Result<usize> {
+    let students = students::table
+        .filter(students::year.eq(year))
+        .order(students::id.asc())
+        .load::<Student>(conn)?;
+
+    // Deleted students keep their data in the trash until it is deleted below
+    let trashed = trashed_students(year, conn)?;
+    let logs = audit_logs::table
+        .filter(audit_logs::year.eq(year))
+        .select((audit_logs::id, audit_logs::author, audit_logs::change))
+        .load::<(i32, String, String)>(conn)?;
+    let file_ids: Vec<i32> = elaboration_files::table
+        .inner_join(groups::table.inner_join(days::table))
+        .filter(days::year.eq(year))
+        .select(elaboration_files::id)
+        .load(conn)?;
+
+    let mut pseudonyms = Pseudonyms::default();
+    for (i, student) in students.iter().chain(&trashed).enumerate() {
+        let matrikel = format!("anonym-{}", i + 1);
+
+        if i < students.len() {
+            diesel::update(students::table.find(student.id))
+                .set((
+                    students::given_name.eq("Teilnehmer"),
+                    students::family_name.eq((i + 1).to_string()),
+                    students::matrikel.eq(&matrikel),
+                    students::username.eq(None::<String>),
+                    students::email.eq(None::<String>),
+                    students::partner.eq(None::<String>),
+                ))
+                .execute(conn)?;
+        }
+
+        pseudonyms.names.entry(student.name()).or_insert_with(|| format!("Teilnehmer {}", i + 1));
+        pseudonyms.details.insert(student.matrikel.clone(), matrikel);
+        for detail in student.username.iter().chain(&student.email) {
+            pseudonyms.details.insert(detail.clone(), "-".into());
+        }
+    }
+    pseudonyms.count = students.len() + trashed.len();
+
+    // Data of students that were changed or purged from the trash is only
+    // left in the audit log, so it is collected before any free text is
+    // scrubbed
+    let logs: Vec<_> = logs.into_iter()
+        .map(|(id, author, change)| {
+            let scrubbed = pseudonyms.scrub_audit_log(&change);
+            (id, author, change, scrubbed)
+        })
+        .collect();
+    let replacements = pseudonyms.replacements();
+    let scrub = |text: &str| {
+        replacements.iter().fold(text.to_string(), |text, (original, pseudonym)| {
+            replace_words(&text, original, pseudonym)
+        })
+    };
+
+    for (id, author, change, scrubbed) in logs {
+        let scrubbed = scrub(&scrubbed);
+        // Students are the authors of their uploads in the portal
+        let by_student = pseudonyms.details.contains_key(&author);
+        if scrubbed != change || by_student {
+            diesel::update(audit_logs::table.find(id))
+                .set((
+                    audit_logs::author.eq(if by_student { "-" } else { author.as_str() }),
+                    audit_logs::change.eq(scrubbed),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    let comments = groups::table
+        .inner_join(days::table)
+        .filter(days::year.eq(year))
+        .select((groups::id, groups::comment))
+        .load::<(i32, String)>(conn)?;
+    for (id, comment) in comments {
+        let scrubbed = scrub(&comment);
+        if scrubbed != comment {
+            diesel::update(groups::table.find(id))
+                .set(groups::comment.eq(scrubbed))
+                .execute(conn)?;
+        }
+    }
+
+    let feedbacks = elaborations::table
+        .inner_join(groups::table.inner_join(days::table))
+        .filter(days::year.eq(year))
+        .select((elaborations::group_id, elaborations::experiment_id, elaborations::feedback))
+        .load::<(i32, i32, String)>(conn)?;
+    for (group, experiment, feedback) in feedbacks {
+        let scrubbed = scrub(&feedback);
+        if scrubbed != feedback {
+            diesel::update(elaborations::table.find((group, experiment)))
+                .set(elaborations::feedback.eq(scrubbed))
+                .execute(conn)?;
+        }
+    }
+
+    let feedbacks = elaboration_submissions::table
+        .inner_join(groups::table.inner_join(days::table))
+        .filter(days::year.eq(year))
+        .select((elaboration_submissions::id, elaboration_submissions::feedback))
+        .load::<(i32, String)>(conn)?;
+    for (id, feedback) in feedbacks {
+        let scrubbed = scrub(&feedback);
+        if scrubbed != feedback {
+            diesel::update(elaboration_submissions::table.find(id))
+                .set(elaboration_submissions::feedback.eq(scrubbed))
+                .execute(conn)?;
+        }
+    }
+
+    delete(elaboration_files::table.filter(elaboration_files::id.eq_any(file_ids))).execute(conn)?;
+    delete(mails::table.filter(mails::year.eq(year))).execute(conn)?;
+    delete(trash::table.filter(trash::year.eq(year))).execute(conn)?;
+
+    diesel::update(years::table.find(year))
+        .set(years::anonymized.eq(true))
+        .execute(conn)?;
+
+    Ok(students.len())
+}
+
+/// Pseudonyms for the names and other personal data of students
+#[derive(Debug, Default)]
+struct Pseudonyms {
+    names: HashMap<String, String>,
+    /// Matrikel numbers, usernames and email addresses
+    details: HashMap<String, String>,
+    /// Number of names with a pseudonym
+    count: usize,
+}
+
+impl Pseudonyms {
+    fn name(&mut self, name: &str) -> String {
+        if let Some(pseudonym) = self.names.get(name) {
+            return pseudonym.clone();
+        }
+
+        self.count += 1;
+        let pseudonym = format!("Teilnehmer {}", self.count);
+        self.names.insert(name.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    // Unknown details are former data of changed students
+    fn detail(&mut self, detail: &str) -> String {
+        if detail.starts_with("partner ") {
+            return format!("partner {}", self.detail(&detail[8..]));
+        }
+        if detail == "-" || detail.starts_with('#') {
+            return detail.to_string();
+        }
+
+        self.details.entry(detail.to_string()).or_insert_with(|| "-".into()).clone()
+    }
+
+    /// Replace the personal data in the places where the audit log records
+    /// students, i.e. names followed by their details in parentheses, names of
+    /// students in the trash or of regrouped groups and names of uploads
+    fn scrub_audit_log(&mut self, change: &str) -> String {
+        const PREFIXES: &[&str] = &["Create new student ", "Update student ", "Move student ",
+                                    "Remove student ", "Student "];
+        const TRASH_PREFIXES: &[&str] = &["Restore student ", "Purge student "];
+        const REGROUPED: &str = "with students ";
+
+        if let Some(prefix) = PREFIXES.iter().find(|prefix| change.starts_with(*prefix)) {
+            return format!("{}{}", prefix, self.scrub_students(&change[prefix.len()..]));
+        }
+        if change.starts_with("Add ") && change.ends_with(" to group") {
+            return format!("Add {}", self.scrub_students(&change[4..]));
+        }
+        if change.starts_with("Remove ") && change.ends_with(" from group") {
+            return format!("Remove {}", self.scrub_students(&change[7..]));
+        }
+
+        // Students are named with their matrikel in the trash
+        if let Some(prefix) = TRASH_PREFIXES.iter().find(|prefix| change.starts_with(*prefix)) {
+            if let Some(end) = change.rfind(" from trash (#") {
+                let student = &change[prefix.len()..end];
+                let student = match student.ends_with(')') {
+                    true => self.scrub_students(student),
+                    false => self.name(student),
+                };
+                return format!("{}{}{}", prefix, student, &change[end..]);
+            }
+        }
+
+        // The names are followed by the number of completions
+        if let Some(start) = change.find(REGROUPED).map(|start| start + REGROUPED.len()) {
+            let end = change[start..].find(" completions, ")
+                .and_then(|end| change[start..start + end].rfind(", "))
+                .map(|end| start + end);
+            if let Some(end) = end {
+                let names: Vec<_> = change[start..end].split(", ")
+                    .filter(|name| !name.is_empty())
+                    .map(|name| self.name(name))
+                    .collect();
+                return format!("{}{}{}", &change[..start], names.join(", "), &change[end..]);
+            }
+        }
+
+        if change.starts_with("Upload version ") {
+            if let (Some(start), Some(end)) = (change.find(") as "), change.rfind(" (#")) {
+                if start + 5 <= end {
+                    return format!("{}-{}", &change[..start + 5], &change[end..]);
+                }
+            }
+        }
+
+        change.to_string()
+    }
+
+    // Students are logged as name followed by their details in parentheses,
+    // which are given twice separated by "to" for changes
+    fn scrub_students(&mut self, mut rest: &str) -> String {
+        let mut result = String::with_capacity(rest.len());
+        while let Some(open) = rest.find(" (") {
+            let close = match rest[open..].find(')') {
+                Some(close) => open + close,
+                None => break,
+            };
+
+            let name = self.name(&rest[..open]);
+            let details: Vec<_> = rest[open + 2..close].split(", ")
+                .map(|detail| self.detail(detail))
+                .collect();
+            result.push_str(&format!("{} ({})", name, details.join(", ")));
+
+            rest = &rest[close + 1..];
+            match rest.starts_with(" to ") {
+                true => {
+                    result.push_str(" to ");
+                    rest = &rest[4..];
+                }
+                false => break,
+            }
+        }
+        result.push_str(rest);
+
+        result
+    }
+
+    /// Full names and details with their pseudonyms, longer ones first so full
+    /// names are replaced before names contained in them
+    fn replacements(&self) -> Vec<(String, String)> {
+        let mut replacements: Vec<_> = self.names.iter()
+            .chain(&self.details)
+            .filter(|(original, _)| !original.trim().is_empty())
+            .map(|(original, pseudonym)| (original.clone(), pseudonym.clone()))
+            .collect();
+        replacements.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        replacements
+    }
+}
+
+/// Replace all occurrences of `word` in `text` that are not part of a
+/// longer word or of an id reference like `#12`
+fn replace_words(text: &str, word: &str, replacement: &str) -> String {
+    let is_word_char = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '#');
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(word) {
+        let before = rest[..index].chars().next_back()
+            .or_else(|| result.chars().next_back());
+        let after = rest[index + word.len()..].chars().next();
+
+        result.push_str(&rest[..index]);
+        if is_word_char(before) || is_word_char(after) {
+            result.push_str(word);
+        } else {
+            result.push_str(replacement);
+        }
+        rest = &rest[index + word.len()..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudonyms() -> Pseudonyms {
+        let mut pseudonyms = Pseudonyms::default();
+        pseudonyms.names.insert("Anna Walter".into(), "Teilnehmer 1".into());
+        pseudonyms.details.insert("2256397".into(), "anonym-1".into());
+        pseudonyms.details.insert("aw19xyz".into(), "-".into());
+        pseudonyms.count = 1;
+        pseudonyms
+    }
+
+    #[test]
+    fn scrub_deleted_student() {
+        let mut pseudonyms = pseudonyms();
+        assert_eq!(pseudonyms.scrub_audit_log("Move student Maria Schneider (8164574, ms18gwhd, #12) to trash"),
+                   "Move student Teilnehmer 2 (-, -, #12) to trash");
+        assert_eq!(pseudonyms.scrub_audit_log("Purge student Maria Schneider from trash (#7)"),
+                   "Purge student Teilnehmer 2 from trash (#7)");
+        assert_eq!(pseudonyms.scrub_audit_log("Restore student Anna Walter (2256397) from trash (#8)"),
+                   "Restore student Teilnehmer 1 (anonym-1) from trash (#8)");
+    }
+
+    #[test]
+    fn scrub_changed_student() {
+        let mut pseudonyms = pseudonyms();
+        assert_eq!(pseudonyms.scrub_audit_log("Update student Anna Walter (2256397, aw19xyz, -, partner 8164574, #3) \
+                                               to Anna Becker (2256397, aw19xyz, anna@example.org, partner -)"),
+                   "Update student Teilnehmer 1 (anonym-1, -, -, partner -, #3) \
+                    to Teilnehmer 2 (anonym-1, -, -, partner -)");
+
+        let replacements = pseudonyms.replacements();
+        assert_eq!(replacements[0], ("anna@example.org".to_string(), "-".to_string()));
+        assert!(replacements.contains(&("8164574".to_string(), "-".to_string())));
+        assert!(replacements.contains(&("Anna Becker".to_string(), "Teilnehmer 2".to_string())));
+    }
+
+    #[test]
+    fn scrub_group_members() {
+        let mut pseudonyms = pseudonyms();
+        assert_eq!(pseudonyms.scrub_audit_log("Add Anna Walter (#3) to group"),
+                   "Add Teilnehmer 1 (#3) to group");
+        assert_eq!(pseudonyms.scrub_audit_log("Merge group #4 into group with students Anna Walter, Max Paul, \
+                                               2 completions, 1 elaborations and 0 files"),
+                   "Merge group #4 into group with students Teilnehmer 1, Teilnehmer 2, \
+                    2 completions, 1 elaborations and 0 files");
+        assert_eq!(pseudonyms.scrub_audit_log("Upload version 2 of the elaboration of Pendel (#2) as Max Paul.pdf (#9)"),
+                   "Upload version 2 of the elaboration of Pendel (#2) as - (#9)");
+    }
+
+    #[test]
+    fn scrub_only_logged_forms() {
+        let mut pseudonyms = pseudonyms();
+        let change = "Add link Max (#4) to https://example.org to experiment Paul (#2)";
+        assert_eq!(pseudonyms.scrub_audit_log(change), change);
+        assert_eq!(pseudonyms.count, 1);
+    }
+
+    #[test]
+    fn replace_whole_words_only() {
+        assert_eq!(replace_words("Anna, Annabell und Anna", "Anna", "Teilnehmer 1"),
+                   "Teilnehmer 1, Annabell und Teilnehmer 1");
+        assert_eq!(replace_words("#2256397 und 2256397", "2256397", "anonym-1"),
+                   "#2256397 und anonym-1");
+    }
+}
@@ -3,10 +3,12 @@ use chrono::{DateTime, NaiveDate, Utc};
 use super::schema::*;
 
 #[derive(Debug, Queryable, Identifiable, Associations)]
+#[belongs_to(Room)]
 pub struct Day {
     pub id: i32,
     pub name: String,
     pub year: i16,
+    pub room_id: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -16,11 +18,30 @@ pub struct NewDay {
     pub year: i16,
 }
 
+#[derive(Debug, Queryable, Identifiable)]
+pub struct Room {
+    pub id: i32,
+    pub year: i16,
+    pub name: String,
+    pub layout: String,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name="rooms"]
+pub struct NewRoom {
+    pub year: i16,
+    pub name: String,
+    #[serde(default)]
+    pub layout: String,
+}
+
 #[derive(Debug, Queryable, Identifiable, Associations)]
 pub struct Experiment {
     pub id: i32,
     pub name: String,
     pub year: i16,
+    pub position: i32,
+    pub description: String,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -28,6 +49,11 @@ pub struct Experiment {
 pub struct NewExperiment {
     pub name: String,
     pub year: i16,
+    // New experiments are appended to the end of their year
+    #[serde(skip)]
+    pub position: i32,
+    #[serde(default)]
+    pub description: String,
 }
 
 #[derive(Debug, Queryable, Insertable, AsChangeset, Identifiable, Associations)]
@@ -66,6 +92,9 @@ pub struct Student {
     pub instructed: bool,
     pub given_name: String,
     pub family_name: String,
+    pub email: Option<String>,
+    /// Matrikel of the preferred partner for forming groups
+    pub partner: Option<String>,
 }
 
 impl Student {
@@ -85,6 +114,9 @@ pub struct NewStudent {
     pub username: Option<String>,
     pub given_name: String,
     pub family_name: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub partner: Option<String>,
 }
 
 #[derive(Debug, Queryable, Insertable, Identifiable, Associations)]
@@ -103,8 +135,36 @@ pub struct Task {
     pub id: i32,
     pub experiment_id: i32,
     pub name: String,
+    pub points: i16,
+    pub weight: i16,
+    pub position: i32,
+    pub description: String,
 }
 
+#[derive(Debug, Queryable, Identifiable, Associations)]
+#[belongs_to(Experiment)]
+pub struct Attachment {
+    pub id: i32,
+    pub experiment_id: i32,
+    pub task_id: Option<i32>,
+    pub title: String,
+    pub url: Option<String>,
+    pub file: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="attachments"]
+pub struct NewAttachment<'a> {
+    pub experiment_id: i32,
+    pub task_id: Option<i32>,
+    pub title: &'a str,
+    pub url: Option<&'a str>,
+    pub file: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+}
+
+
 #[derive(Debug, Queryable, Insertable, Identifiable, Associations)]
 #[table_name="completions"]
 #[primary_key(group_id, task_id)]
@@ -113,10 +173,12 @@ pub struct Task {
 pub struct Completion {
     pub group_id: i32,
     pub task_id: i32,
+    pub points: Option<i16>,
 }
 
 #[derive(Debug, Queryable, Insertable, AsChangeset, Identifiable, Associations)]
 #[table_name="elaborations"]
+#[changeset_options(treat_none_as_null="true")]
 #[primary_key(group_id, experiment_id)]
 #[belongs_to(Group)]
 #[belongs_to(Experiment)]
@@ -125,6 +187,66 @@ pub struct Elaboration {
     pub experiment_id: i32,
     pub rework_required: bool,
     pub accepted: bool,
+    pub points: Option<i16>,
+    pub grade: Option<String>,
+    pub feedback: String,
+    pub grader: Option<String>,
+    pub submitted_on: Option<NaiveDate>,
+    pub rework_due: Option<NaiveDate>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations)]
+#[belongs_to(Group)]
+#[belongs_to(Experiment)]
+pub struct ElaborationFile {
+    pub id: i32,
+    pub group_id: i32,
+    pub experiment_id: i32,
+    pub version: i32,
+    pub filename: String,
+    pub size: i32,
+    pub uploaded_at: DateTime<Utc>,
+    pub uploader: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="elaboration_files"]
+pub struct NewElaborationFile<'a> {
+    pub group_id: i32,
+    pub experiment_id: i32,
+    pub version: i32,
+    pub filename: &'a str,
+    pub size: i32,
+    pub uploader: &'a str,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations)]
+#[belongs_to(Group)]
+#[belongs_to(Experiment)]
+pub struct ElaborationSubmission {
+    pub id: i32,
+    pub group_id: i32,
+    pub experiment_id: i32,
+    pub created_at: DateTime<Utc>,
+    pub rework_required: bool,
+    pub accepted: bool,
+    pub points: Option<i16>,
+    pub grade: Option<String>,
+    pub feedback: String,
+    pub grader: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="elaboration_submissions"]
+pub struct NewElaborationSubmission<'a> {
+    pub group_id: i32,
+    pub experiment_id: i32,
+    pub rework_required: bool,
+    pub accepted: bool,
+    pub points: Option<i16>,
+    pub grade: Option<&'a str>,
+    pub feedback: &'a str,
+    pub grader: &'a str,
 }
 
 #[derive(Debug, Queryable, Identifiable)]
@@ -146,11 +268,97 @@ pub struct NewAuditLog<'a, 'b> {
     pub change: &'b str,
 }
 
-#[derive(Debug, Queryable, Insertable, Identifiable)]
-#[table_name="years"]
+#[derive(Debug, Queryable, Identifiable)]
+pub struct Mail {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    pub year: i16,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="mails"]
+pub struct NewMail<'a> {
+    pub year: i16,
+    pub recipient: &'a str,
+    pub subject: &'a str,
+    pub body: &'a str,
+}
+
+#[derive(Debug, Queryable, Identifiable)]
+#[table_name="trash"]
+pub struct TrashEntry {
+    pub id: i32,
+    pub deleted_at: DateTime<Utc>,
+    pub year: i16,
+    pub author: String,
+    pub kind: String,
+    pub name: String,
+    pub data: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="trash"]
+pub struct NewTrashEntry<'a> {
+    pub year: i16,
+    pub author: &'a str,
+    pub kind: &'a str,
+    pub name: &'a str,
+    pub data: &'a str,
+}
+
+#[derive(Debug, Queryable, Identifiable)]
 pub struct Year {
     pub id: i16,
     pub writable: bool,
+    pub anonymized: bool,
+    pub course: String,
+    pub label: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name="years"]
+pub struct NewYear {
+    pub course: String,
+    pub label: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize, Queryable, Insertable, Identifiable)]
+#[table_name="courses"]
+pub struct Course {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Queryable, Identifiable)]
+pub struct CourseAdmin {
+    pub id: i32,
+    pub course: String,
+    pub username: String,
+}
+
+#[derive(Debug, Queryable, Insertable, Identifiable, Associations)]
+#[primary_key(day_id, tutor_id)]
+#[belongs_to(Day)]
+#[belongs_to(Tutor)]
+pub struct DayTutor {
+    pub day_id: i32,
+    pub tutor_id: i32,
+}
+
+#[derive(Debug, Queryable, Identifiable)]
+pub struct SiteAdmin {
+    pub id: i32,
+    pub username: String,
 }
 
 #[derive(Debug, Queryable, Identifiable)]
@@ -158,7 +366,7 @@ pub struct Tutor {
     pub id: i32,
     pub username: String,
     pub year: i16,
-    pub is_admin: bool,
+    pub role: String,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -166,5 +374,5 @@ pub struct Tutor {
 pub struct NewTutor {
     pub username: String,
     pub year: i16,
-    pub is_admin: bool,
+    pub role: String,
 }
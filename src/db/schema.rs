@@ -1,4 +1,16 @@
 // These table definitions can be generated using `diesel print-schema`
+table! {
+    attachments (id) {
+        id -> Int4,
+        experiment_id -> Int4,
+        task_id -> Nullable<Int4>,
+        title -> Text,
+        url -> Nullable<Text>,
+        file -> Nullable<Text>,
+        content_type -> Nullable<Text>,
+    }
+}
+
 table! {
     audit_logs (id) {
         id -> Int4,
@@ -14,6 +26,29 @@ table! {
     completions (group_id, task_id) {
         group_id -> Int4,
         task_id -> Int4,
+        points -> Nullable<Int2>,
+    }
+}
+
+table! {
+    course_admins (id) {
+        id -> Int4,
+        course -> Text,
+        username -> Text,
+    }
+}
+
+table! {
+    courses (id) {
+        id -> Text,
+        name -> Text,
+    }
+}
+
+table! {
+    day_tutors (day_id, tutor_id) {
+        day_id -> Int4,
+        tutor_id -> Int4,
     }
 }
 
@@ -22,6 +57,35 @@ table! {
         id -> Int4,
         name -> Text,
         year -> Int2,
+        room_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    elaboration_files (id) {
+        id -> Int4,
+        group_id -> Int4,
+        experiment_id -> Int4,
+        version -> Int4,
+        filename -> Text,
+        size -> Int4,
+        uploaded_at -> Timestamptz,
+        uploader -> Text,
+    }
+}
+
+table! {
+    elaboration_submissions (id) {
+        id -> Int4,
+        group_id -> Int4,
+        experiment_id -> Int4,
+        created_at -> Timestamptz,
+        rework_required -> Bool,
+        accepted -> Bool,
+        points -> Nullable<Int2>,
+        grade -> Nullable<Text>,
+        feedback -> Text,
+        grader -> Text,
     }
 }
 
@@ -31,6 +95,12 @@ table! {
         experiment_id -> Int4,
         rework_required -> Bool,
         accepted -> Bool,
+        points -> Nullable<Int2>,
+        grade -> Nullable<Text>,
+        feedback -> Text,
+        grader -> Nullable<Text>,
+        submitted_on -> Nullable<Date>,
+        rework_due -> Nullable<Date>,
     }
 }
 
@@ -47,6 +117,8 @@ table! {
         id -> Int4,
         name -> Text,
         year -> Int2,
+        position -> Int4,
+        description -> Text,
     }
 }
 
@@ -74,6 +146,36 @@ table! {
     }
 }
 
+table! {
+    mails (id) {
+        id -> Int4,
+        created_at -> Timestamptz,
+        year -> Int2,
+        recipient -> Text,
+        subject -> Text,
+        body -> Text,
+        sent_at -> Nullable<Timestamptz>,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+    }
+}
+
+table! {
+    rooms (id) {
+        id -> Int4,
+        year -> Int2,
+        name -> Text,
+        layout -> Text,
+    }
+}
+
+table! {
+    site_admins (id) {
+        id -> Int4,
+        username -> Text,
+    }
+}
+
 table! {
     students (id) {
         id -> Int4,
@@ -83,6 +185,8 @@ table! {
         instructed -> Bool,
         given_name -> Text,
         family_name -> Text,
+        email -> Nullable<Text>,
+        partner -> Nullable<Text>,
     }
 }
 
@@ -91,6 +195,22 @@ table! {
         id -> Int4,
         experiment_id -> Int4,
         name -> Text,
+        points -> Int2,
+        weight -> Int2,
+        position -> Int4,
+        description -> Text,
+    }
+}
+
+table! {
+    trash (id) {
+        id -> Int4,
+        deleted_at -> Timestamptz,
+        year -> Int2,
+        author -> Text,
+        kind -> Text,
+        name -> Text,
+        data -> Text,
     }
 }
 
@@ -99,7 +219,7 @@ table! {
         id -> Int4,
         username -> Text,
         year -> Int2,
-        is_admin -> Bool,
+        role -> Text,
     }
 }
 
@@ -107,12 +227,27 @@ table! {
     years (id) {
         id -> Int2,
         writable -> Bool,
+        anonymized -> Bool,
+        course -> Text,
+        label -> Text,
+        start_date -> Date,
+        end_date -> Date,
     }
 }
 
+joinable!(attachments -> experiments (experiment_id));
+joinable!(attachments -> tasks (task_id));
 joinable!(completions -> groups (group_id));
 joinable!(completions -> tasks (task_id));
+joinable!(course_admins -> courses (course));
+joinable!(day_tutors -> days (day_id));
+joinable!(day_tutors -> tutors (tutor_id));
+joinable!(days -> rooms (room_id));
 joinable!(days -> years (year));
+joinable!(elaboration_files -> experiments (experiment_id));
+joinable!(elaboration_files -> groups (group_id));
+joinable!(elaboration_submissions -> experiments (experiment_id));
+joinable!(elaboration_submissions -> groups (group_id));
 joinable!(elaborations -> experiments (experiment_id));
 joinable!(elaborations -> groups (group_id));
 joinable!(events -> days (day_id));
@@ -121,21 +256,35 @@ joinable!(experiments -> years (year));
 joinable!(group_mappings -> groups (group_id));
 joinable!(group_mappings -> students (student_id));
 joinable!(groups -> days (day_id));
+joinable!(mails -> years (year));
+joinable!(rooms -> years (year));
 joinable!(students -> years (year));
 joinable!(tasks -> experiments (experiment_id));
+joinable!(trash -> years (year));
+joinable!(years -> courses (course));
 
 allow_tables_to_appear_in_same_query!(
+    attachments,
     audit_logs,
     completions,
+    course_admins,
+    courses,
+    day_tutors,
     days,
+    elaboration_files,
+    elaboration_submissions,
     elaborations,
     events,
     experiments,
     group_mappings,
     groups,
     ip_whitelist,
+    mails,
+    rooms,
+    site_admins,
     students,
     tasks,
+    trash,
     tutors,
     years,
 );
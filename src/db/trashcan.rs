@@ -0,0 +1,955 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use crate::errors::*;
+use diesel::prelude::*;
+use std::collections::{HashMap, HashSet};
+use super::{attachments, completions, day_tutors, days, elaboration_files, elaboration_submissions,
+            elaborations, events, experiments, group_mappings, groups, rooms, students, tasks,
+            trash, tutors};
+use super::{Attachment, Completion, Day, DayTutor, Elaboration, ElaborationFile,
+            ElaborationSubmission, Event, Experiment, Group, NewTrashEntry, Student, Task,
+            TrashEntry};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Number of dependent entries that are deleted together with an entry
+#[derive(Debug, Default, Serialize)]
+pub struct Impact {
+    pub groups: usize,
+    /// Students removed from groups
+    pub group_mappings: usize,
+    pub tasks: usize,
+    pub completions: usize,
+    pub events: usize,
+    pub elaborations: usize,
+}
+
+/// Deleted entry including everything that depended on it
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Trashed {
+    Student {
+        id: i32,
+        matrikel: String,
+        username: Option<String>,
+        email: Option<String>,
+        instructed: bool,
+        given_name: String,
+        family_name: String,
+        #[serde(default)]
+        partner: Option<String>,
+        groups: Vec<i32>,
+    },
+    Experiment {
+        id: i32,
+        name: String,
+        #[serde(default)]
+        position: i32,
+        #[serde(default)]
+        description: String,
+        // Attachments of the experiment itself, those of tasks are kept there
+        #[serde(default)]
+        attachments: Vec<TrashedAttachment>,
+        tasks: Vec<TrashedTask>,
+        // (day, date)
+        events: Vec<(i32, String)>,
+        elaborations: Vec<TrashedElaboration>,
+        #[serde(default)]
+        files: Vec<TrashedFile>,
+    },
+    Task(TrashedTask),
+    Day {
+        id: i32,
+        name: String,
+        // (experiment, date)
+        events: Vec<(i32, String)>,
+        groups: Vec<TrashedGroup>,
+        #[serde(default)]
+        tutors: Vec<i32>,
+        #[serde(default)]
+        room: Option<i32>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedTask {
+    id: i32,
+    experiment_id: i32,
+    name: String,
+    #[serde(default)]
+    position: i32,
+    // Entries trashed before tasks were scored count every task once
+    #[serde(default = "default_score")]
+    points: i16,
+    #[serde(default = "default_score")]
+    weight: i16,
+    completions: Vec<i32>,
+    // (group, points) of completions with partial credit
+    #[serde(default)]
+    partial_completions: Vec<(i32, i16)>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    attachments: Vec<TrashedAttachment>,
+}
+
+fn default_score() -> i16 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedGroup {
+    id: i32,
+    desk: i32,
+    comment: String,
+    students: Vec<i32>,
+    completions: Vec<i32>,
+    // (task, points) of completions with partial credit
+    #[serde(default)]
+    partial_completions: Vec<(i32, i16)>,
+    elaborations: Vec<TrashedElaboration>,
+    #[serde(default)]
+    files: Vec<TrashedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedElaboration {
+    group_id: i32,
+    experiment_id: i32,
+    rework_required: bool,
+    accepted: bool,
+    // Entries trashed before elaborations were graded only know the status
+    #[serde(default)]
+    points: Option<i16>,
+    #[serde(default)]
+    grade: Option<String>,
+    #[serde(default)]
+    feedback: String,
+    #[serde(default)]
+    grader: Option<String>,
+    #[serde(default)]
+    submitted_on: Option<String>,
+    #[serde(default)]
+    rework_due: Option<String>,
+    #[serde(default)]
+    submissions: Vec<TrashedSubmission>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedSubmission {
+    created_at: String,
+    rework_required: bool,
+    accepted: bool,
+    points: Option<i16>,
+    grade: Option<String>,
+    feedback: String,
+    grader: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedAttachment {
+    id: i32,
+    title: String,
+    url: Option<String>,
+    file: Option<String>,
+    content_type: Option<String>,
+}
+
+impl From<Attachment> for TrashedAttachment {
+    fn from(attachment: Attachment) -> TrashedAttachment {
+        TrashedAttachment {
+            id: attachment.id,
+            title: attachment.title,
+            url: attachment.url,
+            file: attachment.file,
+            content_type: attachment.content_type,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrashedFile {
+    id: i32,
+    group_id: i32,
+    experiment_id: i32,
+    version: i32,
+    filename: String,
+    size: i32,
+    uploaded_at: String,
+    uploader: String,
+}
+
+impl From<ElaborationFile> for TrashedFile {
+    fn from(file: ElaborationFile) -> TrashedFile {
+        TrashedFile {
+            id: file.id,
+            group_id: file.group_id,
+            experiment_id: file.experiment_id,
+            version: file.version,
+            filename: file.filename,
+            size: file.size,
+            uploaded_at: file.uploaded_at.to_rfc3339(),
+            uploader: file.uploader,
+        }
+    }
+}
+
+// Collect the elaborations including their history of submissions
+fn collect_elaborations(elaborations: Vec<Elaboration>, conn: &PgConnection) -> Result<Vec<TrashedElaboration>> {
+    let mut trashed = Vec::with_capacity(elaborations.len());
+    for elaboration in elaborations {
+        let submissions = elaboration_submissions::table
+            .filter(elaboration_submissions::group_id.eq(elaboration.group_id))
+            .filter(elaboration_submissions::experiment_id.eq(elaboration.experiment_id))
+            .order(elaboration_submissions::id.asc())
+            .load::<ElaborationSubmission>(conn)?
+            .into_iter()
+            .map(|submission| TrashedSubmission {
+                created_at: submission.created_at.to_rfc3339(),
+                rework_required: submission.rework_required,
+                accepted: submission.accepted,
+                points: submission.points,
+                grade: submission.grade,
+                feedback: submission.feedback,
+                grader: submission.grader,
+            })
+            .collect();
+
+        trashed.push(TrashedElaboration {
+            group_id: elaboration.group_id,
+            experiment_id: elaboration.experiment_id,
+            rework_required: elaboration.rework_required,
+            accepted: elaboration.accepted,
+            points: elaboration.points,
+            grade: elaboration.grade,
+            feedback: elaboration.feedback,
+            grader: elaboration.grader,
+            submitted_on: elaboration.submitted_on.map(|date| date.format(DATE_FORMAT).to_string()),
+            rework_due: elaboration.rework_due.map(|date| date.format(DATE_FORMAT).to_string()),
+            submissions: submissions,
+        });
+    }
+
+    Ok(trashed)
+}
+
+impl Trashed {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Trashed::Student { .. } => "student",
+            Trashed::Experiment { .. } => "experiment",
+            Trashed::Task(_) => "task",
+            Trashed::Day { .. } => "day",
+        }
+    }
+
+    fn impact(&self) -> Impact {
+        match self {
+            Trashed::Student { groups, .. } => Impact {
+                group_mappings: groups.len(),
+                ..Impact::default()
+            },
+            Trashed::Experiment { tasks, events, elaborations, .. } => Impact {
+                tasks: tasks.len(),
+                completions: tasks.iter().map(|task| task.completions.len()).sum(),
+                events: events.len(),
+                elaborations: elaborations.len(),
+                ..Impact::default()
+            },
+            Trashed::Task(task) => Impact {
+                completions: task.completions.len(),
+                ..Impact::default()
+            },
+            Trashed::Day { events, groups, .. } => Impact {
+                groups: groups.len(),
+                group_mappings: groups.iter().map(|group| group.students.len()).sum(),
+                completions: groups.iter().map(|group| group.completions.len()).sum(),
+                events: events.len(),
+                elaborations: groups.iter().map(|group| group.elaborations.len()).sum(),
+                ..Impact::default()
+            },
+        }
+    }
+}
+
+// Load the subset of the given ids that still exist in the given table
+macro_rules! existing {
+    ($table:ident, $ids:expr, $conn:expr) => {
+        $table::table
+            .filter($table::id.eq_any($ids))
+            .select($table::id)
+            .load::<i32>($conn)?
+            .into_iter()
+            .collect::<HashSet<i32>>()
+    }
+}
+
+// Fails with `ErrorKind::InProgress` if one of the groups has completed a
+// task or handed in an elaboration
+fn ensure_no_progress(groups: &[i32], conn: &PgConnection) -> Result<()> {
+    let completed: i64 = completions::table
+        .filter(completions::group_id.eq_any(groups))
+        .count()
+        .get_result(conn)?;
+    let elaborated: i64 = elaborations::table
+        .filter(elaborations::group_id.eq_any(groups))
+        .count()
+        .get_result(conn)?;
+
+    match completed + elaborated {
+        0 => Ok(()),
+        _ => Err(ErrorKind::InProgress.into()),
+    }
+}
+
+/// Move the student and its group mappings into the trash
+///
+/// Fails with `ErrorKind::InProgress` if one of the groups of the student has
+/// already made progress. Only returns what would be affected without changing
+/// anything if `dry_run` is set.
+///
+/// Should be run inside a transaction.
+pub fn trash_student(student: &Student, author: &str, dry_run: bool, conn: &PgConnection) -> Result<Impact> {
+    let trashed = Trashed::Student {
+        id: student.id,
+        matrikel: student.matrikel.clone(),
+        username: student.username.clone(),
+        email: student.email.clone(),
+        instructed: student.instructed,
+        given_name: student.given_name.clone(),
+        family_name: student.family_name.clone(),
+        partner: student.partner.clone(),
+        groups: group_mappings::table
+            .filter(group_mappings::student_id.eq(student.id))
+            .select(group_mappings::group_id)
+            .load::<i32>(conn)?,
+    };
+    if let Trashed::Student { ref groups, .. } = trashed {
+        ensure_no_progress(groups, conn)?;
+    }
+    if dry_run {
+        return Ok(trashed.impact());
+    }
+
+    diesel::delete(group_mappings::table
+        .filter(group_mappings::student_id.eq(student.id)))
+        .execute(conn)?;
+    diesel::delete(students::table.find(student.id))
+        .execute(conn)?;
+
+    // The matrikel tells students of the same name apart and finds the entry
+    // in the audit log for the export of the student
+    let name = format!("{} ({})", student.name(), student.matrikel);
+    insert(student.year, author, &name, &trashed, conn)
+}
+
+/// Move the task and all its completions into the trash
+///
+/// Only returns what would be affected without changing anything if
+/// `dry_run` is set.
+///
+/// Should be run inside a transaction.
+pub fn trash_task(task: &Task, year: i16, author: &str, dry_run: bool, conn: &PgConnection) -> Result<Impact> {
+    let trashed = Trashed::Task(collect_task(task, conn)?);
+    if dry_run {
+        return Ok(trashed.impact());
+    }
+
+    delete_task(task.id, conn)?;
+
+    insert(year, author, &task.name, &trashed, conn)
+}
+
+/// Move the experiment into the trash including its tasks, completions,
+/// events and elaborations
+///
+/// Only returns what would be affected without changing anything if
+/// `dry_run` is set.
+///
+/// Should be run inside a transaction.
+pub fn trash_experiment(experiment: &Experiment, author: &str, dry_run: bool, conn: &PgConnection) -> Result<Impact> {
+    let db_tasks = tasks::table
+        .filter(tasks::experiment_id.eq(experiment.id))
+        .order((tasks::position.asc(), tasks::id.asc()))
+        .load::<Task>(conn)?;
+
+    let trashed = Trashed::Experiment {
+        id: experiment.id,
+        name: experiment.name.clone(),
+        position: experiment.position,
+        description: experiment.description.clone(),
+        attachments: attachments::table
+            .filter(attachments::experiment_id.eq(experiment.id))
+            .filter(attachments::task_id.is_null())
+            .load::<Attachment>(conn)?
+            .into_iter()
+            .map(TrashedAttachment::from)
+            .collect(),
+        tasks: db_tasks.iter()
+            .map(|task| collect_task(task, conn))
+            .collect::<Result<Vec<_>>>()?,
+        events: events::table
+            .filter(events::experiment_id.eq(experiment.id))
+            .load::<Event>(conn)?
+            .into_iter()
+            .map(|event| (event.day_id, event.date.format(DATE_FORMAT).to_string()))
+            .collect(),
+        elaborations: collect_elaborations(elaborations::table
+            .filter(elaborations::experiment_id.eq(experiment.id))
+            .load::<Elaboration>(conn)?, conn)?,
+        files: elaboration_files::table
+            .filter(elaboration_files::experiment_id.eq(experiment.id))
+            .load::<ElaborationFile>(conn)?
+            .into_iter()
+            .map(TrashedFile::from)
+            .collect(),
+    };
+    if dry_run {
+        return Ok(trashed.impact());
+    }
+
+    for task in db_tasks {
+        delete_task(task.id, conn)?;
+    }
+    diesel::delete(attachments::table
+        .filter(attachments::experiment_id.eq(experiment.id)))
+        .execute(conn)?;
+    diesel::delete(events::table
+        .filter(events::experiment_id.eq(experiment.id)))
+        .execute(conn)?;
+    diesel::delete(elaboration_files::table
+        .filter(elaboration_files::experiment_id.eq(experiment.id)))
+        .execute(conn)?;
+    diesel::delete(elaboration_submissions::table
+        .filter(elaboration_submissions::experiment_id.eq(experiment.id)))
+        .execute(conn)?;
+    diesel::delete(elaborations::table
+        .filter(elaborations::experiment_id.eq(experiment.id)))
+        .execute(conn)?;
+    diesel::delete(experiments::table.find(experiment.id))
+        .execute(conn)?;
+
+    insert(experiment.year, author, &experiment.name, &trashed, conn)
+}
+
+/// Move the day into the trash including its events and groups
+///
+/// Fails with `ErrorKind::InProgress` if one of the groups of the day has
+/// already made progress. Only returns what would be affected without changing
+/// anything if `dry_run` is set.
+///
+/// Should be run inside a transaction.
+pub fn trash_day(day: &Day, author: &str, dry_run: bool, conn: &PgConnection) -> Result<Impact> {
+    let db_groups = groups::table
+        .filter(groups::day_id.eq(day.id))
+        .order(groups::id.asc())
+        .load::<Group>(conn)?;
+    let group_ids: Vec<_> = db_groups.iter().map(|group| group.id).collect();
+    ensure_no_progress(&group_ids, conn)?;
+
+    let mut trashed_groups = Vec::with_capacity(db_groups.len());
+    for group in db_groups {
+        let db_completions = completions::table
+            .filter(completions::group_id.eq(group.id))
+            .load::<Completion>(conn)?;
+
+        trashed_groups.push(TrashedGroup {
+            id: group.id,
+            desk: group.desk,
+            comment: group.comment,
+            students: group_mappings::table
+                .filter(group_mappings::group_id.eq(group.id))
+                .select(group_mappings::student_id)
+                .load(conn)?,
+            completions: db_completions.iter()
+                .map(|completion| completion.task_id)
+                .collect(),
+            partial_completions: db_completions.iter()
+                .filter_map(|completion| completion.points
+                    .map(|points| (completion.task_id, points)))
+                .collect(),
+            elaborations: collect_elaborations(elaborations::table
+                .filter(elaborations::group_id.eq(group.id))
+                .load::<Elaboration>(conn)?, conn)?,
+            files: elaboration_files::table
+                .filter(elaboration_files::group_id.eq(group.id))
+                .load::<ElaborationFile>(conn)?
+                .into_iter()
+                .map(TrashedFile::from)
+                .collect(),
+        });
+    }
+
+    let trashed = Trashed::Day {
+        id: day.id,
+        name: day.name.clone(),
+        events: events::table
+            .filter(events::day_id.eq(day.id))
+            .load::<Event>(conn)?
+            .into_iter()
+            .map(|event| (event.experiment_id, event.date.format(DATE_FORMAT).to_string()))
+            .collect(),
+        groups: trashed_groups,
+        tutors: day_tutors::table
+            .filter(day_tutors::day_id.eq(day.id))
+            .select(day_tutors::tutor_id)
+            .load(conn)?,
+        room: day.room_id,
+    };
+    if dry_run {
+        return Ok(trashed.impact());
+    }
+
+    for group in group_ids {
+        super::delete_group(group, conn)?;
+    }
+    diesel::delete(events::table
+        .filter(events::day_id.eq(day.id)))
+        .execute(conn)?;
+    diesel::delete(day_tutors::table
+        .filter(day_tutors::day_id.eq(day.id)))
+        .execute(conn)?;
+    diesel::delete(days::table.find(day.id))
+        .execute(conn)?;
+
+    insert(day.year, author, &day.name, &trashed, conn)
+}
+
+fn collect_task(task: &Task, conn: &PgConnection) -> Result<TrashedTask> {
+    let db_completions = completions::table
+        .filter(completions::task_id.eq(task.id))
+        .load::<Completion>(conn)?;
+
+    Ok(TrashedTask {
+        id: task.id,
+        experiment_id: task.experiment_id,
+        name: task.name.clone(),
+        position: task.position,
+        points: task.points,
+        weight: task.weight,
+        completions: db_completions.iter()
+            .map(|completion| completion.group_id)
+            .collect(),
+        partial_completions: db_completions.iter()
+            .filter_map(|completion| completion.points
+                .map(|points| (completion.group_id, points)))
+            .collect(),
+        description: task.description.clone(),
+        attachments: attachments::table
+            .filter(attachments::task_id.eq(task.id))
+            .load::<Attachment>(conn)?
+            .into_iter()
+            .map(TrashedAttachment::from)
+            .collect(),
+    })
+}
+
+fn delete_task(task: i32, conn: &PgConnection) -> Result<()> {
+    diesel::delete(attachments::table
+        .filter(attachments::task_id.eq(task)))
+        .execute(conn)?;
+    diesel::delete(completions::table
+        .filter(completions::task_id.eq(task)))
+        .execute(conn)?;
+    diesel::delete(tasks::table.find(task))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Store the trashed entry and return its impact
+fn insert(year: i16, author: &str, name: &str, trashed: &Trashed, conn: &PgConnection) -> Result<Impact> {
+    let data = serde_json::to_string(trashed)?;
+
+    diesel::insert_into(trash::table)
+        .values(&NewTrashEntry {
+            year: year,
+            author: author,
+            kind: trashed.kind(),
+            name: name,
+            data: &data,
+        })
+        .execute(conn)?;
+
+    Ok(trashed.impact())
+}
+
+/// Restore the trashed entry and remove it from the trash
+///
+/// References to entries that were deleted in the meantime are dropped.
+/// Returns `false` without changing anything if the entry cannot be
+/// restored, because its experiment no longer exists or a task with the
+/// same name was created in the meantime.
+///
+/// Should be run inside a transaction.
+pub fn restore_trash(entry: &TrashEntry, conn: &PgConnection) -> Result<bool> {
+    let trashed: Trashed = serde_json::from_str(&entry.data)?;
+
+    match trashed {
+        Trashed::Student { id, matrikel, username, email, instructed, given_name, family_name, partner, groups: group_ids } => {
+            diesel::insert_into(students::table)
+                .values((
+                    students::id.eq(id),
+                    students::matrikel.eq(matrikel),
+                    students::year.eq(entry.year),
+                    students::username.eq(username),
+                    students::instructed.eq(instructed),
+                    students::given_name.eq(given_name),
+                    students::family_name.eq(family_name),
+                    students::email.eq(email),
+                    students::partner.eq(partner),
+                ))
+                .execute(conn)?;
+
+            let existing_groups = existing!(groups, &group_ids, conn);
+            for group in group_ids.into_iter().filter(|group| existing_groups.contains(group)) {
+                diesel::insert_into(group_mappings::table)
+                    .values((
+                        group_mappings::student_id.eq(id),
+                        group_mappings::group_id.eq(group),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+        Trashed::Task(task) => {
+            let experiment_exists = !existing!(experiments, &[task.experiment_id][..], conn).is_empty();
+            let name_taken: i64 = tasks::table
+                .filter(tasks::experiment_id.eq(task.experiment_id))
+                .filter(tasks::name.eq(&task.name))
+                .count()
+                .get_result(conn)?;
+            if !experiment_exists || name_taken > 0 {
+                return Ok(false);
+            }
+
+            restore_task(task, conn)?;
+        }
+        Trashed::Experiment { id, name, position, description, attachments, tasks, events, elaborations, files } => {
+            diesel::insert_into(experiments::table)
+                .values((
+                    experiments::id.eq(id),
+                    experiments::name.eq(name),
+                    experiments::year.eq(entry.year),
+                    experiments::position.eq(position),
+                    experiments::description.eq(description),
+                ))
+                .execute(conn)?;
+            restore_attachments(id, None, attachments, conn)?;
+
+            for task in tasks {
+                restore_task(task, conn)?;
+            }
+
+            let day_ids: Vec<_> = events.iter().map(|&(day, _)| day).collect();
+            let existing_days = existing!(days, &day_ids, conn);
+            for (day, date) in events.into_iter().filter(|(day, _)| existing_days.contains(day)) {
+                diesel::insert_into(events::table)
+                    .values(&Event {
+                        day_id: day,
+                        experiment_id: id,
+                        date: parse_date(&date)?,
+                    })
+                    .execute(conn)?;
+            }
+
+            restore_elaborations(elaborations, conn)?;
+            restore_files(files, conn)?;
+        }
+        Trashed::Day { id, name, events, groups: trashed_groups, tutors: day_tutor_ids, room } => {
+            // The room may have been deleted in the meantime
+            let room = match room {
+                Some(room) if existing!(rooms, &vec![room], conn).contains(&room) => Some(room),
+                _ => None,
+            };
+            diesel::insert_into(days::table)
+                .values((
+                    days::id.eq(id),
+                    days::name.eq(name),
+                    days::year.eq(entry.year),
+                    days::room_id.eq(room),
+                ))
+                .execute(conn)?;
+
+            let experiment_ids: Vec<_> = events.iter().map(|&(experiment, _)| experiment).collect();
+            let existing_experiments = existing!(experiments, &experiment_ids, conn);
+            for (experiment, date) in events.into_iter().filter(|(experiment, _)| existing_experiments.contains(experiment)) {
+                diesel::insert_into(events::table)
+                    .values(&Event {
+                        day_id: id,
+                        experiment_id: experiment,
+                        date: parse_date(&date)?,
+                    })
+                    .execute(conn)?;
+            }
+
+            for group in trashed_groups {
+                diesel::insert_into(groups::table)
+                    .values((
+                        groups::id.eq(group.id),
+                        groups::desk.eq(group.desk),
+                        groups::day_id.eq(id),
+                        groups::comment.eq(group.comment),
+                    ))
+                    .execute(conn)?;
+
+                let existing_students = existing!(students, &group.students, conn);
+                for student in group.students.into_iter().filter(|student| existing_students.contains(student)) {
+                    diesel::insert_into(group_mappings::table)
+                        .values((
+                            group_mappings::student_id.eq(student),
+                            group_mappings::group_id.eq(group.id),
+                        ))
+                        .execute(conn)?;
+                }
+
+                let partial_completions: HashMap<_,_> = group.partial_completions.into_iter().collect();
+                let existing_tasks = existing!(tasks, &group.completions, conn);
+                for task in group.completions.into_iter().filter(|task| existing_tasks.contains(task)) {
+                    diesel::insert_into(completions::table)
+                        .values(&Completion {
+                            group_id: group.id,
+                            task_id: task,
+                            points: partial_completions.get(&task).cloned(),
+                        })
+                        .execute(conn)?;
+                }
+
+                restore_elaborations(group.elaborations, conn)?;
+                restore_files(group.files, conn)?;
+            }
+
+            let existing_tutors = existing!(tutors, &day_tutor_ids, conn);
+            for tutor in day_tutor_ids.into_iter().filter(|tutor| existing_tutors.contains(tutor)) {
+                diesel::insert_into(day_tutors::table)
+                    .values(&DayTutor {
+                        day_id: id,
+                        tutor_id: tutor,
+                    })
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    diesel::delete(trash::table.find(entry.id))
+        .execute(conn)?;
+
+    Ok(true)
+}
+
+fn restore_task(task: TrashedTask, conn: &PgConnection) -> Result<()> {
+    diesel::insert_into(tasks::table)
+        .values((
+            tasks::id.eq(task.id),
+            tasks::experiment_id.eq(task.experiment_id),
+            tasks::name.eq(task.name),
+            tasks::position.eq(task.position),
+            tasks::points.eq(task.points),
+            tasks::weight.eq(task.weight),
+            tasks::description.eq(task.description),
+        ))
+        .execute(conn)?;
+    restore_attachments(task.experiment_id, Some(task.id), task.attachments, conn)?;
+
+    let partial_completions: HashMap<_,_> = task.partial_completions.into_iter().collect();
+    let existing_groups = existing!(groups, &task.completions, conn);
+    for group in task.completions.into_iter().filter(|group| existing_groups.contains(group)) {
+        diesel::insert_into(completions::table)
+            .values(&Completion {
+                group_id: group,
+                task_id: task.id,
+                points: partial_completions.get(&group).cloned(),
+            })
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+fn restore_attachments(experiment: i32, task: Option<i32>, attachments: Vec<TrashedAttachment>, conn: &PgConnection) -> Result<()> {
+    for attachment in attachments {
+        diesel::insert_into(attachments::table)
+            .values((
+                attachments::id.eq(attachment.id),
+                attachments::experiment_id.eq(experiment),
+                attachments::task_id.eq(task),
+                attachments::title.eq(attachment.title),
+                attachments::url.eq(attachment.url),
+                attachments::file.eq(attachment.file),
+                attachments::content_type.eq(attachment.content_type),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+fn restore_elaborations(elaborations: Vec<TrashedElaboration>, conn: &PgConnection) -> Result<()> {
+    let group_ids: Vec<_> = elaborations.iter().map(|e| e.group_id).collect();
+    let experiment_ids: Vec<_> = elaborations.iter().map(|e| e.experiment_id).collect();
+    let existing_groups = existing!(groups, &group_ids, conn);
+    let existing_experiments = existing!(experiments, &experiment_ids, conn);
+
+    for elaboration in elaborations {
+        if !existing_groups.contains(&elaboration.group_id)
+            || !existing_experiments.contains(&elaboration.experiment_id) {
+            continue;
+        }
+
+        diesel::insert_into(elaborations::table)
+            .values(&Elaboration {
+                group_id: elaboration.group_id,
+                experiment_id: elaboration.experiment_id,
+                rework_required: elaboration.rework_required,
+                accepted: elaboration.accepted,
+                points: elaboration.points,
+                grade: elaboration.grade,
+                feedback: elaboration.feedback,
+                grader: elaboration.grader,
+                submitted_on: elaboration.submitted_on
+                    .map(|date| parse_date(&date))
+                    .transpose()?,
+                rework_due: elaboration.rework_due
+                    .map(|date| parse_date(&date))
+                    .transpose()?,
+            })
+            .execute(conn)?;
+
+        for submission in elaboration.submissions {
+            let created_at = DateTime::parse_from_rfc3339(&submission.created_at)
+                .chain_err(|| format!("Invalid timestamp {} in trash", submission.created_at))?;
+
+            diesel::insert_into(elaboration_submissions::table)
+                .values((
+                    elaboration_submissions::group_id.eq(elaboration.group_id),
+                    elaboration_submissions::experiment_id.eq(elaboration.experiment_id),
+                    elaboration_submissions::created_at.eq(created_at.with_timezone(&Utc)),
+                    elaboration_submissions::rework_required.eq(submission.rework_required),
+                    elaboration_submissions::accepted.eq(submission.accepted),
+                    elaboration_submissions::points.eq(submission.points),
+                    elaboration_submissions::grade.eq(submission.grade),
+                    elaboration_submissions::feedback.eq(submission.feedback),
+                    elaboration_submissions::grader.eq(submission.grader),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Uploaded files stay on disk until they are purged together with the trash
+fn restore_files(files: Vec<TrashedFile>, conn: &PgConnection) -> Result<()> {
+    let group_ids: Vec<_> = files.iter().map(|file| file.group_id).collect();
+    let experiment_ids: Vec<_> = files.iter().map(|file| file.experiment_id).collect();
+    let existing_groups = existing!(groups, &group_ids, conn);
+    let existing_experiments = existing!(experiments, &experiment_ids, conn);
+
+    for file in files {
+        if !existing_groups.contains(&file.group_id)
+            || !existing_experiments.contains(&file.experiment_id) {
+            continue;
+        }
+
+        let uploaded_at = DateTime::parse_from_rfc3339(&file.uploaded_at)
+            .chain_err(|| format!("Invalid timestamp {} in trash", file.uploaded_at))?;
+
+        diesel::insert_into(elaboration_files::table)
+            .values((
+                elaboration_files::id.eq(file.id),
+                elaboration_files::group_id.eq(file.group_id),
+                elaboration_files::experiment_id.eq(file.experiment_id),
+                elaboration_files::version.eq(file.version),
+                elaboration_files::filename.eq(file.filename),
+                elaboration_files::size.eq(file.size),
+                elaboration_files::uploaded_at.eq(uploaded_at.with_timezone(&Utc)),
+                elaboration_files::uploader.eq(file.uploader),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Finally delete all trash entries older than the given number of days
+pub fn purge_trash(retention_days: i64, conn: &PgConnection) -> Result<usize> {
+    let limit = Utc::now() - Duration::days(retention_days);
+
+    Ok(diesel::delete(trash::table
+        .filter(trash::deleted_at.lt(limit)))
+        .execute(conn)?)
+}
+
+/// Load the ids of all uploaded files referenced by entries in the trash
+pub fn trashed_file_ids(conn: &PgConnection) -> Result<HashSet<i32>> {
+    let mut ids = HashSet::new();
+    for data in trash::table.select(trash::data).load::<String>(conn)? {
+        match serde_json::from_str(&data)? {
+            Trashed::Experiment { files, .. } => {
+                ids.extend(files.iter().map(|file| file.id));
+            }
+            Trashed::Day { groups, .. } => {
+                ids.extend(groups.iter().flat_map(|group| &group.files).map(|file| file.id));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Load the students of the year that are in the trash
+pub fn trashed_students(year: i16, conn: &PgConnection) -> Result<Vec<Student>> {
+    let mut students = vec![];
+    for data in trash::table.filter(trash::year.eq(year)).select(trash::data).load::<String>(conn)? {
+        if let Trashed::Student { id, matrikel, username, email, instructed, given_name, family_name, partner, .. } = serde_json::from_str(&data)? {
+            students.push(Student { id, matrikel, year, username, instructed, given_name, family_name, email, partner });
+        }
+    }
+
+    Ok(students)
+}
+
+/// Load the entries in the trash of the year that contain the given student,
+/// either the student itself or a day with one of the groups of the student
+pub fn trash_entries_of_student(year: i16, student: i32, conn: &PgConnection) -> Result<Vec<TrashEntry>> {
+    let mut entries = vec![];
+    for entry in trash::table.filter(trash::year.eq(year)).order(trash::id.asc()).load::<TrashEntry>(conn)? {
+        let contained = match serde_json::from_str(&entry.data)? {
+            Trashed::Student { id, .. } => id == student,
+            Trashed::Day { groups, .. } => groups.iter().any(|group| group.students.contains(&student)),
+            _ => false,
+        };
+        if contained {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Load the names of all attached files referenced by entries in the trash
+pub fn trashed_attachment_files(conn: &PgConnection) -> Result<HashSet<String>> {
+    let mut files = HashSet::new();
+    let mut collect = |attachments: Vec<TrashedAttachment>| {
+        files.extend(attachments.into_iter().filter_map(|attachment| attachment.file));
+    };
+
+    for data in trash::table.select(trash::data).load::<String>(conn)? {
+        match serde_json::from_str(&data)? {
+            Trashed::Experiment { attachments, tasks, .. } => {
+                collect(attachments);
+                for task in tasks {
+                    collect(task.attachments);
+                }
+            }
+            Trashed::Task(task) => collect(task.attachments),
+            _ => {}
+        }
+    }
+
+    Ok(files)
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .chain_err(|| format!("Invalid date {} in trash", date))
+}
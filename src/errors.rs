@@ -9,6 +9,8 @@ error_chain!{
 
     errors {
         AmbiguousDate {}
+        DateOutOfRange {}
+        InProgress {}
     }
 }
 
@@ -16,6 +18,8 @@ error_chain!{
 pub enum ApiError {
     /// 422 Unprocessable Entity
     ConstraintViolation,
+    /// 422 Unprocessable Entity with a message for the user
+    Invalid(String),
     /// 423 Locked
     Locked,
     /// 500 Internal Server Error (default)
@@ -48,14 +52,20 @@ impl From<::diesel::result::Error> for ApiError {
     }
 }
 
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::request::Request;
 use rocket::response::{self, Response, Responder};
+use std::io::Cursor;
 
 impl<'r> Responder<'r> for ApiError {
     fn respond_to(self, req: &Request) -> response::Result<'r> {
         match self {
             ApiError::ConstraintViolation => Response::build().status(Status::UnprocessableEntity).ok(),
+            ApiError::Invalid(message) => Response::build()
+                .status(Status::UnprocessableEntity)
+                .header(ContentType::Plain)
+                .sized_body(Cursor::new(message))
+                .ok(),
             ApiError::Locked => Response::build().status(Status::Locked).ok(),
             ApiError::Other(e) => Err::<(), _>(e).respond_to(req), // generic error responder
         }
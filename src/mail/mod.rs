@@ -0,0 +1,200 @@
+use chrono::Utc;
+use crate::db;
+use crate::errors::*;
+use diesel::prelude::*;
+use lettre::{SendableEmail, SmtpClient, Transport as _};
+use lettre::smtp::authentication::Credentials;
+use lettre_email::EmailBuilder;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use tera::Tera;
+
+// Time between checks of the queue for unsent mails
+const QUEUE_INTERVAL: Duration = Duration::from_secs(30);
+// Give up sending a mail after this many failed attempts
+const MAX_ATTEMPTS: i32 = 5;
+
+#[derive(Clone)]
+pub enum Transport {
+    Smtp {
+        host: String,
+        credentials: Option<(String, String)>,
+    },
+    /// Deliver all mails into a local maildir (useful for testing)
+    Maildir(PathBuf),
+}
+
+#[derive(Clone)]
+pub struct Config {
+    from: String,
+    transport: Transport,
+}
+
+/// Renders and enqueues mails, does nothing if mails are disabled
+pub struct Mailer {
+    templates: Option<Tera>,
+}
+
+/// Read the mail configuration from `Rocket.toml`
+///
+/// Returns `None` if no `mail_transport` is configured.
+pub fn config(config: &rocket::Config) -> Result<Option<Config>> {
+    let transport = match config.get_str("mail_transport") {
+        Ok(transport) => transport,
+        Err(_) => return Ok(None),
+    };
+
+    let from = config.get_str("mail_from")
+        .chain_err(|| "mail_from not specified")?
+        .to_string();
+
+    let transport = match transport {
+        "smtp" => {
+            let host = config.get_str("smtp_host")
+                .chain_err(|| "smtp_host not specified")?
+                .to_string();
+            let credentials = match (config.get_str("smtp_username"), config.get_str("smtp_password")) {
+                (Ok(username), Ok(password)) => Some((username.to_string(), password.to_string())),
+                _ => None,
+            };
+
+            Transport::Smtp { host, credentials }
+        }
+        "maildir" => {
+            let directory = config.get_str("mail_directory")
+                .chain_err(|| "mail_directory not specified")?;
+
+            Transport::Maildir(directory.into())
+        }
+        transport => return Err(format!("Unknown mail_transport {}", transport).into()),
+    };
+
+    Ok(Some(Config { from, transport }))
+}
+
+impl Mailer {
+    /// Load the mail templates from `templates/mail/*.txt` if mails are enabled
+    pub fn new(config: Option<&Config>, template_dir: &str) -> Result<Mailer> {
+        let templates = match config {
+            Some(_) => Some(Tera::new(&format!("{}/mail/*.txt", template_dir))
+                .chain_err(|| "Could not load mail templates")?),
+            None => None,
+        };
+
+        Ok(Mailer { templates })
+    }
+
+    /// Render the given template and add one mail per recipient to the queue
+    ///
+    /// The first line of the rendered template is used as the subject.
+    ///
+    /// Should be run inside a transaction.
+    pub fn enqueue<T: Serialize>(&self, year: i16, recipients: &[String], template: &str,
+                                 context: &T, conn: &PgConnection) -> Result<()> {
+        let templates = match self.templates.as_ref() {
+            Some(templates) => templates,
+            None => return Ok(()),
+        };
+
+        let rendered = templates.render(template, context)
+            .chain_err(|| format!("Could not render mail template {}", template))?;
+        let mut lines = rendered.splitn(2, '\n');
+        let subject = lines.next().unwrap_or_default().trim();
+        let body = lines.next().unwrap_or_default().trim_start();
+
+        for recipient in recipients {
+            diesel::insert_into(db::mails::table)
+                .values(&db::NewMail { year, recipient, subject, body })
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Start a background thread that sends all queued mails
+pub fn spawn_worker(config: Config, pool: db::Pool) {
+    thread::spawn(move || {
+        loop {
+            if let Err(error) = send_queued(&config, &pool) {
+                eprintln!("Error while sending mails: {}", error);
+            }
+
+            thread::sleep(QUEUE_INTERVAL);
+        }
+    });
+}
+
+fn send_queued(config: &Config, pool: &db::Pool) -> Result<()> {
+    let conn = pool.get().chain_err(|| "Could not get DB connection")?;
+
+    let mails = db::mails::table
+        .filter(db::mails::sent_at.is_null())
+        .filter(db::mails::attempts.lt(MAX_ATTEMPTS))
+        .order(db::mails::id.asc())
+        .load::<db::Mail>(&*conn)?;
+
+    for mail in mails {
+        let target = db::mails::table.find(mail.id);
+
+        match send(config, &mail) {
+            Ok(()) => diesel::update(target)
+                .set(db::mails::sent_at.eq(Utc::now()))
+                .execute(&*conn)?,
+            Err(error) => diesel::update(target)
+                .set((
+                    db::mails::attempts.eq(db::mails::attempts + 1),
+                    db::mails::last_error.eq(error.to_string()),
+                ))
+                .execute(&*conn)?,
+        };
+    }
+
+    Ok(())
+}
+
+fn send(config: &Config, mail: &db::Mail) -> Result<()> {
+    let email: SendableEmail = EmailBuilder::new()
+        .to(mail.recipient.as_str())
+        .from(config.from.as_str())
+        .subject(mail.subject.as_str())
+        .text(mail.body.as_str())
+        .build()
+        .map_err(|e| format!("Could not build mail: {}", e))?
+        .into();
+
+    match config.transport {
+        Transport::Smtp { ref host, ref credentials } => {
+            let mut client = SmtpClient::new_simple(host)
+                .map_err(|e| format!("Could not create SMTP client: {}", e))?;
+            if let Some((username, password)) = credentials {
+                client = client.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+
+            client.transport().send(email)
+                .map_err(|e| format!("Could not send mail: {}", e))?;
+        }
+        Transport::Maildir(ref directory) => {
+            // Write into tmp/ first and then move into new/ as required by
+            // the maildir format to avoid reading incomplete mails
+            let name = format!("{}.{}.hwpb", Utc::now().timestamp(), mail.id);
+            let tmp = directory.join("tmp").join(&name);
+            fs::create_dir_all(directory.join("tmp"))
+                .and_then(|_| fs::create_dir_all(directory.join("new")))
+                .chain_err(|| "Could not create maildir")?;
+
+            let message = email.message_to_string()
+                .map_err(|e| format!("Could not format mail: {}", e))?;
+            File::create(&tmp)
+                .and_then(|mut file| file.write_all(message.as_bytes()))
+                .and_then(|_| fs::rename(&tmp, directory.join("new").join(&name)))
+                .chain_err(|| "Could not write mail to maildir")?;
+        }
+    }
+
+    Ok(())
+}
@@ -9,6 +9,7 @@
 
 mod db;
 mod errors;
+mod mail;
 mod user;
 mod web;
 
@@ -47,10 +48,36 @@ fn run() -> Result<()> {
     let (push_url, listen_addr) = push::parameters(rocket.config())?;
     push::SERVER.spawn(listen_addr);
 
+    let pool = db::init_pool(&database_url)?;
+
+    // add the configured site admins on first run
+    web::session::bootstrap_site_admins(rocket.config(),
+        &*pool.get().chain_err(|| "Could not get DB connection")?)?;
+
+    // regularly remove expired entries from the trash (default is after 30
+    // days) and uploaded elaborations that are no longer referenced
+    let trash_retention = rocket.config().get_int("trash_retention_days")
+        .unwrap_or(30);
+    let uploads = web::upload::config(rocket.config())?;
+    web::admin::spawn_purger(trash_retention, uploads.clone(), pool.clone());
+
+    // load mail templates and start sending queued mails if enabled
+    let mail_config = mail::config(rocket.config())?;
+    let template_dir = rocket.config().get_str("template_dir")
+        .unwrap_or("templates")
+        .to_owned();
+    let mailer = mail::Mailer::new(mail_config.as_ref(), &template_dir)?;
+    if let Some(mail_config) = mail_config {
+        mail::spawn_worker(mail_config, pool.clone());
+    }
+
     rocket
-        .manage(db::init_pool(&database_url)?)
+        .manage(pool)
+        .manage(mailer)
+        .manage(uploads)
         .manage(IpWhitelisting(ip_whitelisting))
         .manage(LoginMessage(login_message))
+        .manage(web::admin::TrashRetention(trash_retention))
         .manage(push_url)
         .mount("/", routes![
             web::index,
@@ -74,37 +101,76 @@ fn run() -> Result<()> {
             web::api::delete_completion,
             web::api::put_elaboration,
             web::api::delete_elaboration,
+            web::api::post_elaboration_file,
+            web::api::get_elaboration_file,
             web::api::put_group_comment,
             web::api::put_group_desk,
+            web::api::put_group_day,
+            web::api::post_group_merge,
+            web::api::post_group_split,
             web::api::put_group_student,
+            web::api::post_day_groups,
             web::api::delete_group_student,
             web::api::search_groups,
             web::api::search_students,
-            web::api::put_year,
+            web::api::post_course,
+            web::api::post_course_admin,
+            web::api::delete_course_admin,
+            web::api::post_site_admin,
+            web::api::delete_site_admin,
+            web::api::post_year,
+            web::api::post_year_archive,
             web::api::delete_year,
             web::api::put_year_writable,
+            web::api::put_year_anonymized,
             web::api::post_experiment,
+            web::api::patch_experiment,
+            web::api::put_experiment_order,
             web::api::delete_experiment,
             web::api::post_experiment_task,
+            web::api::patch_experiment_task,
+            web::api::put_task_order,
+            web::api::put_experiment_task_scoring,
+            web::api::put_experiment_description,
+            web::api::put_experiment_task_description,
+            web::api::post_experiment_link,
+            web::api::post_experiment_attachment,
+            web::api::get_attachment,
+            web::api::delete_attachment,
             web::api::delete_experiment_task,
             web::api::put_event,
             web::api::delete_event,
             web::api::post_day,
+            web::api::patch_day,
+            web::api::put_day_room,
             web::api::delete_day,
+            web::api::post_room,
+            web::api::patch_room,
+            web::api::delete_room,
+            web::api::put_day_tutor,
+            web::api::delete_day_tutor,
             web::api::post_student,
-            web::api::post_students_csv,
+            web::api::post_students_import_preview,
+            web::api::post_students_import,
+            web::api::patch_student,
             web::api::delete_student,
             web::api::put_student_instucted,
             web::api::post_tutor,
             web::api::delete_tutor,
-            web::api::put_tutor_admin,
+            web::api::put_tutor_role,
             web::api::post_ip_whitelist,
             web::api::delete_ip_whitelist,
+            web::api::put_trash_restored,
+            web::api::delete_trash,
         ])
         .mount("/analysis", routes![
             web::analysis::passed,
             web::analysis::passed_complete,
-            web::analysis::missing_reworks,
+            web::analysis::overdue_reworks,
+        ])
+        .mount("/portal", routes![
+            web::portal::index,
+            web::portal::post_elaboration_file,
         ])
         .mount("/admin", routes![
             web::admin::index,
@@ -115,18 +181,12 @@ fn run() -> Result<()> {
             web::admin::tutors,
             web::admin::audit_index,
             web::admin::audit,
+            web::admin::trash,
             web::admin::export,
+            web::admin::student_export,
+            web::admin::archive,
         ])
         .attach(rocket_contrib::templates::Template::fairing())
-        .attach(rocket::fairing::AdHoc::on_attach("Load admins", |rocket| {
-            match web::session::load_site_admins(rocket.config()) {
-                Ok(site_admins) => Ok(rocket.manage(site_admins)),
-                Err(error) => {
-                    eprintln!("{}", error);
-                    Err(rocket)
-                }
-            }
-        }))
         .launch();
 
     Ok(())
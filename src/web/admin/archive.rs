@@ -0,0 +1,794 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::db;
+use crate::db::PgInetExpressionMethods;
+use crate::errors::*;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use std::collections::HashMap;
+use std::io::Read;
+
+// Increase when changing the archive format in an incompatible way
+pub const VERSION: u32 = 1;
+
+// Maximum size of uploaded archives, which have to be read completely
+const ARCHIVE_SIZE_LIMIT: u64 = 64 * 1024 * 1024;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Complete and self-contained copy of all data of a single year
+///
+/// Ids are only used to reference entries within the archive and are
+/// replaced by new ids when restoring the archive.
+#[derive(Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub year: i16,
+    // Missing in archives created before years became terms, which always
+    // lasted a whole calendar year
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+    pub writable: bool,
+    pub anonymized: bool,
+    pub experiments: Vec<Experiment>,
+    #[serde(default)]
+    pub rooms: Vec<Room>,
+    pub days: Vec<Day>,
+    pub students: Vec<Student>,
+    pub tutors: Vec<Tutor>,
+    pub ip_whitelist: Vec<String>,
+    pub audit_logs: Vec<AuditLog>,
+    pub mails: Vec<Mail>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Experiment {
+    pub id: i32,
+    pub name: String,
+    // Attached files are not archived, only reference links
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub links: Vec<Link>,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Task {
+    pub id: i32,
+    pub name: String,
+    // Archives created before tasks were scored count every task once
+    #[serde(default)]
+    pub points: Option<i16>,
+    #[serde(default)]
+    pub weight: Option<i16>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Link {
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Room {
+    pub id: i32,
+    pub name: String,
+    pub layout: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Day {
+    pub id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub room: Option<i32>,
+    pub events: Vec<Event>,
+    pub groups: Vec<Group>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Event {
+    pub experiment: i32,
+    pub date: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Group {
+    pub id: i32,
+    pub desk: i32,
+    pub comment: String,
+    pub students: Vec<i32>,
+    pub completions: Vec<i32>,
+    // (task, points) of completions with partial credit
+    #[serde(default)]
+    pub partial_completions: Vec<(i32, i16)>,
+    // Uploaded files of the elaborations are not archived, only their grading
+    pub elaborations: Vec<Elaboration>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Elaboration {
+    pub experiment: i32,
+    pub rework_required: bool,
+    pub accepted: bool,
+    // Archives created before elaborations were graded only know the status
+    #[serde(default)]
+    pub points: Option<i16>,
+    #[serde(default)]
+    pub grade: Option<String>,
+    #[serde(default)]
+    pub feedback: String,
+    #[serde(default)]
+    pub grader: Option<String>,
+    #[serde(default)]
+    pub submitted_on: Option<String>,
+    #[serde(default)]
+    pub rework_due: Option<String>,
+    #[serde(default)]
+    pub submissions: Vec<Submission>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Submission {
+    pub created_at: String,
+    pub rework_required: bool,
+    pub accepted: bool,
+    pub points: Option<i16>,
+    pub grade: Option<String>,
+    pub feedback: String,
+    pub grader: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Student {
+    pub id: i32,
+    pub matrikel: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub given_name: String,
+    pub family_name: String,
+    pub instructed: bool,
+    #[serde(default)]
+    pub partner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Tutor {
+    pub username: String,
+    // Archives created before tutor roles only distinguish admins
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub is_admin: bool,
+    // Archives created before day tutors implicitly assign all days
+    #[serde(default)]
+    pub days: Option<Vec<i32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditLog {
+    pub created_at: String,
+    pub author: String,
+    pub affected_group: Option<i32>,
+    pub change: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Mail {
+    pub created_at: String,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub sent_at: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+/// Load all data of the given year into an archive
+///
+/// Returns `None` if the year does not exist.
+pub fn create_archive(year: i16, conn: &PgConnection) -> Result<Option<Archive>> {
+    use db::{attachments, audit_logs, completions, day_tutors, days, events, experiments,
+             group_mappings, groups, ip_whitelist, mails, rooms, students, tasks, tutors};
+
+    // Load everything inside a transaction to get a consistent view
+    conn.transaction(|| -> Result<_> {
+        let db_year = match db::years::table.find(year)
+            .get_result::<db::Year>(conn)
+            .optional()? {
+            Some(db_year) => db_year,
+            None => return Ok(None),
+        };
+
+        let db_experiments = experiments::table
+            .filter(experiments::year.eq(year))
+            .order((experiments::position.asc(), experiments::id.asc()))
+            .load::<db::Experiment>(conn)?;
+        let db_tasks = db::Task::belonging_to(&db_experiments)
+            .order((tasks::position.asc(), tasks::id.asc()))
+            .load::<db::Task>(conn)?
+            .grouped_by(&db_experiments);
+        let db_links = db::Attachment::belonging_to(&db_experiments)
+            .filter(attachments::url.is_not_null())
+            .order(attachments::id.asc())
+            .load::<db::Attachment>(conn)?;
+        let links_of = |experiment: i32, task: Option<i32>| -> Vec<Link> {
+            db_links.iter()
+                .filter(|link| link.experiment_id == experiment && link.task_id == task)
+                .map(|link| Link {
+                    title: link.title.clone(),
+                    url: link.url.clone().unwrap_or_default(),
+                })
+                .collect()
+        };
+
+        let experiments = db_experiments.into_iter()
+            .zip(db_tasks)
+            .map(|(experiment, tasks)| Experiment {
+                id: experiment.id,
+                links: links_of(experiment.id, None),
+                name: experiment.name,
+                description: experiment.description,
+                tasks: tasks.into_iter()
+                    .map(|task| Task {
+                        id: task.id,
+                        links: links_of(experiment.id, Some(task.id)),
+                        name: task.name,
+                        points: Some(task.points),
+                        weight: Some(task.weight),
+                        description: task.description,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let rooms = rooms::table
+            .filter(rooms::year.eq(year))
+            .order(rooms::id.asc())
+            .load::<db::Room>(conn)?
+            .into_iter()
+            .map(|room| Room {
+                id: room.id,
+                name: room.name,
+                layout: room.layout,
+            })
+            .collect();
+
+        let db_days = days::table
+            .filter(days::year.eq(year))
+            .order(days::id.asc())
+            .load::<db::Day>(conn)?;
+        let mut archived_days = Vec::with_capacity(db_days.len());
+        for day in db_days {
+            let events = events::table
+                .filter(events::day_id.eq(day.id))
+                .order(events::date.asc())
+                .load::<db::Event>(conn)?
+                .into_iter()
+                .map(|event| Event {
+                    experiment: event.experiment_id,
+                    date: event.date.format(DATE_FORMAT).to_string(),
+                })
+                .collect();
+
+            let db_groups = groups::table
+                .filter(groups::day_id.eq(day.id))
+                .order(groups::id.asc())
+                .load::<db::Group>(conn)?;
+            let mut archived_groups = Vec::with_capacity(db_groups.len());
+            for group in db_groups {
+                let db_completions = completions::table
+                    .filter(completions::group_id.eq(group.id))
+                    .order(completions::task_id.asc())
+                    .load::<db::Completion>(conn)?;
+
+                archived_groups.push(Group {
+                    id: group.id,
+                    desk: group.desk,
+                    comment: group.comment,
+                    students: group_mappings::table
+                        .filter(group_mappings::group_id.eq(group.id))
+                        .order(group_mappings::student_id.asc())
+                        .select(group_mappings::student_id)
+                        .load(conn)?,
+                    completions: db_completions.iter()
+                        .map(|completion| completion.task_id)
+                        .collect(),
+                    partial_completions: db_completions.iter()
+                        .filter_map(|completion| completion.points
+                            .map(|points| (completion.task_id, points)))
+                        .collect(),
+                    elaborations: archive_elaborations(group.id, conn)?,
+                });
+            }
+
+            archived_days.push(Day {
+                id: day.id,
+                name: day.name,
+                room: day.room_id,
+                events: events,
+                groups: archived_groups,
+            });
+        }
+
+        let students = students::table
+            .filter(students::year.eq(year))
+            .order(students::id.asc())
+            .load::<db::Student>(conn)?
+            .into_iter()
+            .map(|student| Student {
+                id: student.id,
+                matrikel: student.matrikel,
+                username: student.username,
+                email: student.email,
+                given_name: student.given_name,
+                family_name: student.family_name,
+                instructed: student.instructed,
+                partner: student.partner,
+            })
+            .collect();
+
+        let tutors = tutors::table
+            .filter(tutors::year.eq(year))
+            .order(tutors::id.asc())
+            .load::<db::Tutor>(conn)?;
+        let mut archived_tutors = Vec::with_capacity(tutors.len());
+        for tutor in tutors {
+            archived_tutors.push(Tutor {
+                days: Some(day_tutors::table
+                    .filter(day_tutors::tutor_id.eq(tutor.id))
+                    .order(day_tutors::day_id.asc())
+                    .select(day_tutors::day_id)
+                    .load(conn)?),
+                is_admin: tutor.role == "admin",
+                username: tutor.username,
+                role: Some(tutor.role),
+            });
+        }
+
+        let ip_whitelist = ip_whitelist::table
+            .filter(ip_whitelist::year.eq(year))
+            .order(ip_whitelist::id.asc())
+            .select(ip_whitelist::ipnet.abbrev())
+            .load(conn)?;
+
+        let audit_logs = audit_logs::table
+            .filter(audit_logs::year.eq(year))
+            .order(audit_logs::id.asc())
+            .load::<db::AuditLog>(conn)?
+            .into_iter()
+            .map(|log| AuditLog {
+                created_at: log.created_at.to_rfc3339(),
+                author: log.author,
+                affected_group: log.affected_group,
+                change: log.change,
+            })
+            .collect();
+
+        let mails = mails::table
+            .filter(mails::year.eq(year))
+            .order(mails::id.asc())
+            .load::<db::Mail>(conn)?
+            .into_iter()
+            .map(|mail| Mail {
+                created_at: mail.created_at.to_rfc3339(),
+                recipient: mail.recipient,
+                subject: mail.subject,
+                body: mail.body,
+                sent_at: mail.sent_at.map(|sent_at| sent_at.to_rfc3339()),
+                attempts: mail.attempts,
+                last_error: mail.last_error,
+            })
+            .collect();
+
+        Ok(Some(Archive {
+            version: VERSION,
+            year: year,
+            label: Some(db_year.label),
+            start_date: Some(db_year.start_date.format(DATE_FORMAT).to_string()),
+            end_date: Some(db_year.end_date.format(DATE_FORMAT).to_string()),
+            writable: db_year.writable,
+            anonymized: db_year.anonymized,
+            experiments: experiments,
+            rooms: rooms,
+            days: archived_days,
+            students: students,
+            tutors: archived_tutors,
+            ip_whitelist: ip_whitelist,
+            audit_logs: audit_logs,
+            mails: mails,
+        }))
+    })
+}
+
+/// Parse an uploaded archive
+///
+/// Returns an error message for the user if the archive is invalid.
+pub fn read_archive<R: Read>(reader: R) -> Result<::std::result::Result<Archive, String>> {
+    // Read one byte more than allowed to detect larger archives
+    let mut data = Vec::new();
+    reader.take(ARCHIVE_SIZE_LIMIT + 1).read_to_end(&mut data)
+        .chain_err(|| "Could not read uploaded archive")?;
+    if data.len() as u64 > ARCHIVE_SIZE_LIMIT {
+        return Ok(Err(format!("Das Archiv ist größer als {} MiB", ARCHIVE_SIZE_LIMIT / 1024 / 1024)));
+    }
+
+    let archive = match serde_json::from_slice::<Archive>(&data) {
+        Ok(archive) => archive,
+        Err(e) => return Ok(Err(format!("Ungültiges Archiv: {}", e))),
+    };
+
+    if archive.version != VERSION {
+        return Ok(Err(format!("Nicht unterstützte Archivversion {}", archive.version)));
+    }
+
+    Ok(Ok(archive))
+}
+
+/// Restore the archive as new term with the given label in the given course
+/// and return the id of the new term
+///
+/// All entries get new ids, audit log entries keep their original text (and
+/// thus may reference the old ids).
+///
+/// Should be run inside a transaction.
+pub fn restore_archive(course: &str, label: &str, archive: &Archive, conn: &PgConnection) -> Result<i16> {
+    use db::{audit_logs, completions, days, elaborations, events, experiments,
+             group_mappings, groups, ip_whitelist, mails, rooms, students, tasks, years};
+
+    let calendar_year = i32::from(archive.year);
+    let start_date = match archive.start_date {
+        Some(ref date) => parse_date(date)?,
+        None => NaiveDate::from_ymd(calendar_year, 1, 1),
+    };
+    let end_date = match archive.end_date {
+        Some(ref date) => parse_date(date)?,
+        None => NaiveDate::from_ymd(calendar_year, 12, 31),
+    };
+
+    let year = db::add_term(&db::NewYear {
+        course: course.to_string(),
+        label: label.to_string(),
+        start_date: start_date,
+        end_date: end_date,
+    }, conn)?;
+
+    diesel::update(years::table.find(year))
+        .set((
+            years::writable.eq(archive.writable),
+            years::anonymized.eq(archive.anonymized),
+        ))
+        .execute(conn)?;
+
+    let mut experiment_ids = HashMap::new();
+    let mut task_ids = HashMap::new();
+    // Experiments and tasks are archived in their order
+    for (position, experiment) in archive.experiments.iter().enumerate() {
+        let id: i32 = diesel::insert_into(experiments::table)
+            .values(&db::NewExperiment {
+                name: experiment.name.clone(),
+                year: year,
+                position: position as i32 + 1,
+                description: experiment.description.clone(),
+            })
+            .returning(experiments::id)
+            .get_result(conn)?;
+        experiment_ids.insert(experiment.id, id);
+        restore_links(id, None, &experiment.links, conn)?;
+
+        for (position, task) in experiment.tasks.iter().enumerate() {
+            let task_id: i32 = diesel::insert_into(tasks::table)
+                .values((
+                    tasks::experiment_id.eq(id),
+                    tasks::name.eq(&task.name),
+                    tasks::points.eq(task.points.unwrap_or(1)),
+                    tasks::weight.eq(task.weight.unwrap_or(1)),
+                    tasks::position.eq(position as i32 + 1),
+                    tasks::description.eq(&task.description),
+                ))
+                .returning(tasks::id)
+                .get_result(conn)?;
+            task_ids.insert(task.id, task_id);
+            restore_links(id, Some(task_id), &task.links, conn)?;
+        }
+    }
+
+    let mut student_ids = HashMap::new();
+    for student in &archive.students {
+        let id: i32 = diesel::insert_into(students::table)
+            .values((
+                students::matrikel.eq(&student.matrikel),
+                students::year.eq(year),
+                students::username.eq(&student.username),
+                students::instructed.eq(student.instructed),
+                students::given_name.eq(&student.given_name),
+                students::family_name.eq(&student.family_name),
+                students::email.eq(&student.email),
+                students::partner.eq(&student.partner),
+            ))
+            .returning(students::id)
+            .get_result(conn)?;
+        student_ids.insert(student.id, id);
+    }
+
+    let lookup = |ids: &HashMap<i32, i32>, id: i32, kind: &str| {
+        ids.get(&id).cloned()
+            .ok_or_else(|| Error::from(format!("Archive references unknown {} #{}", kind, id)))
+    };
+
+    let mut room_ids = HashMap::new();
+    for room in &archive.rooms {
+        let id: i32 = diesel::insert_into(rooms::table)
+            .values(&db::NewRoom {
+                year: year,
+                name: room.name.clone(),
+                layout: room.layout.clone(),
+            })
+            .returning(rooms::id)
+            .get_result(conn)?;
+        room_ids.insert(room.id, id);
+    }
+
+    let mut day_ids = HashMap::new();
+    let mut group_ids = HashMap::new();
+    for day in &archive.days {
+        let room_id = match day.room {
+            Some(room) => Some(lookup(&room_ids, room, "room")?),
+            None => None,
+        };
+        let day_id: i32 = diesel::insert_into(days::table)
+            .values((
+                days::name.eq(&day.name),
+                days::year.eq(year),
+                days::room_id.eq(room_id),
+            ))
+            .returning(days::id)
+            .get_result(conn)?;
+        day_ids.insert(day.id, day_id);
+
+        for event in &day.events {
+            let date = parse_date(&event.date)?;
+            diesel::insert_into(events::table)
+                .values(&db::Event {
+                    day_id: day_id,
+                    experiment_id: lookup(&experiment_ids, event.experiment, "experiment")?,
+                    date: date,
+                })
+                .execute(conn)?;
+        }
+
+        for group in &day.groups {
+            let group_id: i32 = diesel::insert_into(groups::table)
+                .values(&db::NewGroup {
+                    desk: group.desk,
+                    day_id: day_id,
+                    comment: group.comment.clone(),
+                })
+                .returning(groups::id)
+                .get_result(conn)?;
+            group_ids.insert(group.id, group_id);
+
+            for &student in &group.students {
+                diesel::insert_into(group_mappings::table)
+                    .values(&db::GroupMapping {
+                        student_id: lookup(&student_ids, student, "student")?,
+                        group_id: group_id,
+                    })
+                    .execute(conn)?;
+            }
+
+            let partial_completions: HashMap<_,_> = group.partial_completions.iter()
+                .cloned()
+                .collect();
+            for &task in &group.completions {
+                diesel::insert_into(completions::table)
+                    .values(&db::Completion {
+                        group_id: group_id,
+                        task_id: lookup(&task_ids, task, "task")?,
+                        points: partial_completions.get(&task).cloned(),
+                    })
+                    .execute(conn)?;
+            }
+
+            for elaboration in &group.elaborations {
+                let experiment_id = lookup(&experiment_ids, elaboration.experiment, "experiment")?;
+                diesel::insert_into(elaborations::table)
+                    .values(&db::Elaboration {
+                        group_id: group_id,
+                        experiment_id: experiment_id,
+                        rework_required: elaboration.rework_required,
+                        accepted: elaboration.accepted,
+                        points: elaboration.points,
+                        grade: elaboration.grade.clone(),
+                        feedback: elaboration.feedback.clone(),
+                        grader: elaboration.grader.clone(),
+                        submitted_on: match elaboration.submitted_on {
+                            Some(ref date) => Some(parse_date(date)?),
+                            None => None,
+                        },
+                        rework_due: match elaboration.rework_due {
+                            Some(ref date) => Some(parse_date(date)?),
+                            None => None,
+                        },
+                    })
+                    .execute(conn)?;
+
+                for submission in &elaboration.submissions {
+                    diesel::insert_into(db::elaboration_submissions::table)
+                        .values((
+                            db::elaboration_submissions::group_id.eq(group_id),
+                            db::elaboration_submissions::experiment_id.eq(experiment_id),
+                            db::elaboration_submissions::created_at.eq(parse_timestamp(&submission.created_at)?),
+                            db::elaboration_submissions::rework_required.eq(submission.rework_required),
+                            db::elaboration_submissions::accepted.eq(submission.accepted),
+                            db::elaboration_submissions::points.eq(submission.points),
+                            db::elaboration_submissions::grade.eq(&submission.grade),
+                            db::elaboration_submissions::feedback.eq(&submission.feedback),
+                            db::elaboration_submissions::grader.eq(&submission.grader),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+        }
+    }
+
+    for tutor in &archive.tutors {
+        let tutor_id: i32 = diesel::insert_into(db::tutors::table)
+            .values(&db::NewTutor {
+                username: tutor.username.clone(),
+                year: year,
+                role: match tutor.role {
+                    Some(ref role) => role.clone(),
+                    None if tutor.is_admin => "admin".to_string(),
+                    None => "tutor".to_string(),
+                },
+            })
+            .returning(db::tutors::id)
+            .get_result(conn)?;
+
+        let days = match tutor.days {
+            Some(ref days) => days.iter()
+                .map(|&day| lookup(&day_ids, day, "day"))
+                .collect::<Result<Vec<_>>>()?,
+            None => day_ids.values().cloned().collect(),
+        };
+        for day_id in days {
+            diesel::insert_into(db::day_tutors::table)
+                .values(&db::DayTutor {
+                    day_id: day_id,
+                    tutor_id: tutor_id,
+                })
+                .execute(conn)?;
+        }
+    }
+
+    for ipnet in &archive.ip_whitelist {
+        diesel::insert_into(ip_whitelist::table)
+            .values((
+                ip_whitelist::ipnet.eq(db::to_inet(ipnet)),
+                ip_whitelist::year.eq(year),
+            ))
+            .execute(conn)?;
+    }
+
+    for log in &archive.audit_logs {
+        // Groups may have been deleted before archiving, so unknown
+        // groups are not an error here
+        diesel::insert_into(audit_logs::table)
+            .values((
+                audit_logs::created_at.eq(parse_timestamp(&log.created_at)?),
+                audit_logs::year.eq(year),
+                audit_logs::author.eq(&log.author),
+                audit_logs::affected_group.eq(log.affected_group
+                    .and_then(|group| group_ids.get(&group).cloned())),
+                audit_logs::change.eq(&log.change),
+            ))
+            .execute(conn)?;
+    }
+
+    // Mails that were not sent before archiving are not restored, they would
+    // otherwise be sent again
+    for mail in archive.mails.iter().filter(|mail| mail.sent_at.is_some()) {
+        let sent_at = match mail.sent_at {
+            Some(ref sent_at) => Some(parse_timestamp(sent_at)?),
+            None => None,
+        };
+
+        diesel::insert_into(mails::table)
+            .values((
+                mails::created_at.eq(parse_timestamp(&mail.created_at)?),
+                mails::year.eq(year),
+                mails::recipient.eq(&mail.recipient),
+                mails::subject.eq(&mail.subject),
+                mails::body.eq(&mail.body),
+                mails::sent_at.eq(sent_at),
+                mails::attempts.eq(mail.attempts),
+                mails::last_error.eq(&mail.last_error),
+            ))
+            .execute(conn)?;
+    }
+
+    Ok(year)
+}
+
+// Insert the archived links of an experiment or one of its tasks
+fn restore_links(experiment: i32, task: Option<i32>, links: &[Link], conn: &PgConnection) -> Result<()> {
+    for link in links {
+        diesel::insert_into(db::attachments::table)
+            .values(&db::NewAttachment {
+                experiment_id: experiment,
+                task_id: task,
+                title: &link.title,
+                url: Some(&link.url),
+                file: None,
+                content_type: None,
+            })
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+// Archive the elaborations of the group including their history
+fn archive_elaborations(group: i32, conn: &PgConnection) -> Result<Vec<Elaboration>> {
+    use db::{elaboration_submissions, elaborations};
+
+    let db_elaborations = elaborations::table
+        .filter(elaborations::group_id.eq(group))
+        .order(elaborations::experiment_id.asc())
+        .load::<db::Elaboration>(conn)?;
+
+    let mut archived = Vec::with_capacity(db_elaborations.len());
+    for elaboration in db_elaborations {
+        let submissions = elaboration_submissions::table
+            .filter(elaboration_submissions::group_id.eq(group))
+            .filter(elaboration_submissions::experiment_id.eq(elaboration.experiment_id))
+            .order(elaboration_submissions::id.asc())
+            .load::<db::ElaborationSubmission>(conn)?
+            .into_iter()
+            .map(|submission| Submission {
+                created_at: submission.created_at.to_rfc3339(),
+                rework_required: submission.rework_required,
+                accepted: submission.accepted,
+                points: submission.points,
+                grade: submission.grade,
+                feedback: submission.feedback,
+                grader: submission.grader,
+            })
+            .collect();
+
+        archived.push(Elaboration {
+            experiment: elaboration.experiment_id,
+            rework_required: elaboration.rework_required,
+            accepted: elaboration.accepted,
+            points: elaboration.points,
+            grade: elaboration.grade,
+            feedback: elaboration.feedback,
+            grader: elaboration.grader,
+            submitted_on: elaboration.submitted_on.map(|date| date.format(DATE_FORMAT).to_string()),
+            rework_due: elaboration.rework_due.map(|date| date.format(DATE_FORMAT).to_string()),
+            submissions: submissions,
+        });
+    }
+
+    Ok(archived)
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .chain_err(|| format!("Invalid date {}", date))
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .chain_err(|| format!("Invalid timestamp {}", timestamp))
+}
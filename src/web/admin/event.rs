@@ -2,19 +2,22 @@ use crate::db;
 use crate::errors::*;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize)]
 pub struct Context {
     pub base: super::BaseContext,
     pub days: Vec<Day>,
+    pub rooms: Vec<Room>,
 }
 
 #[derive(Serialize)]
 pub struct Day {
     pub id: i32,
     pub name: String,
+    pub room: Option<i32>,
     pub experiments: Vec<Experiment>,
+    pub tutors: Vec<Tutor>,
 }
 
 #[derive(Serialize)]
@@ -24,10 +27,38 @@ pub struct Experiment {
     pub date: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct Tutor {
+    pub id: i32,
+    pub username: String,
+    pub assigned: bool,
+}
+
+#[derive(Serialize)]
+pub struct Room {
+    pub id: i32,
+    pub name: String,
+    pub layout: String,
+}
+
+pub fn load_rooms(year: i16, conn: &PgConnection) -> Result<Vec<Room>> {
+    Ok(db::rooms::table
+        .filter(db::rooms::year.eq(year))
+        .order(db::rooms::name.asc())
+        .load::<db::Room>(conn)?
+        .into_iter()
+        .map(|room| Room {
+            id: room.id,
+            name: room.name,
+            layout: room.layout,
+        })
+        .collect())
+}
+
 pub fn load_days(year: i16, conn: &PgConnection) -> Result<Vec<Day>> {
     let experiments = db::experiments::table
         .filter(db::experiments::year.eq(year))
-        .order(db::experiments::name.asc())
+        .order((db::experiments::position.asc(), db::experiments::name.asc()))
         .load::<db::Experiment>(conn)?;
 
     let days = db::days::table
@@ -35,13 +66,22 @@ pub fn load_days(year: i16, conn: &PgConnection) -> Result<Vec<Day>> {
         .order(db::days::name.asc())
         .load::<db::Day>(conn)?;
 
+    let tutors = db::tutors::table
+        .filter(db::tutors::year.eq(year))
+        .order(db::tutors::username.asc())
+        .load::<db::Tutor>(conn)?;
+
     let events = db::Event::belonging_to(&days)
         .load::<db::Event>(conn)?
         .grouped_by(&days);
+    let day_tutors = db::DayTutor::belonging_to(&days)
+        .load::<db::DayTutor>(conn)?
+        .grouped_by(&days);
 
     Ok(days.into_iter()
         .zip(events)
-        .map(|(day, events)| {
+        .zip(day_tutors)
+        .map(|((day, events), day_tutors)| {
             let events: HashMap<_,_> = events.into_iter()
                 .map(|event| (event.experiment_id, event.date))
                 .collect();
@@ -55,10 +95,23 @@ pub fn load_days(year: i16, conn: &PgConnection) -> Result<Vec<Day>> {
                 })
                 .collect();
 
+            let assigned: HashSet<_> = day_tutors.into_iter()
+                .map(|day_tutor| day_tutor.tutor_id)
+                .collect();
+            let tutors = tutors.iter()
+                .map(|tutor| Tutor {
+                    id: tutor.id,
+                    username: tutor.username.clone(),
+                    assigned: assigned.contains(&tutor.id),
+                })
+                .collect();
+
             Day {
                 id: day.id,
                 name: day.name,
+                room: day.room_id,
                 experiments: experiments,
+                tutors: tutors,
             }
         })
         .collect())
@@ -1,18 +1,23 @@
 use crate::db;
 use crate::errors::*;
+use crate::web::models::Attachment;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
+use std::collections::HashMap;
 
 #[derive(Serialize)]
 pub struct Context {
     pub base: super::BaseContext,
     pub experiments: Vec<Experiment>,
+    pub uploads: bool,
 }
 
 #[derive(Serialize)]
 pub struct Experiment {
     pub id: i32,
     pub name: String,
+    pub description: String,
+    pub attachments: Vec<Attachment>,
     pub tasks: Vec<Task>,
 }
 
@@ -20,32 +25,55 @@ pub struct Experiment {
 pub struct Task {
     pub id: i32,
     pub name: String,
+    pub points: i16,
+    pub weight: i16,
+    pub description: String,
+    pub attachments: Vec<Attachment>,
 }
 
 pub fn load_experiments(year: i16, conn: &PgConnection) -> Result<Vec<Experiment>> {
     let experiments = db::experiments::table
         .filter(db::experiments::year.eq(year))
-        .order(db::experiments::name.asc())
+        .order((db::experiments::position.asc(), db::experiments::name.asc()))
         .load::<db::Experiment>(conn)?;
 
     let tasks = db::Task::belonging_to(&experiments)
-        .order(db::tasks::name.asc())
+        .order((db::tasks::position.asc(), db::tasks::name.asc()))
         .load::<db::Task>(conn)?
         .grouped_by(&experiments);
 
+    let attachments = db::Attachment::belonging_to(&experiments)
+        .order(db::attachments::id.asc())
+        .load::<db::Attachment>(conn)?
+        .grouped_by(&experiments);
+
     Ok(experiments.into_iter()
         .zip(tasks)
-        .map(|(experiment, tasks)| {
+        .zip(attachments)
+        .map(|((experiment, tasks), attachments)| {
+            let mut attachments_by_task: HashMap<_, Vec<_>> = HashMap::new();
+            for attachment in attachments {
+                attachments_by_task.entry(attachment.task_id)
+                    .or_default()
+                    .push(Attachment::from(attachment));
+            }
+
             let tasks = tasks.into_iter()
                 .map(|task| Task {
+                     attachments: attachments_by_task.remove(&Some(task.id)).unwrap_or_default(),
                      id: task.id,
                      name: task.name,
+                     points: task.points,
+                     weight: task.weight,
+                     description: task.description,
                 })
                 .collect();
 
             Experiment {
                 id: experiment.id,
                 name: experiment.name,
+                description: experiment.description,
+                attachments: attachments_by_task.remove(&None).unwrap_or_default(),
                 tasks: tasks,
             }
         })
@@ -18,25 +18,77 @@ pub struct CsvResponse {
 
 impl<'r> Responder<'r> for CsvResponse {
     fn respond_to(self, _: &Request) -> response::Result<'r> {
-        let disposition = header::ContentDisposition {
-            disposition: header::DispositionType::Attachment,
-            parameters: vec![
-                header::DispositionParam::Filename(
-                    header::Charset::Ext("UTF-8".into()),
-                    None,
-                    self.filename.into_bytes(),
-                )
-            ]
-        };
-
         Response::build()
             .header(ContentType::CSV)
+            .header(attachment(self.filename))
+            .sized_body(Cursor::new(self.content))
+            .ok()
+    }
+}
+
+pub struct JsonResponse {
+    pub filename: String,
+    pub content: Vec<u8>,
+}
+
+impl<'r> Responder<'r> for JsonResponse {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        Response::build()
+            .header(ContentType::JSON)
+            .header(attachment(self.filename))
+            .sized_body(Cursor::new(self.content))
+            .ok()
+    }
+}
+
+pub struct PdfResponse {
+    pub filename: String,
+    pub content: Vec<u8>,
+}
+
+impl<'r> Responder<'r> for PdfResponse {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        Response::build()
+            .header(ContentType::PDF)
+            .header(attachment(self.filename))
+            .sized_body(Cursor::new(self.content))
+            .ok()
+    }
+}
+
+/// File of an arbitrary type, which is shown inline if the browser supports it
+pub struct FileResponse {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+impl<'r> Responder<'r> for FileResponse {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        let mut disposition = attachment(self.filename);
+        disposition.disposition = header::DispositionType::Inline;
+
+        Response::build()
+            .header(ContentType::parse_flexible(&self.content_type).unwrap_or(ContentType::Binary))
             .header(disposition)
             .sized_body(Cursor::new(self.content))
             .ok()
     }
 }
 
+fn attachment(filename: String) -> header::ContentDisposition {
+    header::ContentDisposition {
+        disposition: header::DispositionType::Attachment,
+        parameters: vec![
+            header::DispositionParam::Filename(
+                header::Charset::Ext("UTF-8".into()),
+                None,
+                filename.into_bytes(),
+            )
+        ]
+    }
+}
+
 pub fn create_csv(year: i16, conn: &PgConnection) -> Result<Vec<u8>> {
     let mut csv = Writer::from_writer(vec![]);
 
@@ -128,3 +180,322 @@ pub fn create_csv(year: i16, conn: &PgConnection) -> Result<Vec<u8>> {
 
     csv.into_inner().chain_err(|| "Could not finalize csv writer")
 }
+
+#[derive(Serialize)]
+struct StudentExport {
+    student: ExportedStudent,
+    groups: Vec<ExportedGroup>,
+    audit_logs: Vec<ExportedAuditLog>,
+    mails: Vec<ExportedMail>,
+    trash: Vec<ExportedTrashEntry>,
+}
+
+#[derive(Serialize)]
+struct ExportedStudent {
+    id: i32,
+    year: i16,
+    matrikel: String,
+    given_name: String,
+    family_name: String,
+    username: Option<String>,
+    email: Option<String>,
+    partner: Option<String>,
+    instructed: bool,
+}
+
+#[derive(Serialize)]
+struct ExportedGroup {
+    id: i32,
+    desk: i32,
+    day: String,
+    comment: String,
+    completions: Vec<ExportedCompletion>,
+    elaborations: Vec<ExportedElaboration>,
+    files: Vec<ExportedFile>,
+}
+
+#[derive(Serialize)]
+struct ExportedCompletion {
+    experiment: String,
+    task: String,
+    points: i16,
+}
+
+#[derive(Serialize)]
+struct ExportedElaboration {
+    experiment: String,
+    rework_required: bool,
+    accepted: bool,
+    points: Option<i16>,
+    grade: Option<String>,
+    feedback: String,
+    grader: Option<String>,
+    submitted_on: Option<String>,
+    rework_due: Option<String>,
+    submissions: Vec<ExportedSubmission>,
+}
+
+#[derive(Serialize)]
+struct ExportedSubmission {
+    created_at: String,
+    rework_required: bool,
+    accepted: bool,
+    points: Option<i16>,
+    grade: Option<String>,
+    feedback: String,
+    grader: String,
+}
+
+#[derive(Serialize)]
+struct ExportedFile {
+    id: i32,
+    experiment: String,
+    version: i32,
+    filename: String,
+    size: i32,
+    uploaded_at: String,
+    uploader: String,
+}
+
+#[derive(Serialize)]
+struct ExportedAuditLog {
+    id: i32,
+    created_at: String,
+    author: String,
+    affected_group: Option<i32>,
+    change: String,
+}
+
+#[derive(Serialize)]
+struct ExportedMail {
+    id: i32,
+    created_at: String,
+    recipient: String,
+    subject: String,
+    body: String,
+    sent_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExportedTrashEntry {
+    id: i32,
+    deleted_at: String,
+    author: String,
+    kind: String,
+    name: String,
+    data: serde_json::Value,
+}
+
+/// Whether the change of an audit log entry mentions the student
+///
+/// Only the forms used by the audit log that are delimited by the id or the
+/// matrikel of the student are matched, because the name may have changed
+/// since and the id or matrikel of another student may contain those of the
+/// student.
+fn mentions_student(change: &str, student: &db::Student) -> bool {
+    let mentions = [
+        format!(", #{})", student.id),
+        format!("({}, ", student.matrikel),
+        format!("({})", student.matrikel),
+        format!("partner {},", student.matrikel),
+        format!("partner {})", student.matrikel),
+    ];
+
+    mentions.iter().any(|mention| change.contains(mention.as_str()))
+        || change.starts_with("Student ") && change.contains(&format!(" (#{}) is ", student.id))
+        || change.starts_with("Add ") && change.ends_with(&format!(" (#{}) to group", student.id))
+        || change.starts_with("Remove ") && change.ends_with(&format!(" (#{}) from group", student.id))
+}
+
+/// Export all data stored about a single student (for GDPR requests)
+///
+/// Returns `None` if the student does not belong to the given year.
+pub fn create_student_json(year: i16, student: i32, conn: &PgConnection) -> Result<Option<(String, Vec<u8>)>> {
+    use db::{audit_logs, completions, days, elaboration_files, elaboration_submissions, elaborations,
+             experiments, group_mappings, groups, mails, tasks};
+
+    let export = conn.transaction(|| -> Result<_> {
+        let student = match db::students::table
+            .find(student)
+            .filter(db::students::year.eq(year))
+            .get_result::<db::Student>(conn)
+            .optional()? {
+            Some(student) => student,
+            None => return Ok(None),
+        };
+
+        let student_groups = groups::table
+            .inner_join(days::table)
+            .inner_join(group_mappings::table)
+            .filter(group_mappings::student_id.eq(student.id))
+            .order(groups::id.asc())
+            .select((groups::all_columns, days::name))
+            .load::<(db::Group, String)>(conn)?;
+
+        let mut exported_groups = Vec::with_capacity(student_groups.len());
+        for (group, day) in student_groups {
+            let completions = completions::table
+                .inner_join(tasks::table.inner_join(experiments::table))
+                .filter(completions::group_id.eq(group.id))
+                .order((experiments::position.asc(), experiments::name.asc(),
+                    tasks::position.asc(), tasks::name.asc()))
+                .select((experiments::name, tasks::name, completions::points, tasks::points))
+                .load::<(String, String, Option<i16>, i16)>(conn)?
+                .into_iter()
+                .map(|(experiment, task, points, max_points)| ExportedCompletion {
+                    experiment, task,
+                    points: points.unwrap_or(max_points),
+                })
+                .collect();
+
+            let submissions = elaboration_submissions::table
+                .filter(elaboration_submissions::group_id.eq(group.id))
+                .order(elaboration_submissions::id.asc())
+                .load::<db::ElaborationSubmission>(conn)?;
+
+            let elaborations = elaborations::table
+                .inner_join(experiments::table)
+                .filter(elaborations::group_id.eq(group.id))
+                .order((experiments::position.asc(), experiments::name.asc()))
+                .load::<(db::Elaboration, db::Experiment)>(conn)?
+                .into_iter()
+                .map(|(elaboration, experiment)| {
+                    let submissions = submissions.iter()
+                        .filter(|submission| submission.experiment_id == experiment.id)
+                        .map(|submission| ExportedSubmission {
+                            created_at: submission.created_at.to_rfc3339(),
+                            rework_required: submission.rework_required,
+                            accepted: submission.accepted,
+                            points: submission.points,
+                            grade: submission.grade.clone(),
+                            feedback: submission.feedback.clone(),
+                            grader: submission.grader.clone(),
+                        })
+                        .collect();
+
+                    ExportedElaboration {
+                        experiment: experiment.name,
+                        rework_required: elaboration.rework_required,
+                        accepted: elaboration.accepted,
+                        points: elaboration.points,
+                        grade: elaboration.grade,
+                        feedback: elaboration.feedback,
+                        grader: elaboration.grader,
+                        submitted_on: elaboration.submitted_on.map(|date| date.to_string()),
+                        rework_due: elaboration.rework_due.map(|date| date.to_string()),
+                        submissions: submissions,
+                    }
+                })
+                .collect();
+
+            // Only the metadata of uploaded files, the files themselves are
+            // downloaded from the page of the group
+            let files = elaboration_files::table
+                .inner_join(experiments::table)
+                .filter(elaboration_files::group_id.eq(group.id))
+                .order((experiments::position.asc(), experiments::name.asc(),
+                    elaboration_files::version.asc()))
+                .select((elaboration_files::all_columns, experiments::name))
+                .load::<(db::ElaborationFile, String)>(conn)?
+                .into_iter()
+                .map(|(file, experiment)| ExportedFile {
+                    id: file.id,
+                    experiment: experiment,
+                    version: file.version,
+                    filename: file.filename,
+                    size: file.size,
+                    uploaded_at: file.uploaded_at.to_rfc3339(),
+                    uploader: file.uploader,
+                })
+                .collect();
+
+            exported_groups.push(ExportedGroup {
+                id: group.id,
+                desk: group.desk,
+                day: day,
+                comment: group.comment,
+                completions: completions,
+                elaborations: elaborations,
+                files: files,
+            });
+        }
+
+        // Audit log entries either affect one of the groups of the student
+        // or mention the student
+        let group_ids: Vec<_> = exported_groups.iter().map(|group| group.id).collect();
+        let audit_logs = audit_logs::table
+            .filter(audit_logs::year.eq(year))
+            .order(audit_logs::id.asc())
+            .load::<db::AuditLog>(conn)?
+            .into_iter()
+            .filter(|log| {
+                log.affected_group.map_or(false, |group| group_ids.contains(&group))
+                    || mentions_student(&log.change, &student)
+            })
+            .map(|log| ExportedAuditLog {
+                id: log.id,
+                created_at: log.created_at.to_rfc3339(),
+                author: log.author,
+                affected_group: log.affected_group,
+                change: log.change,
+            })
+            .collect();
+
+        let exported_mails = match student.email {
+            Some(ref email) => mails::table
+                .filter(mails::year.eq(year))
+                .filter(mails::recipient.eq(email))
+                .order(mails::id.asc())
+                .load::<db::Mail>(conn)?
+                .into_iter()
+                .map(|mail| ExportedMail {
+                    id: mail.id,
+                    created_at: mail.created_at.to_rfc3339(),
+                    recipient: mail.recipient,
+                    subject: mail.subject,
+                    body: mail.body,
+                    sent_at: mail.sent_at.map(|sent_at| sent_at.to_rfc3339()),
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        let mut trash = Vec::new();
+        for entry in db::trash_entries_of_student(year, student.id, conn)? {
+            trash.push(ExportedTrashEntry {
+                id: entry.id,
+                deleted_at: entry.deleted_at.to_rfc3339(),
+                author: entry.author,
+                kind: entry.kind,
+                name: entry.name,
+                data: serde_json::from_str(&entry.data)?,
+            });
+        }
+
+        Ok(Some(StudentExport {
+            student: ExportedStudent {
+                id: student.id,
+                year: student.year,
+                matrikel: student.matrikel,
+                given_name: student.given_name,
+                family_name: student.family_name,
+                username: student.username,
+                email: student.email,
+                partner: student.partner,
+                instructed: student.instructed,
+            },
+            groups: exported_groups,
+            audit_logs: audit_logs,
+            mails: exported_mails,
+            trash: trash,
+        }))
+    })?;
+
+    match export {
+        Some(export) => {
+            let json = serde_json::to_vec_pretty(&export)?;
+            Ok(Some((export.student.matrikel, json)))
+        }
+        None => Ok(None),
+    }
+}
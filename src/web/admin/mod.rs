@@ -1,16 +1,22 @@
+pub mod archive;
 mod audit;
 mod event;
 mod experiment;
 pub mod export;
 mod student;
+mod trash;
 mod tutor;
 
+pub use self::trash::{spawn_purger, trash_permission, TrashRetention};
+
 use chrono::Local;
 use crate::db;
 use crate::errors::*;
-use crate::web::session::{IpWhitelisting, SiteAdmin, User};
+use crate::web::permissions::Permission;
+use crate::web::session::{IpWhitelisting, User};
+use crate::web::upload::Uploads;
 use crate::web::models;
-use diesel::PgConnection;
+use diesel::prelude::*;
 use rocket::State;
 use rocket::request::Form;
 use rocket::response::Redirect;
@@ -21,6 +27,13 @@ pub struct BaseContext {
     pub site: &'static str,
     pub year: i16,
     pub read_only_year: bool,
+    pub anonymized_year: bool,
+    pub year_label: String,
+    pub course: String,
+    pub course_name: String,
+    pub manage: bool,
+    pub manage_students: bool,
+    pub course_admin: bool,
     pub site_admin: bool,
     pub years: Vec<models::Year>,
 }
@@ -29,71 +42,96 @@ impl BaseContext {
     fn new(site: &'static str, year: i16, user: &User, conn: &PgConnection) -> Result<BaseContext> {
         let filtered_years = models::find_years(&*conn)?
             .into_iter()
-            .filter(|year| user.is_admin_for(year.name))
+            .filter(|year| user.is_admin_for(year.id))
             .collect();
 
+        let (db_year, course) = db::years::table
+            .inner_join(db::courses::table)
+            .filter(db::years::id.eq(year))
+            .get_result::<(db::Year, db::Course)>(conn)?;
+
         Ok(BaseContext {
             site: site,
             year: year,
-            read_only_year: !models::is_writable_year(year, &conn)?,
+            read_only_year: !db_year.writable,
+            anonymized_year: db_year.anonymized,
+            year_label: db_year.label,
+            manage: user.has_permission(Permission::Manage, year),
+            manage_students: user.has_permission(Permission::Students, year),
+            course_admin: user.is_course_admin(&course.id),
+            course: course.id,
+            course_name: course.name,
             site_admin: user.is_site_admin(),
             years: filtered_years,
         })
     }
 }
 
-#[get("/<year>")]
-pub fn index(year: i16, user: User) -> Result<Redirect> {
+#[get("/<course>/<year>")]
+pub fn index(course: String, year: i16, conn: db::Conn, user: User) -> Result<Redirect> {
     user.ensure_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
 
-    Ok(Redirect::to(format!("/admin/{}/experiments", year)))
+    match user.has_permission(Permission::Manage, year) {
+        true => Ok(Redirect::to(format!("/admin/{}/{}/experiments", course, year))),
+        false => Ok(Redirect::to(format!("/admin/{}/{}/students", course, year))),
+    }
 }
 
-#[get("/<year>/experiments")]
-pub fn experiments(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_admin_for(year)?;
+#[get("/<course>/<year>/experiments")]
+pub fn experiments(course: String, year: i16, uploads: State<Uploads>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::Manage, year)?;
+    models::ensure_course(&course, year, &conn)?;
 
     let context = experiment::Context {
         base: BaseContext::new("experiments", year, &user, &conn)?,
         experiments: experiment::load_experiments(year, &conn)?,
+        uploads: uploads.is_enabled(),
     };
 
     Ok(Template::render("admin-experiments", context))
 }
 
-#[get("/<year>/events")]
-pub fn events(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_admin_for(year)?;
+#[get("/<course>/<year>/events")]
+pub fn events(course: String, year: i16, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::Manage, year)?;
+    models::ensure_course(&course, year, &conn)?;
 
     let context = event::Context {
         base: BaseContext::new("events", year, &user, &conn)?,
         days: event::load_days(year, &conn)?,
+        rooms: event::load_rooms(year, &conn)?,
     };
 
     Ok(Template::render("admin-events", context))
 }
 
-#[get("/<year>/students")]
-pub fn students(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    students_ordered(year, Form(student::Order::default()), conn, user)
+#[get("/<course>/<year>/students")]
+pub fn students(course: String, year: i16, conn: db::Conn, user: User) -> Result<Template> {
+    students_ordered(course, year, Form(student::Order::default()), conn, user)
 }
 
-#[get("/<year>/students?<order..>")]
-pub fn students_ordered(year: i16, order: Form<student::Order>, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_admin_for(year)?;
+#[get("/<course>/<year>/students?<order..>")]
+pub fn students_ordered(course: String, year: i16, order: Form<student::Order>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::Students, year)?;
+    models::ensure_course(&course, year, &conn)?;
 
     let (students, chosen_order) = student::load_students(year, order.into_inner(), &conn)?;
     let context = student::Context {
         base: BaseContext::new("students", year, &user, &conn)?,
         students: students,
         order: chosen_order,
+        days: student::load_days(year, &conn)?,
     };
 
     Ok(Template::render("admin-students", context))
 }
 
-#[get("/<year>/tutors")]
-pub fn tutors(year: i16, ip_whitelisting: State<IpWhitelisting>, conn: db::Conn, user: SiteAdmin) -> Result<Template> {
+#[get("/<course>/<year>/tutors")]
+pub fn tutors(course: String, year: i16, ip_whitelisting: State<IpWhitelisting>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
     let ip_whitelist = match ip_whitelisting.0 {
         true => Some(tutor::load_whitelist(year, &conn)?),
         false => None,
@@ -102,19 +140,31 @@ pub fn tutors(year: i16, ip_whitelisting: State<IpWhitelisting>, conn: db::Conn,
     let context = tutor::Context {
         base: BaseContext::new("tutors", year, &user, &conn)?,
         tutors: tutor::load_tutors(year, &conn)?,
+        roles: tutor::roles(),
         ip_whitelist: ip_whitelist,
+        course_admins: tutor::load_course_admins(year, &conn)?,
+        site_admins: match user.is_site_admin() {
+            true => Some(tutor::load_site_admins(&conn)?),
+            false => None,
+        },
     };
 
     Ok(Template::render("admin-tutors", context))
 }
 
-#[get("/<year>/audit")]
-pub fn audit_index(year: i16, _user: SiteAdmin) -> Redirect {
-    Redirect::to(format!("/admin/{}/audit?limit=100", year))
+#[get("/<course>/<year>/audit")]
+pub fn audit_index(course: String, year: i16, conn: db::Conn, user: User) -> Result<Redirect> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
+    Ok(Redirect::to(format!("/admin/{}/{}/audit?limit=100", course, year)))
 }
 
-#[get("/<year>/audit?<filters..>")]
-pub fn audit(year: i16, filters: Form<audit::Filters>, conn: db::Conn, user: SiteAdmin) -> Result<Template> {
+#[get("/<course>/<year>/audit?<filters..>")]
+pub fn audit(course: String, year: i16, filters: Form<audit::Filters>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
     let context = audit::Context {
         base: BaseContext::new("audit", year, &user, &conn)?,
         logs: audit::load_logs(year, &filters, &conn)?,
@@ -125,8 +175,25 @@ pub fn audit(year: i16, filters: Form<audit::Filters>, conn: db::Conn, user: Sit
     Ok(Template::render("admin-audit", context))
 }
 
-#[get("/<year>/export")]
-pub fn export(year: i16, conn: db::Conn, _user: SiteAdmin) -> Result<export::CsvResponse> {
+#[get("/<course>/<year>/trash")]
+pub fn trash(course: String, year: i16, retention: State<TrashRetention>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
+    let context = trash::Context {
+        base: BaseContext::new("trash", year, &user, &conn)?,
+        entries: trash::load_entries(year, retention.0, &user, &conn)?,
+        retention: retention.0,
+    };
+
+    Ok(Template::render("admin-trash", context))
+}
+
+#[get("/<course>/<year>/export")]
+pub fn export(course: String, year: i16, conn: db::Conn, user: User) -> Result<export::CsvResponse> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
     let name = format!("hwpb-export-{}.csv", Local::today().format("%Y-%m-%d"));
     let csv = export::create_csv(year, &conn)?;
 
@@ -135,3 +202,32 @@ pub fn export(year: i16, conn: db::Conn, _user: SiteAdmin) -> Result<export::Csv
         content: csv
     })
 }
+
+#[get("/<course>/<year>/students/<student>/export")]
+pub fn student_export(course: String, year: i16, student: i32, conn: db::Conn, user: User) -> Result<Option<export::JsonResponse>> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
+    let export = export::create_student_json(year, student, &conn)?;
+
+    Ok(export.map(|(matrikel, json)| export::JsonResponse {
+        filename: format!("hwpb-student-{}-{}.json", matrikel, Local::today().format("%Y-%m-%d")),
+        content: json,
+    }))
+}
+
+#[get("/<course>/<year>/archive")]
+pub fn archive(course: String, year: i16, conn: db::Conn, user: User) -> Result<Option<export::JsonResponse>> {
+    user.ensure_course_admin_for(year)?;
+    models::ensure_course(&course, year, &conn)?;
+
+    let archive = match archive::create_archive(year, &conn)? {
+        Some(archive) => archive,
+        None => return Ok(None),
+    };
+
+    Ok(Some(export::JsonResponse {
+        filename: format!("hwpb-archive-{}-{}.json", year, Local::today().format("%Y-%m-%d")),
+        content: serde_json::to_vec(&archive)?,
+    }))
+}
@@ -10,6 +10,7 @@ pub struct Context {
     pub base: super::BaseContext,
     pub students: Vec<Student>,
     pub order: Order,
+    pub days: Vec<Day>,
 }
 
 #[derive(Serialize)]
@@ -17,12 +18,20 @@ pub struct Student {
     pub id: i32,
     pub matrikel: String,
     pub username: Option<String>,
+    pub email: Option<String>,
     pub given_name: String,
     pub family_name: String,
+    pub partner: Option<String>,
     pub groups: Vec<i32>,
     pub instructed: bool,
 }
 
+#[derive(Serialize)]
+pub struct Day {
+    pub id: i32,
+    pub name: String,
+}
+
 #[derive(Default, FromForm, Serialize)]
 pub struct Order {
     order: Option<String>,
@@ -49,8 +58,10 @@ pub fn load_students(year: i16, mut order: Order, conn: &PgConnection) -> Result
                 id: student.id,
                 matrikel: student.matrikel,
                 username: student.username,
+                email: student.email,
                 given_name: student.given_name,
                 family_name: student.family_name,
+                partner: student.partner,
                 groups: groups,
                 instructed: student.instructed,
             }
@@ -64,6 +75,8 @@ pub fn load_students(year: i16, mut order: Order, conn: &PgConnection) -> Result
             Some("family-name") => a.family_name.cmp(&b.family_name),
             Some("instructed") => a.instructed.cmp(&b.instructed),
             Some("username") => a.username.cmp(&b.username),
+            Some("email") => a.email.cmp(&b.email),
+            Some("partner") => a.partner.cmp(&b.partner),
             _ => order_by_groups(a, b),
         };
 
@@ -80,6 +93,20 @@ pub fn load_students(year: i16, mut order: Order, conn: &PgConnection) -> Result
     Ok((students, order))
 }
 
+/// Days of the year on which groups can be formed
+pub fn load_days(year: i16, conn: &PgConnection) -> Result<Vec<Day>> {
+    Ok(db::days::table
+        .filter(db::days::year.eq(year))
+        .order(db::days::name.asc())
+        .load::<db::Day>(conn)?
+        .into_iter()
+        .map(|day| Day {
+            id: day.id,
+            name: day.name,
+        })
+        .collect())
+}
+
 /// Order students without a group to the top, otherwise order by
 /// family and then first name.
 fn order_by_groups(a: &Student, b: &Student) -> Ordering {
@@ -0,0 +1,92 @@
+use chrono::Duration;
+use crate::db;
+use crate::errors::*;
+use crate::web::permissions::Permission;
+use crate::web::session::User;
+use crate::web::upload::Uploads;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use std::thread;
+use std::time;
+
+// Time between purges of expired entries
+const PURGE_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+/// Number of days deleted entries are kept in the trash
+pub struct TrashRetention(pub i64);
+
+/// Start a background thread that regularly removes expired entries from the
+/// trash and uploaded files that are no longer referenced
+pub fn spawn_purger(retention: i64, uploads: Uploads, pool: db::Pool) {
+    thread::spawn(move || {
+        loop {
+            if let Err(error) = purge(retention, &uploads, &pool) {
+                eprintln!("Error while purging the trash: {}", error);
+            }
+
+            thread::sleep(PURGE_INTERVAL);
+        }
+    });
+}
+
+fn purge(retention: i64, uploads: &Uploads, pool: &db::Pool) -> Result<()> {
+    let conn = pool.get().chain_err(|| "Could not get DB connection")?;
+
+    db::purge_trash(retention, &*conn)?;
+    uploads.purge(&*conn)?;
+
+    Ok(())
+}
+
+/// Permission needed to delete, restore or purge entries of the given kind
+pub fn trash_permission(kind: &str) -> Permission {
+    match kind {
+        "student" => Permission::Students,
+        _ => Permission::Manage,
+    }
+}
+
+#[derive(Serialize)]
+pub struct Context {
+    pub base: super::BaseContext,
+    pub entries: Vec<Entry>,
+    pub retention: i64,
+}
+
+#[derive(Serialize)]
+pub struct Entry {
+    id: i32,
+    kind: &'static str,
+    name: String,
+    author: String,
+    deleted_at: String,
+    purged_at: String,
+}
+
+/// Load the entries of the year the user may restore
+pub fn load_entries(year: i16, retention: i64, user: &User, conn: &PgConnection) -> Result<Vec<Entry>> {
+    Ok(db::trash::table
+        .filter(db::trash::year.eq(year))
+        .order(db::trash::deleted_at.desc())
+        .load::<db::TrashEntry>(conn)?
+        .into_iter()
+        .filter(|entry| user.has_permission(trash_permission(&entry.kind), year))
+        .map(|entry| {
+            Entry {
+                id: entry.id,
+                kind: match entry.kind.as_str() {
+                    "student" => "Teilnehmer",
+                    "experiment" => "Versuch",
+                    "task" => "Aufgabe",
+                    "day" => "Tag",
+                    _ => "Unbekannt",
+                },
+                name: entry.name,
+                author: entry.author,
+                deleted_at: entry.deleted_at.format("%Y-%m-%d %H:%M").to_string(),
+                purged_at: (entry.deleted_at + Duration::days(retention))
+                    .format("%Y-%m-%d").to_string(),
+            }
+        })
+        .collect())
+}
@@ -1,5 +1,6 @@
 use crate::db::{self, PgInetExpressionMethods};
 use crate::errors::*;
+use crate::web::permissions::Role;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
 
@@ -7,14 +8,29 @@ use diesel::pg::PgConnection;
 pub struct Context {
     pub base: super::BaseContext,
     pub tutors: Vec<Tutor>,
+    pub roles: Vec<(&'static str, &'static str)>,
     pub ip_whitelist: Option<Vec<WhitelistEntry>>,
+    pub course_admins: Vec<CourseAdmin>,
+    pub site_admins: Option<Vec<SiteAdmin>>,
 }
 
 #[derive(Serialize)]
 pub struct Tutor {
     pub id: i32,
     pub username: String,
-    pub is_admin: bool,
+    pub role: String,
+}
+
+#[derive(Serialize)]
+pub struct CourseAdmin {
+    pub id: i32,
+    pub username: String,
+}
+
+#[derive(Serialize)]
+pub struct SiteAdmin {
+    pub id: i32,
+    pub username: String,
 }
 
 #[derive(Serialize)]
@@ -34,12 +50,19 @@ pub fn load_tutors(year: i16, conn: &PgConnection) -> Result<Vec<Tutor>> {
             Tutor {
                 id: tutor.id,
                 username: tutor.username,
-                is_admin: tutor.is_admin,
+                role: tutor.role,
             }
         })
         .collect())
 }
 
+/// Ids and names of all roles that can be assigned to tutors
+pub fn roles() -> Vec<(&'static str, &'static str)> {
+    Role::ALL.iter()
+        .map(|role| (role.id(), role.name()))
+        .collect()
+}
+
 pub fn load_whitelist(year: i16, conn: &PgConnection) -> Result<Vec<WhitelistEntry>> {
     let whitelist = db::ip_whitelist::table
         .filter(db::ip_whitelist::year.eq(year))
@@ -51,3 +74,26 @@ pub fn load_whitelist(year: i16, conn: &PgConnection) -> Result<Vec<WhitelistEnt
         .map(|(id, ipnet)| WhitelistEntry { id, ipnet })
         .collect())
 }
+
+pub fn load_course_admins(year: i16, conn: &PgConnection) -> Result<Vec<CourseAdmin>> {
+    let admins = db::course_admins::table
+        .inner_join(db::years::table.on(db::years::course.eq(db::course_admins::course)))
+        .filter(db::years::id.eq(year))
+        .select((db::course_admins::id, db::course_admins::username))
+        .order(db::course_admins::username)
+        .load::<(i32, String)>(conn)?;
+
+    Ok(admins.into_iter()
+        .map(|(id, username)| CourseAdmin { id, username })
+        .collect())
+}
+
+pub fn load_site_admins(conn: &PgConnection) -> Result<Vec<SiteAdmin>> {
+    let admins = db::site_admins::table
+        .order(db::site_admins::username)
+        .load::<db::SiteAdmin>(conn)?;
+
+    Ok(admins.into_iter()
+        .map(|admin| SiteAdmin { id: admin.id, username: admin.username })
+        .collect())
+}
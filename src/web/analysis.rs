@@ -1,9 +1,11 @@
 use bit_vec::BitVec;
+use chrono::Utc;
 use crate::db;
 use crate::errors::*;
 use crate::web::admin::export::CsvResponse;
-use crate::web::session::{SiteAdmin, User};
-use crate::web::models::is_writable_year;
+use crate::web::permissions::Permission;
+use crate::web::session::User;
+use crate::web::models::{ensure_course, is_writable_year, load_students_for_groups, year_title, Score};
 use csv::Writer;
 use diesel::dsl::not;
 use diesel::prelude::*;
@@ -17,37 +19,71 @@ use std::collections::{HashMap, BTreeSet};
 struct Analysis {
     heading: &'static str,
     show_export_links: bool,
+    min_score: Option<i32>,
     students: Vec<Student>,
+    course: String,
     year: i16,
+    year_title: String,
     read_only_year: bool,
 }
 
-#[get("/passed/<year>")]
-pub fn passed(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_tutor_for(year)?;
+/// Condition for passing the course
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassCondition {
+    /// An accepted elaboration for every experiment of the year
+    Elaborations,
+    /// Accepted elaborations and at least the given percentage of the
+    /// weighted points of every experiment
+    MinimumScore(i32),
+}
 
-    let (elaborations_by_student, _) =
-        load_elaborations_by_student(year, None, Some(true), &*conn)?;
+impl PassCondition {
+    /// Parse the optional minimum score in percent given in the query string
+    pub fn from_min_score(min_score: Option<i32>) -> Result<PassCondition> {
+        match min_score {
+            None => Ok(PassCondition::Elaborations),
+            Some(percent) if percent >= 0 && percent <= 100 =>
+                Ok(PassCondition::MinimumScore(percent)),
+            Some(percent) => Err(format!("Invalid minimum score {}", percent).into()),
+        }
+    }
 
-    let students = elaborations_by_student.into_iter()
-        .filter_map(|(student, elaboration)| {
-            if elaboration.all() { Some(student) } else { None }
-        })
-        .collect();
+    fn min_score(self) -> Option<i32> {
+        match self {
+            PassCondition::Elaborations => None,
+            PassCondition::MinimumScore(percent) => Some(percent),
+        }
+    }
+}
+
+#[get("/<course>/<year>/passed?<min_score>")]
+pub fn passed(course: String, year: i16, min_score: Option<i32>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::View, year)?;
+    ensure_course(&course, year, &conn)?;
+
+    let condition = PassCondition::from_min_score(min_score)?;
+    let students = load_passed_students(year, condition, &*conn)?;
 
     let context = Analysis {
         heading: "Zugelassene Studenten",
-        show_export_links: user.is_site_admin(),
+        show_export_links: user.is_course_admin_for(year),
+        min_score: condition.min_score(),
         students: students,
+        course: course,
         year: year,
+        year_title: year_title(year, &conn)?,
         read_only_year: !is_writable_year(year, &conn)?,
     };
 
     Ok(Template::render("analysis", &context))
 }
 
-#[get("/passed-complete/<year>")]
-pub fn passed_complete(year: i16, conn: db::Conn, _user: SiteAdmin) -> Result<CsvResponse> {
+#[get("/<course>/<year>/passed-complete?<min_score>")]
+pub fn passed_complete(course: String, year: i16, min_score: Option<i32>, conn: db::Conn, user: User) -> Result<CsvResponse> {
+    user.ensure_course_admin_for(year)?;
+    ensure_course(&course, year, &conn)?;
+    let condition = PassCondition::from_min_score(min_score)?;
+
     // Load all students
     let mut students = db::students::table
         .filter(db::students::year.eq(year))
@@ -55,12 +91,9 @@ pub fn passed_complete(year: i16, conn: db::Conn, _user: SiteAdmin) -> Result<Cs
         .load::<db::Student>(&*conn)?;
 
     // Load all students that pass the course
-    let (elaborations_by_student, _) =
-        load_elaborations_by_student(year, None, Some(true), &*conn)?;
-    let passed_students: BTreeSet<_> = elaborations_by_student.into_iter()
-        .filter_map(|(student, elaboration)| {
-            if elaboration.all() { Some(student.matrikel) } else { None }
-        })
+    let passed_students: BTreeSet<_> = load_passed_students(year, condition, &*conn)?
+        .into_iter()
+        .map(|student| student.matrikel)
         .collect();
 
     // Sort passed students to the front
@@ -83,57 +116,87 @@ pub fn passed_complete(year: i16, conn: db::Conn, _user: SiteAdmin) -> Result<Cs
         csv.write_record(None::<&[u8]>)?; // Finish record
     }
 
+    // Term labels may contain slashes, e.g. "Wintersemester 2019/20"
+    let title = year_title(year, &conn)?;
     Ok(CsvResponse {
-        filename: format!("Hardwarepraktikum-{}.csv", year),
+        filename: format!("{}.csv", title.replace(|c: char| c == ' ' || c == '/', "-")),
         content: csv.into_inner().chain_err(|| "Could not finalize csv writer")?
     })
 }
 
-#[get("/missing-reworks/<year>")]
-pub fn missing_reworks(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_tutor_for(year)?;
-
-    let (tasks_by_student, _) = load_tasks_by_student(year, false, &*conn)?;
-    let (elaborations_by_student, _) =
-        load_elaborations_by_student(year, Some(true), Some(false), &*conn)?;
-
-    let students_with_all_tasks = tasks_by_student.into_iter()
-        .filter_map(|(student, tasks)| {
-            // Only consider students that have completed all tasks
-            if tasks.all() { Some(student) } else { None }
-        })
-        .collect();
-
-    let students_with_unaccepted_reworks = elaborations_by_student.into_iter()
-        .filter_map(|(student, elaboration)| {
-            // Consider all students with at least one unaccepted rework
-            if elaboration.any() { Some(student) } else { None }
-        })
-        .collect::<BTreeSet<_>>();
+#[derive(Serialize)]
+struct OverdueReworks {
+    reworks: Vec<OverdueRework>,
+    course: String,
+    year: i16,
+    year_title: String,
+    read_only_year: bool,
+}
 
-    let mut students: Vec<_> = students_with_unaccepted_reworks
-        .intersection(&students_with_all_tasks)
-        .cloned()
-        .collect();
+#[derive(Serialize)]
+struct OverdueRework {
+    group: i32,
+    desk: i32,
+    day: String,
+    experiment: String,
+    rework_due: String,
+    grader: Option<String>,
+    students: Vec<String>,
+}
 
-    // try to sort students from the same group together
-    students.sort_by(|left, right| {
-        // iter is always sorted, so next_back is the max element
-        left.groups.iter().next_back().cmp(
-            &right.groups.iter().next_back())
-    });
+#[get("/<course>/<year>/overdue-reworks")]
+pub fn overdue_reworks(course: String, year: i16, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::View, year)?;
+    ensure_course(&course, year, &conn)?;
 
-    let context = Analysis {
-        heading: "Fehlende Nachbesserungen",
-        show_export_links: false,
-        students: students,
+    let context = OverdueReworks {
+        reworks: load_overdue_reworks(year, &conn)?,
+        course: course,
         year: year,
+        year_title: year_title(year, &conn)?,
         read_only_year: !is_writable_year(year, &conn)?,
     };
 
-    Ok(Template::render("analysis", &context))
+    Ok(Template::render("analysis-reworks", &context))
 }
 
+// Load all elaborations that still need rework after their due date, the
+// longest overdue first
+fn load_overdue_reworks(year: i16, conn: &PgConnection) -> Result<Vec<OverdueRework>> {
+    use db::{days, elaborations, experiments, groups};
+
+    let today = Utc::today().naive_utc();
+    let reworks = elaborations::table
+        .inner_join(groups::table.inner_join(days::table))
+        .inner_join(experiments::table)
+        .filter(days::year.eq(year))
+        .filter(elaborations::rework_required.eq(true))
+        .filter(elaborations::accepted.eq(false))
+        .filter(elaborations::rework_due.lt(today))
+        .order((elaborations::rework_due.asc(), groups::desk.asc()))
+        .select((elaborations::all_columns, groups::all_columns, days::name, experiments::name))
+        .load::<(db::Elaboration, db::Group, String, String)>(conn)?;
+
+    let groups = reworks.iter().map(|(_, group, _, _)| group.clone()).collect();
+    let students: HashMap<_,_> = load_students_for_groups(groups, conn)?
+        .into_iter()
+        .map(|(group, students)| (group.id, students))
+        .collect();
+
+    Ok(reworks.into_iter()
+        .map(|(elaboration, group, day, experiment)| OverdueRework {
+            group: group.id,
+            desk: group.desk,
+            day: day,
+            experiment: experiment,
+            rework_due: elaboration.rework_due.map(|date| date.to_string()).unwrap_or_default(),
+            grader: elaboration.grader,
+            students: students.get(&group.id)
+                .map(|students| students.iter().map(db::Student::name).collect())
+                .unwrap_or_default(),
+        })
+        .collect())
+}
 
 #[derive(Clone, Debug, Eq, Serialize)]
 pub struct Student {
@@ -161,15 +224,130 @@ impl PartialEq for Student {
     }
 }
 
+// Load all students that passed the course under the given condition, which
+// always requires an accepted elaboration for every experiment of the year
+pub fn load_passed_students(year: i16, condition: PassCondition, conn: &PgConnection) -> Result<Vec<Student>> {
+    let (elaborations_by_student, _) =
+        load_elaborations_by_student(year, None, Some(true), conn)?;
+
+    let students = elaborations_by_student.into_iter()
+        .filter_map(|(student, elaboration)| {
+            if elaboration.all() { Some(student) } else { None }
+        });
+
+    match condition {
+        PassCondition::Elaborations => Ok(students.collect()),
+        PassCondition::MinimumScore(percent) => {
+            let (scores_by_student, experiments) = load_scores_by_student(year, conn)?;
+            let scores_by_student: HashMap<_,_> = scores_by_student.into_iter()
+                .map(|(student, scores)| (student.id, scores))
+                .collect();
+            let no_scores = empty_scores(&experiments, conn)?;
+
+            Ok(students
+                .filter(|student| {
+                    scores_by_student.get(&student.id)
+                        .unwrap_or(&no_scores)
+                        .iter()
+                        .all(|score| score.reaches(percent))
+                })
+                .collect())
+        }
+    }
+}
+
+// Load the tasks of all experiments of the year, in the same order as the
+// experiments
+fn load_tasks_by_experiment(experiments: &[db::Experiment], conn: &PgConnection) -> Result<Vec<Vec<db::Task>>> {
+    Ok(db::Task::belonging_to(experiments)
+        .order((db::tasks::position.asc(), db::tasks::name.asc()))
+        .load::<db::Task>(conn)?
+        .grouped_by(experiments))
+}
+
+// Scores of a student who did not complete any task yet
+fn empty_scores(experiments: &[db::Experiment], conn: &PgConnection) -> Result<Vec<Score>> {
+    Ok(load_tasks_by_experiment(experiments, conn)?
+        .iter()
+        .map(|tasks| {
+            let mut score = Score::default();
+            for task in tasks {
+                score.add(task, None);
+            }
+            score
+        })
+        .collect())
+}
+
+// Load all students with the score of every experiment, where the best
+// partial credit counts if a student completed a task in several groups
+pub fn load_scores_by_student(year: i16, conn: &PgConnection)
+                              -> Result<(Vec<(Student, Vec<Score>)>, Vec<db::Experiment>)> {
+    let experiments = db::experiments::table
+        .filter(db::experiments::year.eq(year))
+        .order((db::experiments::position.asc(), db::experiments::name.asc()))
+        .load::<db::Experiment>(conn)?;
+    let tasks = load_tasks_by_experiment(&experiments, conn)?;
+
+    let scores_by_student = db::completions::table
+        .inner_join(db::tasks::table)
+        .inner_join(db::group_mappings::table
+            .on(db::completions::group_id.eq(db::group_mappings::group_id)))
+        .inner_join(db::students::table
+            .on(db::group_mappings::student_id.eq(db::students::id)))
+        .filter(db::students::year.eq(year))
+        .order(db::students::id)
+        .select((db::completions::all_columns, db::tasks::points, db::students::all_columns))
+        .load::<(db::Completion, i16, db::Student)>(conn)?.into_iter()
+        .group_by(|&(_, _, ref student)| student.id).into_iter()
+        .map(|(_, completions)| {
+            let mut best_points: HashMap<i32, i16> = HashMap::new();
+            let mut groups = BTreeSet::new();
+
+            let mut student = None;
+
+            for (completion, max_points, db_student) in completions {
+                let points = completion.points.unwrap_or(max_points);
+                let best = best_points.entry(completion.task_id).or_insert(points);
+                *best = (*best).max(points);
+                groups.insert(completion.group_id);
+
+                student.get_or_insert(db_student);
+            }
+
+            let student = student.expect("empty group (itertools)");
+
+            let scores = tasks.iter()
+                .map(|tasks| {
+                    let mut score = Score::default();
+                    for task in tasks {
+                        score.add(task, best_points.get(&task.id).map(|&points| Some(points)));
+                    }
+                    score
+                })
+                .collect();
+
+            (Student {
+                id: student.id,
+                name: student.name(),
+                matrikel: student.matrikel,
+                username: student.username,
+                groups: groups,
+                instructed: student.instructed,
+            }, scores)
+        })
+        .collect();
+
+    Ok((scores_by_student, experiments))
+}
+
 // Load all students with their completed tasks
 pub fn load_tasks_by_student(year: i16, include_extra_tasks: bool, conn: &PgConnection)
                              -> Result<(Vec<(Student, BitVec)>, Vec<db::Task>)> {
     let mut tasks_query = db::tasks::table
-        .filter(db::tasks::experiment_id.eq_any(
-            db::experiments::table
-                .filter(db::experiments::year.eq(year))
-                .select(db::experiments::id)
-        ))
+        .inner_join(db::experiments::table)
+        .filter(db::experiments::year.eq(year))
+        .select(db::tasks::all_columns)
         .into_boxed();
 
     // Ignore tasks that start with [Zz] (Zusatzaufgabe) if requested
@@ -178,7 +356,8 @@ pub fn load_tasks_by_student(year: i16, include_extra_tasks: bool, conn: &PgConn
     }
 
     let tasks = tasks_query
-        .order((db::tasks::experiment_id.asc(), db::tasks::name.asc()))
+        .order((db::experiments::position.asc(), db::experiments::name.asc(),
+                db::tasks::position.asc(), db::tasks::name.asc()))
         .load::<db::Task>(conn)?;
 
     // Load map (task_id, index) where the indices start at 0 and are
@@ -237,7 +416,7 @@ pub fn load_elaborations_by_student(year: i16, rework_required: Option<bool>,
                                     -> Result<(Vec<(Student, BitVec)>, Vec<db::Experiment>)> {
     let experiments = db::experiments::table
         .filter(db::experiments::year.eq(year))
-        .order(db::experiments::id.asc())
+        .order((db::experiments::position.asc(), db::experiments::name.asc()))
         .load::<db::Experiment>(conn)?;
 
     // Generate map (experiment_id, index) where the indices start at 0 and are
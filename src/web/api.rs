@@ -1,14 +1,25 @@
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate, Utc};
 use crate::db::{self, PgInetExpressionMethods};
-use crate::errors::{ApiError, ApiResult, ResultExt};
-use crate::web::models::find_writable_year;
+use crate::errors::{ApiError, ApiResult, Error, ErrorKind, ResultExt};
+use crate::mail::Mailer;
+use crate::web::admin::{archive, trash_permission};
+use crate::web::admin::export::{FileResponse, PdfResponse};
+use crate::web::grouping;
+use crate::web::import;
+use crate::web::models::{ensure_free_desk, ensure_group_tutor, find_writable_year};
+use crate::web::permissions::{Permission, Role};
 use crate::web::push;
+use crate::web::regroup;
+use crate::web::room::Layout;
 use crate::web::session::{SiteAdmin, User};
-use csv::ReaderBuilder;
+use crate::web::upload::{self, Uploads};
 use diesel::prelude::*;
-use rocket::Data;
-use rocket::http::Status;
+use rocket::{Data, State};
+use rocket::http::{ContentType, Status};
+use rocket::request::Form;
+use rocket::response::status;
 use rocket_contrib::json::Json;
+use std::io::Read;
 
 fn add_audit_log(year: i16, group: Option<i32>, author: &str, conn: &PgConnection, change: &str) -> ApiResult<()> {
     let log = db::NewAuditLog {
@@ -29,55 +40,138 @@ fn add_audit_log(year: i16, group: Option<i32>, author: &str, conn: &PgConnectio
 #[post("/group", data = "<group>")]
 pub fn post_group(group: Json<db::NewGroup>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
-        let id: i32 = diesel::insert_into(db::groups::table)
-            .values(&*group)
-            .returning(db::groups::id)
-            .get_result(&*conn)?;
+        insert_group(&group, &conn, &user)?;
+
+        Ok(Status::NoContent)
+    })
+}
 
-        let year = find_writable_year(id, &*conn)?;
-        user.ensure_tutor_for(year)?;
+// Insert single group with audit log and push event without a transaction
+fn insert_group(group: &db::NewGroup, conn: &PgConnection, user: &User) -> ApiResult<i32> {
+    let (year, day_name) = db::days::table.find(group.day_id)
+        .select((db::days::year, db::days::name))
+        .get_result::<(i16, String)>(conn)?;
+    user.ensure_permission(Permission::Groups, year)?;
 
-        let day_name: String = db::days::table.find(group.day_id)
-            .select(db::days::name).get_result(&*conn)?;
-        add_audit_log(year, Some(id), user.name(), &*conn,
-            &format!("Create new group at desk {} on {} (#{}) with comment '{}'",
-                group.desk, day_name, group.day_id, group.comment))?;
+    ensure_free_desk(group.day_id, group.desk, None, conn)?;
 
-        push::SERVER.push(year, "group", &push::Group::New {
-            day: group.day_id,
-        }).ok();
+    let id: i32 = diesel::insert_into(db::groups::table)
+        .values(group)
+        .returning(db::groups::id)
+        .get_result(conn)?;
+    find_writable_year(id, conn)?;
 
-        Ok(Status::NoContent)
+    add_audit_log(year, Some(id), user.name(), conn,
+        &format!("Create new group at desk {} on {} (#{}) with comment '{}'",
+            group.desk, day_name, group.day_id, group.comment))?;
+
+    push::SERVER.push(year, "group", &push::Group::New {
+        day: group.day_id,
+    }).ok();
+
+    Ok(id)
+}
+
+/// Size of the groups to form automatically
+#[derive(Deserialize)]
+pub struct GroupFormation {
+    size: usize,
+}
+
+/// Form groups on the day of all students of its year without a group, keeping
+/// preferred partners together, or only propose them for dry runs
+#[post("/day/<day>/groups?<dry_run>", data = "<formation>")]
+pub fn post_day_groups(day: i32, formation: Json<GroupFormation>, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<grouping::Formation>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let full_day = db::days::table
+            .find(day)
+            .get_result::<db::Day>(&*conn)?;
+        user.ensure_permission(Permission::Groups, full_day.year)?;
+
+        let proposal = grouping::propose(&full_day, formation.size, &conn)?;
+        if dry_run {
+            return Ok(Json(proposal));
+        }
+
+        for group in &proposal.groups {
+            let id = insert_group(&db::NewGroup {
+                desk: group.desk,
+                day_id: day,
+                comment: String::new(),
+            }, &conn, &user)?;
+
+            for &student in &group.students {
+                insert_group_student(id, student, &conn, &user)?;
+            }
+        }
+
+        Ok(Json(proposal))
     })
 }
 
-#[put("/group/<group>/completed/<task>")]
-pub fn put_completion(group: i32, task: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+// Maximum size of the points of a completion
+const POINTS_SIZE_LIMIT: u64 = 64;
+
+// Read the points of a completion, where an empty body means all points
+fn read_points(data: Data) -> ApiResult<Option<i16>> {
+    let mut body = String::new();
+    data.open().take(POINTS_SIZE_LIMIT + 1).read_to_string(&mut body)
+        .map_err(|_| ApiError::ConstraintViolation)?;
+    if body.len() as u64 > POINTS_SIZE_LIMIT {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    match body.trim() {
+        "" => Ok(None),
+        points => serde_json::from_str(points)
+            .map(Some)
+            .map_err(|_| ApiError::ConstraintViolation),
+    }
+}
+
+// Partial credit can be given as points, otherwise the task is completed
+// with all of its points
+#[put("/group/<group>/completed/<task>", data = "<points>")]
+pub fn put_completion(group: i32, task: i32, points: Data, conn: db::Conn, user: User) -> ApiResult<Status> {
     let completion = db::Completion {
         group_id: group,
         task_id: task,
+        points: read_points(points)?,
     };
 
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Completions, year)?;
+        ensure_group_tutor(&user, year, group, &conn)?;
+
+        let (experiment_name, full_task) = db::tasks::table.find(task)
+            .inner_join(db::experiments::table)
+            .select((db::experiments::name, db::tasks::all_columns))
+            .get_result::<(String, db::Task)>(&*conn)?;
+        if let Some(points) = completion.points {
+            if points < 0 || points > full_task.points {
+                return Err(ApiError::ConstraintViolation);
+            }
+        }
 
         diesel::insert_into(db::completions::table)
             .values(&completion)
-            .on_conflict_do_nothing()
+            .on_conflict((db::completions::group_id, db::completions::task_id))
+                .do_update()
+                .set(db::completions::points.eq(completion.points))
             .execute(&*conn)?;
 
-        let (experiment_name, task_name) = db::tasks::table.find(task)
-            .inner_join(db::experiments::table)
-            .select((db::experiments::name, db::tasks::name))
-            .get_result::<(String, String)>(&*conn)?;
+        let points = completion.points.unwrap_or(full_task.points);
         add_audit_log(year, Some(group), user.name(), &*conn,
-            &format!("Mark task {} (#{}) of {} as completed",
-                task_name, task, experiment_name))?;
+            &format!("Mark task {} (#{}) of {} as completed with {} of {} points",
+                full_task.name, task, experiment_name, points, full_task.points))?;
 
         push::SERVER.push(year, "completion", &push::Completion {
             group, task,
             completed: true,
+            points: completion.points,
         }).ok();
 
         Ok(Status::NoContent)
@@ -88,7 +182,8 @@ pub fn put_completion(group: i32, task: i32, conn: db::Conn, user: User) -> ApiR
 pub fn delete_completion(group: i32, task: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Completions, year)?;
+        ensure_group_tutor(&user, year, group, &conn)?;
 
         diesel::delete(db::completions::table
             .filter(db::completions::group_id.eq(group))
@@ -106,49 +201,174 @@ pub fn delete_completion(group: i32, task: i32, conn: db::Conn, user: User) -> A
         push::SERVER.push(year, "completion", &push::Completion {
             group, task,
             completed: false,
+            points: None,
         }).ok();
 
         Ok(Status::NoContent)
     })
 }
 
+// Reworks are due two weeks after they were requested unless a date is given
+const REWORK_PERIOD_DAYS: i64 = 14;
+
 #[derive(Deserialize)]
 pub struct Elaboration {
     rework_required: bool,
     accepted: bool,
+    // The current grading is kept if no new one is given
+    #[serde(default)]
+    grading: Option<Grading>,
+}
+
+#[derive(Deserialize)]
+pub struct Grading {
+    points: Option<i16>,
+    grade: Option<String>,
+    feedback: String,
+    rework_due: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ReworkMail<'a> {
+    title: String,
+    day: &'a str,
+    desk: i32,
+    experiment: &'a str,
+    students: Vec<String>,
+    due: Option<String>,
 }
 
 #[put("/group/<group>/elaboration/<experiment>", data = "<elaboration>")]
-pub fn put_elaboration(group: i32, experiment: i32, elaboration: Json<Elaboration>, conn: db::Conn, user: User) -> ApiResult<Status> {
-    let elaboration = db::Elaboration {
-        group_id: group,
-        experiment_id: experiment,
-        rework_required: elaboration.rework_required,
-        accepted: elaboration.accepted,
+pub fn put_elaboration(group: i32, experiment: i32, elaboration: Json<Elaboration>, mailer: State<Mailer>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    let status = (elaboration.rework_required, elaboration.accepted);
+    let needs_rework = status == (true, false);
+
+    let rework_due: Option<NaiveDate> = match elaboration.grading {
+        Some(Grading { rework_due: Some(ref date), .. }) => Some(date.parse().chain_err(|| "Invalid date")?),
+        _ => None,
     };
+    if let Some(Grading { points: Some(points), .. }) = elaboration.grading {
+        if points < 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+    }
 
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Elaborations, year)?;
+        ensure_group_tutor(&user, year, group, &conn)?;
+
+        let previous = db::elaborations::table
+            .find((group, experiment))
+            .get_result::<db::Elaboration>(&*conn)
+            .optional()?;
+        let previous_status = previous.as_ref().map(|e| (e.rework_required, e.accepted));
+
+        // Every change of the status counts as a new submission, while
+        // reworks keep their due date until they are accepted
+        let today = Utc::today().naive_utc();
+        let submitted_on = match previous {
+            Some(ref previous) if previous_status == Some(status) => previous.submitted_on,
+            _ => Some(today),
+        };
+        let rework_due = match (rework_due, previous.as_ref()) {
+            (Some(date), _) => Some(date),
+            (None, Some(previous)) if !needs_rework || previous_status == Some(status) => previous.rework_due,
+            (None, _) if needs_rework => Some(today + Duration::days(REWORK_PERIOD_DAYS)),
+            (None, _) => None,
+        };
+
+        let record = match (elaboration.grading.as_ref(), previous) {
+            (Some(grading), _) => db::Elaboration {
+                group_id: group,
+                experiment_id: experiment,
+                rework_required: elaboration.rework_required,
+                accepted: elaboration.accepted,
+                points: grading.points,
+                grade: grading.grade.as_ref()
+                    .map(|grade| grade.trim().to_string())
+                    .filter(|grade| !grade.is_empty()),
+                feedback: grading.feedback.trim().to_string(),
+                grader: Some(user.name().to_string()),
+                submitted_on, rework_due,
+            },
+            // The grading stays attributed to whoever graded it
+            (None, Some(previous)) => db::Elaboration {
+                rework_required: elaboration.rework_required,
+                accepted: elaboration.accepted,
+                submitted_on, rework_due,
+                ..previous
+            },
+            (None, None) => db::Elaboration {
+                group_id: group,
+                experiment_id: experiment,
+                rework_required: elaboration.rework_required,
+                accepted: elaboration.accepted,
+                points: None,
+                grade: None,
+                feedback: String::new(),
+                grader: Some(user.name().to_string()),
+                submitted_on, rework_due,
+            },
+        };
 
         diesel::insert_into(db::elaborations::table)
-            .values(&elaboration)
+            .values(&record)
             .on_conflict((db::elaborations::group_id, db::elaborations::experiment_id))
                 .do_update()
-                .set(&elaboration)
+                .set(&record)
+            .execute(&*conn)?;
+
+        diesel::insert_into(db::elaboration_submissions::table)
+            .values(&db::NewElaborationSubmission {
+                group_id: group,
+                experiment_id: experiment,
+                rework_required: record.rework_required,
+                accepted: record.accepted,
+                points: record.points,
+                grade: record.grade.as_ref().map(String::as_str),
+                feedback: &record.feedback,
+                grader: user.name(),
+            })
             .execute(&*conn)?;
 
-        let status = match (elaboration.rework_required, elaboration.accepted) {
+        let status_name = match status {
             (false, false) => "submitted",
             (false,  true) => "accepted",
             ( true, false) => "needing rework",
             ( true,  true) => "rework accepted",
         };
+        let mut details = vec![];
+        if let Some(points) = record.points {
+            details.push(format!("{} points", points));
+        }
+        if let Some(ref grade) = record.grade {
+            details.push(format!("grade '{}'", grade));
+        }
+        if needs_rework {
+            if let Some(due) = record.rework_due {
+                details.push(format!("rework due {}", due));
+            }
+        }
+        let details = match details.is_empty() {
+            true => String::new(),
+            false => format!(" ({})", details.join(", ")),
+        };
         let experiment_name: String = db::experiments::table.find(experiment)
             .select(db::experiments::name).get_result(&*conn)?;
         add_audit_log(year, Some(group), user.name(), &*conn,
-            &format!("Mark elaboration of {} (#{}) as {}",
-                experiment_name, experiment, status))?;
+            &format!("Mark elaboration of {} (#{}) as {}{}",
+                experiment_name, experiment, status_name, details))?;
+        if elaboration.grading.is_some() && !record.feedback.is_empty() {
+            add_audit_log(year, Some(group), user.name(), &*conn,
+                &format!("Set feedback on elaboration of {} (#{}) to '{}'",
+                    experiment_name, experiment, record.feedback))?;
+        }
+
+        // Notify the students of the group once their elaboration needs rework
+        if needs_rework && previous_status != Some((true, false)) {
+            notify_rework(year, group, &experiment_name, record.rework_due, &mailer, &conn)?;
+        }
 
         push::SERVER.push(year, "elaboration", &push::Elaboration {
             group, experiment,
@@ -161,11 +381,41 @@ pub fn put_elaboration(group: i32, experiment: i32, elaboration: Json<Elaboratio
     })
 }
 
+// Enqueue a mail for every student of the group with an email address
+fn notify_rework(year: i16, group: i32, experiment: &str, due: Option<NaiveDate>, mailer: &Mailer, conn: &PgConnection) -> ApiResult<()> {
+    let (desk, day) = db::groups::table
+        .inner_join(db::days::table)
+        .filter(db::groups::id.eq(group))
+        .select((db::groups::desk, db::days::name))
+        .get_result::<(i32, String)>(conn)?;
+    let students = db::students::table
+        .inner_join(db::group_mappings::table)
+        .filter(db::group_mappings::group_id.eq(group))
+        .select(db::students::all_columns)
+        .load::<db::Student>(conn)?;
+
+    let recipients: Vec<_> = students.iter()
+        .filter_map(|student| student.email.clone())
+        .collect();
+    let context = ReworkMail {
+        title: super::models::year_title(year, conn)?,
+        desk, experiment,
+        day: &day,
+        students: students.iter().map(db::Student::name).collect(),
+        due: due.map(|due| due.format("%d.%m.%Y").to_string()),
+    };
+
+    mailer.enqueue(year, &recipients, "rework-required.txt", &context, conn)?;
+
+    Ok(())
+}
+
 #[delete("/group/<group>/elaboration/<experiment>")]
 pub fn delete_elaboration(group: i32, experiment: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Elaborations, year)?;
+        ensure_group_tutor(&user, year, group, &conn)?;
 
         diesel::delete(db::elaborations::table
             .filter(db::elaborations::group_id.eq(group))
@@ -189,11 +439,68 @@ pub fn delete_elaboration(group: i32, experiment: i32, conn: db::Conn, user: Use
     })
 }
 
+#[post("/group/<group>/elaboration/<experiment>/file?<filename>", data = "<file>")]
+pub fn post_elaboration_file(group: i32, experiment: i32, filename: String, file: Data, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    // The file is only read for users who may upload it
+    let year = find_writable_year(group, &*conn)?;
+    user.ensure_permission(Permission::Elaborations, year)?;
+    ensure_group_tutor(&user, year, group, &conn)?;
+
+    let data = uploads.read(file.open())?
+        .map_err(ApiError::Invalid)?;
+    let filename = upload::sanitize_filename(&filename);
+
+    conn.transaction(|| {
+        let file = store_elaboration_file(year, group, experiment, &filename, &data, &uploads, user.name(), &conn)?;
+
+        Ok(Json(file))
+    })
+}
+
+// Store an uploaded elaboration and log the upload inside a transaction
+pub fn store_elaboration_file(year: i16, group: i32, experiment: i32, filename: &str, data: &[u8], uploads: &Uploads, author: &str, conn: &PgConnection) -> ApiResult<i32> {
+    let (experiment_year, experiment_name) = db::experiments::table
+        .find(experiment)
+        .select((db::experiments::year, db::experiments::name))
+        .get_result::<(i16, String)>(conn)?;
+    if experiment_year != year {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    let file = uploads.store(group, experiment, filename, data, author, conn)?;
+
+    add_audit_log(year, Some(group), author, conn,
+        &format!("Upload version {} of the elaboration of {} (#{}) as {} (#{})",
+            file.version, experiment_name, experiment, file.filename, file.id))?;
+
+    Ok(file.id)
+}
+
+#[get("/elaboration-file/<file>")]
+pub fn get_elaboration_file(file: i32, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<PdfResponse> {
+    let (file, year) = db::elaboration_files::table
+        .inner_join(db::groups::table.inner_join(db::days::table))
+        .filter(db::elaboration_files::id.eq(file))
+        .select((db::elaboration_files::all_columns, db::days::year))
+        .get_result::<(db::ElaborationFile, i16)>(&*conn)?;
+
+    // Students may download the elaborations of their own groups
+    if !user.has_permission(Permission::View, year) && !upload::is_group_member(&user, file.group_id, &conn)? {
+        return Err(ApiError::Other(format!("User {} may not access file #{}",
+            user.name(), file.id).into()));
+    }
+
+    Ok(PdfResponse {
+        content: uploads.load(file.id)?,
+        filename: file.filename,
+    })
+}
+
 #[put("/group/<group>/comment", data = "<comment>")]
 pub fn put_group_comment(group: i32, comment: Json<String>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Groups, year)?;
 
         let comment = comment.into_inner();
         diesel::update(db::groups::table.filter(db::groups::id.eq(group)))
@@ -217,9 +524,13 @@ pub fn put_group_comment(group: i32, comment: Json<String>, conn: db::Conn, user
 pub fn put_group_desk(group: i32, desk: Json<i32>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Groups, year)?;
 
         let desk = desk.into_inner();
+        let day: i32 = db::groups::table.find(group)
+            .select(db::groups::day_id).get_result(&*conn)?;
+        ensure_free_desk(day, desk, Some(group), &conn)?;
+
         diesel::update(db::groups::table.filter(db::groups::id.eq(group)))
             .set(db::groups::desk.eq(desk))
             .execute(&*conn)?;
@@ -233,39 +544,95 @@ pub fn put_group_desk(group: i32, desk: Json<i32>, conn: db::Conn, user: User) -
     })
 }
 
-#[put("/group/<group>/student/<student>")]
-pub fn put_group_student(group: i32, student: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
-    let mapping = db::GroupMapping {
-        student_id: student,
-        group_id: group,
-    };
+/// Target day and optionally a new desk of a moved group
+#[derive(Deserialize)]
+pub struct GroupMove {
+    day: i32,
+    desk: Option<i32>,
+}
 
+// Completions and elaborations belong to the group and are moved along
+#[put("/group/<group>/day", data = "<target>")]
+pub fn put_group_day(group: i32, target: Json<GroupMove>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Groups, year)?;
+
+        let (full_group, from_day) = db::groups::table
+            .inner_join(db::days::table)
+            .filter(db::groups::id.eq(group))
+            .get_result::<(db::Group, db::Day)>(&*conn)?;
+        let to_day = db::days::table
+            .find(target.day)
+            .get_result::<db::Day>(&*conn)?;
+        if to_day.year != year {
+            return Err(ApiError::ConstraintViolation);
+        }
 
-        diesel::insert_into(db::group_mappings::table)
-            .values(&mapping)
-            .execute(&*conn)?;
+        let desk = target.desk.unwrap_or(full_group.desk);
+        ensure_free_desk(to_day.id, desk, Some(group), &conn)?;
+
+        diesel::update(db::groups::table.find(group))
+            .set((
+                db::groups::day_id.eq(to_day.id),
+                db::groups::desk.eq(desk),
+            ))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
 
-        let full_student = db::students::table.find(student)
-            .get_result::<db::Student>(&*conn)?;
         add_audit_log(year, Some(group), user.name(), &*conn,
-            &format!("Add {} (#{}) to group", full_student.name(), student))?;
+            &format!("Move group from desk {} on {} (#{}) to desk {} on {} (#{})",
+                full_group.desk, from_day.name, from_day.id, desk, to_day.name, to_day.id))?;
 
-        push::SERVER.push(year, "student", &push::Student::Add {
-            group, student, name: full_student.name()
+        push::SERVER.push(year, "group", &push::Group::Move {
+            group, from: from_day.id, to: to_day.id
         }).ok();
 
         Ok(Status::NoContent)
     })
 }
 
+#[put("/group/<group>/student/<student>")]
+pub fn put_group_student(group: i32, student: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        insert_group_student(group, student, &conn, &user)?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+// Add single student to a group with audit log and push event without a
+// transaction
+fn insert_group_student(group: i32, student: i32, conn: &PgConnection, user: &User) -> ApiResult<()> {
+    let mapping = db::GroupMapping {
+        student_id: student,
+        group_id: group,
+    };
+
+    let year = find_writable_year(group, conn)?;
+    user.ensure_permission(Permission::Groups, year)?;
+
+    diesel::insert_into(db::group_mappings::table)
+        .values(&mapping)
+        .execute(conn)?;
+
+    let full_student = db::students::table.find(student)
+        .get_result::<db::Student>(conn)?;
+    add_audit_log(year, Some(group), user.name(), conn,
+        &format!("Add {} (#{}) to group", full_student.name(), student))?;
+
+    push::SERVER.push(year, "student", &push::Student::Add {
+        group, student, name: full_student.name()
+    }).ok();
+
+    Ok(())
+}
+
 #[delete("/group/<group>/student/<student>")]
 pub fn delete_group_student(group: i32, student: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let year = find_writable_year(group, &*conn)?;
-        user.ensure_tutor_for(year)?;
+        user.ensure_permission(Permission::Groups, year)?;
 
         let num_completions: i64 = db::completions::table
             .filter(db::completions::group_id.eq(group))
@@ -295,6 +662,81 @@ pub fn delete_group_student(group: i32, student: i32, conn: db::Conn, user: User
     })
 }
 
+// Describe the students and resolved conflicts of a merge or split for the log
+fn describe_regrouping(preview: &regroup::Preview) -> String {
+    let conflicts: Vec<_> = preview.conflicts.iter()
+        .map(|conflict| format!("{} of {} from group #{}", match conflict.kind {
+            regroup::ConflictKind::Completion => "completion",
+            regroup::ConflictKind::Elaboration => "elaboration",
+        }, conflict.name, conflict.kept))
+        .collect();
+
+    format!("students {}, {} completions, {} elaborations and {} files{}",
+        preview.students.join(", "), preview.completions, preview.elaborations, preview.files,
+        if conflicts.is_empty() { String::new() } else { format!(", keeping {}", conflicts.join(", ")) })
+}
+
+#[post("/group/<group>/merge/<other>?<dry_run>")]
+pub fn post_group_merge(group: i32, other: i32, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<regroup::Preview>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let year = find_writable_year(group, &*conn)?;
+        user.ensure_permission(Permission::Groups, year)?;
+        if group == other || find_writable_year(other, &*conn)? != year {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        let preview = regroup::merge(group, other, dry_run, &conn)?;
+        if dry_run {
+            return Ok(Json(preview));
+        }
+
+        add_audit_log(year, Some(other), user.name(), &*conn,
+            &format!("Merge group into group #{} and delete it", group))?;
+        add_audit_log(year, Some(group), user.name(), &*conn,
+            &format!("Merge group #{} into group with {}", other, describe_regrouping(&preview)))?;
+
+        push::SERVER.push(year, "group", &push::Group::Change { group }).ok();
+        push::SERVER.push(year, "group", &push::Group::Change { group: other }).ok();
+
+        Ok(Json(preview))
+    })
+}
+
+/// Students that are split off into a new group at the given desk
+#[derive(Deserialize)]
+pub struct GroupSplit {
+    students: Vec<i32>,
+    desk: i32,
+}
+
+#[post("/group/<group>/split?<dry_run>", data = "<split>")]
+pub fn post_group_split(group: i32, split: Json<GroupSplit>, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<regroup::Preview>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let year = find_writable_year(group, &*conn)?;
+        user.ensure_permission(Permission::Groups, year)?;
+
+        let (preview, new_group) = regroup::split(group, &split.students, split.desk, dry_run, &conn)?;
+        let new_group = match new_group {
+            Some(new_group) => new_group,
+            None => return Ok(Json(preview)),
+        };
+
+        add_audit_log(year, Some(group), user.name(), &*conn,
+            &format!("Split group into new group #{} at desk {} with {}",
+                new_group, split.desk, describe_regrouping(&preview)))?;
+        add_audit_log(year, Some(new_group), user.name(), &*conn,
+            &format!("Create group by splitting group #{} with {}", group, describe_regrouping(&preview)))?;
+
+        push::SERVER.push(year, "group", &push::Group::Change { group }).ok();
+
+        Ok(Json(preview))
+    })
+}
+
 #[derive(Deserialize)]
 pub struct Search {
     terms: Vec<String>,
@@ -303,7 +745,7 @@ pub struct Search {
 
 #[post("/group/search", data = "<search>")]
 pub fn search_groups(search: Json<Search>, conn: db::Conn, user: User) -> ApiResult<Json<Vec<super::models::SearchGroup>>> {
-    user.ensure_tutor_for(search.year)?;
+    user.ensure_permission(Permission::View, search.year)?;
 
     let groups = super::models::find_groups(&search.terms, search.year, &conn)?;
 
@@ -312,45 +754,193 @@ pub fn search_groups(search: Json<Search>, conn: db::Conn, user: User) -> ApiRes
 
 #[post("/student/search", data = "<search>")]
 pub fn search_students(search: Json<Search>, conn: db::Conn, user: User) -> ApiResult<Json<Vec<super::models::Student>>> {
-    user.ensure_tutor_for(search.year)?;
+    user.ensure_permission(Permission::View, search.year)?;
 
     let students = super::models::find_students(&search.terms, search.year, &conn)?;
 
     Ok(Json(students))
 }
 
-#[put("/year/<year>")]
-pub fn put_year(year: i16, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
-    let db_year = db::Year {
-        id: year,
-        writable: true,
-    };
+#[derive(Deserialize)]
+pub struct NewCourse {
+    id: String,
+    name: String,
+}
+
+// Course ids are used in urls, so they must not shadow any other route
+fn is_valid_course_id(id: &str) -> bool {
+    const RESERVED: &[&str] = &["admin", "analysis", "api", "group", "login",
+                                "logout", "portal", "static"];
+
+    id.starts_with(|c: char| c.is_ascii_lowercase())
+        && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !RESERVED.contains(&id)
+}
+
+#[post("/course", data = "<course>")]
+pub fn post_course(course: Json<NewCourse>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Json<i16>> {
+    if !is_valid_course_id(&course.id) || course.name.trim().is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
 
     conn.transaction(|| {
-        diesel::insert_into(db::years::table)
-            .values(&db_year)
+        let exists: i64 = db::courses::table.find(&course.id)
+            .count()
+            .get_result(&*conn)?;
+        if exists > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::insert_into(db::courses::table)
+            .values(&db::Course {
+                id: course.id.clone(),
+                name: course.name.trim().to_string(),
+            })
             .execute(&*conn)?;
 
+        // Every course needs at least one term to be reachable
+        let year = db::add_current_term(&course.id, &conn)?;
+
         add_audit_log(year, None, user.name(), &conn,
-            &format!("Create new year {}", year))?;
+            &format!("Create new course {} ({}) with term #{}",
+                course.name, course.id, year))?;
 
-        Ok(Status::NoContent)
+        Ok(Json(year))
+    })
+}
+
+#[derive(Deserialize)]
+pub struct NewTerm {
+    label: String,
+    start_date: String,
+    end_date: String,
+}
+
+impl NewTerm {
+    fn to_db(&self, course: &str) -> ApiResult<db::NewYear> {
+        let label = self.label.trim();
+        let start_date: NaiveDate = self.start_date.parse()
+            .map_err(|_| ApiError::ConstraintViolation)?;
+        let end_date: NaiveDate = self.end_date.parse()
+            .map_err(|_| ApiError::ConstraintViolation)?;
+
+        if label.is_empty() || start_date > end_date {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        Ok(db::NewYear {
+            course: course.to_string(),
+            label: label.to_string(),
+            start_date: start_date,
+            end_date: end_date,
+        })
+    }
+}
+
+// Fails with a constraint violation if the course already has a term with
+// this label
+fn ensure_new_term(course: &str, label: &str, conn: &PgConnection) -> ApiResult<()> {
+    let exists: i64 = db::years::table
+        .filter(db::years::course.eq(course))
+        .filter(db::years::label.eq(label))
+        .count()
+        .get_result(conn)?;
+
+    match exists {
+        0 => Ok(()),
+        _ => Err(ApiError::ConstraintViolation),
+    }
+}
+
+#[post("/course/<course>/year?<template>&<shift_weeks>", data = "<term>")]
+pub fn post_year(course: String, template: Option<i16>, shift_weeks: Option<i64>, term: Json<NewTerm>, conn: db::Conn, user: User) -> ApiResult<Json<i16>> {
+    user.ensure_course_admin(&course)?;
+    let term = term.to_db(&course)?;
+
+    conn.transaction(|| {
+        ensure_new_term(&course, &term.label, &conn)?;
+
+        let year = db::add_term(&term, &conn)?;
+
+        match template {
+            Some(template) => {
+                // Fails with a constraint violation if the template does not
+                // exist or belongs to another course
+                db::years::table.find(template)
+                    .filter(db::years::course.eq(&course))
+                    .get_result::<db::Year>(&*conn)
+                    .optional()?
+                    .ok_or(ApiError::ConstraintViolation)?;
+
+                // Fails with a constraint violation if the event dates can
+                // not be shifted that far
+                let shift_weeks = shift_weeks.unwrap_or(0);
+                db::copy_year(template, year, shift_weeks, &conn).map_err(|e| match e {
+                    Error(ErrorKind::DateOutOfRange, _) => ApiError::ConstraintViolation,
+                    e => e.into(),
+                })?;
+
+                add_audit_log(year, None, user.name(), &conn,
+                    &format!("Create new term {} (#{}) in course {} from term #{} (event dates shifted by {} weeks)",
+                        term.label, year, course, template, shift_weeks))?;
+            },
+            None => {
+                add_audit_log(year, None, user.name(), &conn,
+                    &format!("Create new term {} (#{}) in course {}", term.label, year, course))?;
+            },
+        }
+
+        Ok(Json(year))
+    })
+}
+
+#[post("/course/<course>/year/archive?<label>", data = "<archive>")]
+pub fn post_year_archive(course: String, label: String, archive: Data, conn: db::Conn, user: User) -> ApiResult<Json<i16>> {
+    user.ensure_course_admin(&course)?;
+
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    let archive = archive::read_archive(archive.open())?
+        .map_err(ApiError::Invalid)?;
+
+    conn.transaction(|| {
+        ensure_new_term(&course, label, &conn)?;
+
+        let year = archive::restore_archive(&course, label, &archive, &conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Restore term {} (#{}) in course {} from archive of term #{}",
+                label, year, course, archive.year))?;
+
+        Ok(Json(year))
     })
 }
 
 #[delete("/year/<year>")]
-pub fn delete_year(year: i16, conn: db::Conn, _user: SiteAdmin) -> ApiResult<Status> {
+pub fn delete_year(year: i16, conn: db::Conn, user: User) -> ApiResult<Status> {
+    user.ensure_course_admin_for(year)?;
+
     conn.transaction(|| {
+        let course = db::years::table.find(year)
+            .select(db::years::course)
+            .get_result::<String>(&*conn)?;
+
         db::delete_year(year, &conn)?;
 
         // No audit log entry, because the year does no longer exist
 
-        // Add back the current year if we just deleted the last year,
-        // because you can only add a year through the admin view of
-        // an already exsiting year.
-        let num_years: i64 = db::years::table.count().get_result(&*conn)?;
+        // Add back the current term if we just deleted the last term of
+        // the course, because you can only add a term through the admin
+        // view of an already exsiting term.
+        let num_years: i64 = db::years::table
+            .filter(db::years::course.eq(&course))
+            .count()
+            .get_result(&*conn)?;
         if num_years == 0 {
-            db::add_current_year(&conn)?;
+            db::add_current_term(&course, &conn)?;
         }
 
         Ok(Status::NoContent)
@@ -358,7 +948,9 @@ pub fn delete_year(year: i16, conn: db::Conn, _user: SiteAdmin) -> ApiResult<Sta
 }
 
 #[put("/year/<year>/closed")]
-pub fn put_year_writable(year: i16, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+pub fn put_year_writable(year: i16, conn: db::Conn, user: User) -> ApiResult<Status> {
+    user.ensure_course_admin_for(year)?;
+
     conn.transaction(|| {
         diesel::update(db::years::table.filter(db::years::id.eq(year)))
             .set(db::years::writable.eq(false))
@@ -371,13 +963,49 @@ pub fn put_year_writable(year: i16, conn: db::Conn, user: SiteAdmin) -> ApiResul
     })
 }
 
+#[put("/year/<year>/anonymized")]
+pub fn put_year_anonymized(year: i16, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    user.ensure_course_admin_for(year)?;
+
+    conn.transaction(|| -> ApiResult<()> {
+        let db_year = db::years::table.find(year)
+            .get_result::<db::Year>(&*conn)?;
+
+        // Only closed years can be anonymized, because the final export
+        // needs the real names of the students
+        if db_year.writable || db_year.anonymized {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        let count = db::anonymize_year(year, &conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Anonymize year {} ({} students)", year, count))?;
+
+        Ok(())
+    })?;
+
+    // Remove the files of the deleted elaborations from disk
+    uploads.purge(&conn)?;
+
+    Ok(Status::NoContent)
+}
+
 #[post("/experiment", data = "<experiment>")]
 pub fn post_experiment(experiment: Json<db::NewExperiment>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    let mut experiment = experiment.into_inner();
+
     conn.transaction(|| {
-        user.ensure_admin_for(experiment.year)?;
+        user.ensure_permission(Permission::Manage, experiment.year)?;
+
+        let last_position: Option<i32> = db::experiments::table
+            .filter(db::experiments::year.eq(experiment.year))
+            .select(diesel::dsl::max(db::experiments::position))
+            .get_result(&*conn)?;
+        experiment.position = last_position.unwrap_or(0) + 1;
 
         let id: i32 = diesel::insert_into(db::experiments::table)
-            .values(&*experiment)
+            .values(&experiment)
             .returning(db::experiments::id)
             .get_result(&*conn)?;
 
@@ -388,38 +1016,161 @@ pub fn post_experiment(experiment: Json<db::NewExperiment>, conn: db::Conn, user
     })
 }
 
-#[delete("/experiment/<experiment>")]
-pub fn delete_experiment(experiment: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+// Names of the entries in the new order, which must contain every entry
+// exactly once
+fn ordered_names(order: &[i32], mut entries: Vec<(i32, String)>) -> ApiResult<Vec<String>> {
+    let mut ids: Vec<_> = entries.iter().map(|&(id, _)| id).collect();
+    let mut new_ids = order.to_vec();
+    ids.sort();
+    new_ids.sort();
+    if ids != new_ids {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    Ok(order.iter()
+        .map(|id| {
+            let index = entries.iter().position(|entry| entry.0 == *id)
+                .expect("ids have been checked");
+            entries.swap_remove(index).1
+        })
+        .collect())
+}
+
+#[put("/experiments/<year>/order", data = "<order>")]
+pub fn put_experiment_order(year: i16, order: Json<Vec<i32>>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        user.ensure_permission(Permission::Manage, year)?;
+
+        let experiments = db::experiments::table
+            .filter(db::experiments::year.eq(year))
+            .select((db::experiments::id, db::experiments::name))
+            .load::<(i32, String)>(&*conn)?;
+        let names = ordered_names(&order, experiments)?;
+
+        for (position, &experiment) in order.iter().enumerate() {
+            diesel::update(db::experiments::table.find(experiment))
+                .set(db::experiments::position.eq(position as i32 + 1))
+                .execute(&*conn)
+                .and_then(db::expect1)?;
+        }
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Reorder experiments to {}", names.join(", ")))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[put("/experiment/<experiment>/tasks/order", data = "<order>")]
+pub fn put_task_order(experiment: i32, order: Json<Vec<i32>>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let full_experiment = db::experiments::table
             .find(experiment)
             .get_result::<db::Experiment>(&*conn)?;
-        user.ensure_admin_for(full_experiment.year)?;
-
-        diesel::delete(
-            db::experiments::table.find(experiment))
-            .execute(&*conn)
-            .and_then(db::expect1)?;
+        user.ensure_permission(Permission::Manage, full_experiment.year)?;
+
+        let tasks = db::tasks::table
+            .filter(db::tasks::experiment_id.eq(experiment))
+            .select((db::tasks::id, db::tasks::name))
+            .load::<(i32, String)>(&*conn)?;
+        let names = ordered_names(&order, tasks)?;
+
+        for (position, &task) in order.iter().enumerate() {
+            diesel::update(db::tasks::table.find(task))
+                .set(db::tasks::position.eq(position as i32 + 1))
+                .execute(&*conn)
+                .and_then(db::expect1)?;
+        }
 
         add_audit_log(full_experiment.year, None, user.name(), &conn,
-            &format!("Remove experiment {} (#{})", full_experiment.name, experiment))?;
+            &format!("Reorder tasks of experiment {} (#{}) to {}",
+                full_experiment.name, experiment, names.join(", ")))?;
 
         Ok(Status::NoContent)
     })
 }
 
-#[post("/experiment/<experiment>/task", data = "<task>")]
-pub fn post_experiment_task(experiment: i32, task: Json<String>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+/// New name of an experiment, task or day
+#[derive(Deserialize)]
+pub struct NamePatch {
+    name: String,
+}
+
+#[patch("/experiment/<experiment>", data = "<patch>")]
+pub fn patch_experiment(experiment: i32, patch: Json<NamePatch>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    let name = patch.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
     conn.transaction(|| {
         let full_experiment = db::experiments::table
             .find(experiment)
             .get_result::<db::Experiment>(&*conn)?;
-        user.ensure_admin_for(full_experiment.year)?;
+        user.ensure_permission(Permission::Manage, full_experiment.year)?;
 
-        let id: i32 = diesel::insert_into(db::tasks::table)
+        let duplicates: i64 = db::experiments::table
+            .filter(db::experiments::year.eq(full_experiment.year))
+            .filter(db::experiments::name.eq(name))
+            .filter(db::experiments::id.ne(experiment))
+            .count()
+            .get_result(&*conn)?;
+        if duplicates > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::update(db::experiments::table.find(experiment))
+            .set(db::experiments::name.eq(name))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(full_experiment.year, None, user.name(), &conn,
+            &format!("Rename experiment {} (#{}) to {}", full_experiment.name, experiment, name))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[delete("/experiment/<experiment>?<dry_run>")]
+pub fn delete_experiment(experiment: i32, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<db::Impact>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let full_experiment = db::experiments::table
+            .find(experiment)
+            .get_result::<db::Experiment>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_experiment.year)?;
+
+        let impact = db::trash_experiment(&full_experiment, user.name(), dry_run, &conn)?;
+        if dry_run {
+            return Ok(Json(impact));
+        }
+
+        add_audit_log(full_experiment.year, None, user.name(), &conn,
+            &format!("Move experiment {} (#{}) to trash", full_experiment.name, experiment))?;
+
+        Ok(Json(impact))
+    })
+}
+
+#[post("/experiment/<experiment>/task", data = "<task>")]
+pub fn post_experiment_task(experiment: i32, task: Json<String>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    conn.transaction(|| {
+        let full_experiment = db::experiments::table
+            .find(experiment)
+            .get_result::<db::Experiment>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_experiment.year)?;
+
+        let last_position: Option<i32> = db::tasks::table
+            .filter(db::tasks::experiment_id.eq(experiment))
+            .select(diesel::dsl::max(db::tasks::position))
+            .get_result(&*conn)?;
+
+        let id: i32 = diesel::insert_into(db::tasks::table)
             .values((
                 db::tasks::experiment_id.eq(experiment),
                 db::tasks::name.eq(&*task),
+                db::tasks::position.eq(last_position.unwrap_or(0) + 1),
             ))
             .returning(db::tasks::id)
             .get_result(&*conn)?;
@@ -432,34 +1183,319 @@ pub fn post_experiment_task(experiment: i32, task: Json<String>, conn: db::Conn,
     })
 }
 
-#[delete("/experiment/<experiment>/task/<task>")]
-pub fn delete_experiment_task(experiment: i32, task: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+#[derive(Deserialize)]
+pub struct TaskScoring {
+    points: i16,
+    weight: i16,
+}
+
+#[put("/experiment/<experiment>/task/<task>/scoring", data = "<scoring>")]
+pub fn put_experiment_task_scoring(experiment: i32, task: i32, scoring: Json<TaskScoring>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
-        let (task_name, experiment_name, year) = db::tasks::table
+        let (full_task, experiment_name, year) = db::tasks::table
             .inner_join(db::experiments::table)
             .filter(db::tasks::id.eq(task))
             .filter(db::experiments::id.eq(experiment))
             .select((
-                db::tasks::name,
+                db::tasks::all_columns,
                 db::experiments::name,
                 db::experiments::year,
             ))
-            .get_result::<(String, String, i16)>(&*conn)?;
-        user.ensure_admin_for(year)?;
+            .get_result::<(db::Task, String, i16)>(&*conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
 
-        diesel::delete(
-            db::tasks::table.find(task))
+        if scoring.points <= 0 || scoring.weight < 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::update(db::tasks::table.find(task))
+            .set((
+                db::tasks::points.eq(scoring.points),
+                db::tasks::weight.eq(scoring.weight),
+            ))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        // Partial credit must not exceed the new maximum
+        diesel::update(db::completions::table
+            .filter(db::completions::task_id.eq(task))
+            .filter(db::completions::points.gt(scoring.points)))
+            .set(db::completions::points.eq(scoring.points))
+            .execute(&*conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Change scoring of task {} (#{}) of experiment {} (#{}) from {} points with weight {} to {} points with weight {}",
+                full_task.name, task, experiment_name, experiment,
+                full_task.points, full_task.weight, scoring.points, scoring.weight))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[put("/experiment/<experiment>/description", data = "<description>")]
+pub fn put_experiment_description(experiment: i32, description: Json<String>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_experiment = db::experiments::table
+            .find(experiment)
+            .get_result::<db::Experiment>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_experiment.year)?;
+
+        diesel::update(db::experiments::table.find(experiment))
+            .set(db::experiments::description.eq(&*description))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(full_experiment.year, None, user.name(), &conn,
+            &format!("Change description of experiment {} (#{}) from '{}' to '{}'",
+                full_experiment.name, experiment, full_experiment.description, *description))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[put("/experiment/<experiment>/task/<task>/description", data = "<description>")]
+pub fn put_experiment_task_description(experiment: i32, task: i32, description: Json<String>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let (full_task, experiment_name, year) = db::tasks::table
+            .inner_join(db::experiments::table)
+            .filter(db::tasks::id.eq(task))
+            .filter(db::experiments::id.eq(experiment))
+            .select((
+                db::tasks::all_columns,
+                db::experiments::name,
+                db::experiments::year,
+            ))
+            .get_result::<(db::Task, String, i16)>(&*conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
+
+        diesel::update(db::tasks::table.find(task))
+            .set(db::tasks::description.eq(&*description))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Change description of task {} (#{}) of experiment {} (#{}) from '{}' to '{}'",
+                full_task.name, task, experiment_name, experiment, full_task.description, *description))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+// Find the year of the experiment and the name of the optional task, which
+// must belong to the experiment
+fn find_attachment_target(experiment: i32, task: Option<i32>, conn: &PgConnection) -> ApiResult<(i16, String)> {
+    let (year, experiment_name) = db::experiments::table
+        .find(experiment)
+        .select((db::experiments::year, db::experiments::name))
+        .get_result::<(i16, String)>(conn)?;
+
+    let target = match task {
+        Some(task) => {
+            let task_name = db::tasks::table
+                .filter(db::tasks::id.eq(task))
+                .filter(db::tasks::experiment_id.eq(experiment))
+                .select(db::tasks::name)
+                .get_result::<String>(conn)
+                .optional()?
+                .ok_or(ApiError::ConstraintViolation)?;
+            format!("task {} (#{}) of experiment {} (#{})", task_name, task, experiment_name, experiment)
+        },
+        None => format!("experiment {} (#{})", experiment_name, experiment),
+    };
+
+    Ok((year, target))
+}
+
+#[derive(Deserialize)]
+pub struct Link {
+    task: Option<i32>,
+    title: String,
+    url: String,
+}
+
+#[post("/experiment/<experiment>/link", data = "<link>")]
+pub fn post_experiment_link(experiment: i32, link: Json<Link>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    let url = link.url.trim();
+    if link.title.trim().is_empty() || !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    conn.transaction(|| {
+        let (year, target) = find_attachment_target(experiment, link.task, &conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
+
+        let id: i32 = diesel::insert_into(db::attachments::table)
+            .values(&db::NewAttachment {
+                experiment_id: experiment,
+                task_id: link.task,
+                title: link.title.trim(),
+                url: Some(url),
+                file: None,
+                content_type: None,
+            })
+            .returning(db::attachments::id)
+            .get_result(&*conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Add link {} (#{}) to {} to {}", link.title.trim(), id, url, target))?;
+
+        Ok(Json(id))
+    })
+}
+
+#[post("/experiment/<experiment>/attachment?<task>&<filename>", data = "<file>")]
+pub fn post_experiment_attachment(experiment: i32, task: Option<i32>, filename: String, file: Data, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    // The file is only read for users who may attach it
+    let (year, target) = find_attachment_target(experiment, task, &conn)?;
+    user.ensure_permission(Permission::Manage, year)?;
+
+    let (data, content_type) = uploads.read_attachment(file.open())?
+        .map_err(ApiError::Invalid)?;
+    let filename = upload::sanitize_filename(&filename);
+
+    conn.transaction(|| {
+        // The name of the stored file depends on the id of the attachment
+        let id: i32 = diesel::insert_into(db::attachments::table)
+            .values(&db::NewAttachment {
+                experiment_id: experiment,
+                task_id: task,
+                title: &filename,
+                url: None,
+                file: Some(""),
+                content_type: Some(content_type),
+            })
+            .returning(db::attachments::id)
+            .get_result(&*conn)?;
+
+        let name = uploads.store_attachment(id, &data)?;
+        diesel::update(db::attachments::table.find(id))
+            .set(db::attachments::file.eq(&name))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Attach file {} (#{}) to {}", filename, id, target))?;
+
+        Ok(Json(id))
+    })
+}
+
+#[get("/attachment/<attachment>")]
+pub fn get_attachment(attachment: i32, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<FileResponse> {
+    let (attachment, year) = db::attachments::table
+        .inner_join(db::experiments::table)
+        .filter(db::attachments::id.eq(attachment))
+        .select((db::attachments::all_columns, db::experiments::year))
+        .get_result::<(db::Attachment, i16)>(&*conn)?;
+    user.ensure_permission(Permission::View, year)?;
+
+    // Links are referenced directly and have no content to download
+    match (attachment.file, attachment.content_type) {
+        (Some(file), Some(content_type)) => Ok(FileResponse {
+            content: uploads.load_attachment(&file)?,
+            filename: attachment.title,
+            content_type: content_type,
+        }),
+        _ => Err(ApiError::ConstraintViolation),
+    }
+}
+
+#[delete("/attachment/<attachment>")]
+pub fn delete_attachment(attachment: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_attachment = db::attachments::table
+            .find(attachment)
+            .get_result::<db::Attachment>(&*conn)?;
+        let (year, target) = find_attachment_target(full_attachment.experiment_id, full_attachment.task_id, &conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
+
+        diesel::delete(db::attachments::table.find(attachment))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        // The file itself is removed by the next purge if no copy of the
+        // attachment in a later year references it anymore
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Remove attachment {} (#{}) from {}", full_attachment.title, attachment, target))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[patch("/experiment/<experiment>/task/<task>", data = "<patch>")]
+pub fn patch_experiment_task(experiment: i32, task: i32, patch: Json<NamePatch>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    let name = patch.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    conn.transaction(|| {
+        let (full_task, experiment_name, year) = db::tasks::table
+            .inner_join(db::experiments::table)
+            .filter(db::tasks::id.eq(task))
+            .filter(db::experiments::id.eq(experiment))
+            .select((
+                db::tasks::all_columns,
+                db::experiments::name,
+                db::experiments::year,
+            ))
+            .get_result::<(db::Task, String, i16)>(&*conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
+
+        // Names of tasks are unique within their experiment
+        let duplicates: i64 = db::tasks::table
+            .filter(db::tasks::experiment_id.eq(experiment))
+            .filter(db::tasks::name.eq(name))
+            .filter(db::tasks::id.ne(task))
+            .count()
+            .get_result(&*conn)?;
+        if duplicates > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::update(db::tasks::table.find(task))
+            .set(db::tasks::name.eq(name))
             .execute(&*conn)
             .and_then(db::expect1)?;
 
         add_audit_log(year, None, user.name(), &conn,
-            &format!("Remove task {} (#{}) from experiment {} (#{})",
-                task_name, task, experiment_name, experiment))?;
+            &format!("Rename task {} (#{}) of experiment {} (#{}) to {}",
+                full_task.name, task, experiment_name, experiment, name))?;
 
         Ok(Status::NoContent)
     })
 }
 
+#[delete("/experiment/<experiment>/task/<task>?<dry_run>")]
+pub fn delete_experiment_task(experiment: i32, task: i32, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<db::Impact>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let (full_task, experiment_name, year) = db::tasks::table
+            .inner_join(db::experiments::table)
+            .filter(db::tasks::id.eq(task))
+            .filter(db::experiments::id.eq(experiment))
+            .select((
+                db::tasks::all_columns,
+                db::experiments::name,
+                db::experiments::year,
+            ))
+            .get_result::<(db::Task, String, i16)>(&*conn)?;
+        user.ensure_permission(Permission::Manage, year)?;
+
+        let impact = db::trash_task(&full_task, year, user.name(), dry_run, &conn)?;
+        if dry_run {
+            return Ok(Json(impact));
+        }
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Move task {} (#{}) of experiment {} (#{}) to trash",
+                full_task.name, task, experiment_name, experiment))?;
+
+        Ok(Json(impact))
+    })
+}
+
 #[put("/experiment/<experiment>/day/<day>/event", data = "<date>")]
 pub fn put_event(experiment: i32, day: i32, date: Json<String>, conn: db::Conn, user: User) -> ApiResult<Status> {
     let date: NaiveDate = date.parse().chain_err(|| "Invalid date")?;
@@ -488,7 +1524,7 @@ pub fn put_event(experiment: i32, day: i32, date: Json<String>, conn: db::Conn,
             .select((db::days::year, db::days::name))
             .get_result::<(i16, String)>(&*conn)?;
 
-        user.ensure_admin_for(year)?;
+        user.ensure_permission(Permission::Manage, year)?;
         add_audit_log(year, None, user.name(), &conn,
             &format!("Set event date to {} for day {} (#{}) and experiment {} (#{})",
                 date, day_name, day, experiment_name, experiment))?;
@@ -513,7 +1549,7 @@ pub fn delete_event(experiment: i32, day: i32, conn: db::Conn, user: User) -> Ap
             .select((db::days::year, db::days::name))
             .get_result::<(i16, String)>(&*conn)?;
 
-        user.ensure_admin_for(year)?;
+        user.ensure_permission(Permission::Manage, year)?;
         add_audit_log(year, None, user.name(), &conn,
             &format!("Remove event date for day {} (#{}) and experiment {} (#{})",
                 day_name, day, experiment_name, experiment))?;
@@ -525,7 +1561,7 @@ pub fn delete_event(experiment: i32, day: i32, conn: db::Conn, user: User) -> Ap
 #[post("/day", data = "<day>")]
 pub fn post_day(day: Json<db::NewDay>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
     conn.transaction(|| {
-        user.ensure_admin_for(day.year)?;
+        user.ensure_permission(Permission::Manage, day.year)?;
 
         let id: i32 = diesel::insert_into(db::days::table)
             .values(&*day)
@@ -539,21 +1575,153 @@ pub fn post_day(day: Json<db::NewDay>, conn: db::Conn, user: User) -> ApiResult<
     })
 }
 
-#[delete("/day/<day>")]
-pub fn delete_day(day: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+#[patch("/day/<day>", data = "<patch>")]
+pub fn patch_day(day: i32, patch: Json<NamePatch>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    let name = patch.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
     conn.transaction(|| {
         let full_day = db::days::table
             .find(day)
             .get_result::<db::Day>(&*conn)?;
-        user.ensure_admin_for(full_day.year)?;
+        user.ensure_permission(Permission::Manage, full_day.year)?;
+
+        // Days are found by their name in the links to events
+        let duplicates: i64 = db::days::table
+            .filter(db::days::year.eq(full_day.year))
+            .filter(db::days::name.eq(name))
+            .filter(db::days::id.ne(day))
+            .count()
+            .get_result(&*conn)?;
+        if duplicates > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
 
-        diesel::delete(
-            db::days::table.find(day))
+        diesel::update(db::days::table.find(day))
+            .set(db::days::name.eq(name))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(full_day.year, None, user.name(), &conn,
+            &format!("Rename day {} (#{}) to {}", full_day.name, day, name))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[put("/day/<day>/room", data = "<room>")]
+pub fn put_day_room(day: i32, room: Json<Option<i32>>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_day = db::days::table
+            .find(day)
+            .get_result::<db::Day>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_day.year)?;
+
+        let room_name = match *room {
+            Some(room) => {
+                let room = db::rooms::table
+                    .find(room)
+                    .get_result::<db::Room>(&*conn)?;
+                if room.year != full_day.year {
+                    return Err(ApiError::ConstraintViolation);
+                }
+                room.name
+            },
+            None => "no room".into(),
+        };
+
+        diesel::update(db::days::table.find(day))
+            .set(db::days::room_id.eq(*room))
             .execute(&*conn)
             .and_then(db::expect1)?;
 
         add_audit_log(full_day.year, None, user.name(), &conn,
-            &format!("Remove day {} (#{})", full_day.name, day))?;
+            &format!("Day {} (#{}) takes place in {}", full_day.name, day, room_name))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[delete("/day/<day>?<dry_run>")]
+pub fn delete_day(day: i32, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<db::Impact>> {
+    let dry_run = dry_run.unwrap_or(false);
+
+    conn.transaction(|| {
+        let full_day = db::days::table
+            .find(day)
+            .get_result::<db::Day>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_day.year)?;
+
+        // Fails with a constraint violation if a group of the day has
+        // already made progress
+        let impact = db::trash_day(&full_day, user.name(), dry_run, &conn).map_err(|e| match e {
+            Error(ErrorKind::InProgress, _) => ApiError::ConstraintViolation,
+            e => e.into(),
+        })?;
+        if dry_run {
+            return Ok(Json(impact));
+        }
+
+        add_audit_log(full_day.year, None, user.name(), &conn,
+            &format!("Move day {} (#{}) to trash", full_day.name, day))?;
+
+        Ok(Json(impact))
+    })
+}
+
+#[put("/day/<day>/tutor/<tutor>")]
+pub fn put_day_tutor(day: i32, tutor: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_day = db::days::table
+            .find(day)
+            .get_result::<db::Day>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_day.year)?;
+
+        let full_tutor = db::tutors::table
+            .find(tutor)
+            .get_result::<db::Tutor>(&*conn)?;
+        if full_tutor.year != full_day.year {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::insert_into(db::day_tutors::table)
+            .values(&db::DayTutor {
+                day_id: day,
+                tutor_id: tutor,
+            })
+            .on_conflict_do_nothing()
+            .execute(&*conn)?;
+
+        add_audit_log(full_day.year, None, user.name(), &conn,
+            &format!("Assign tutor {} (#{}) to day {} (#{})",
+                full_tutor.username, tutor, full_day.name, day))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[delete("/day/<day>/tutor/<tutor>")]
+pub fn delete_day_tutor(day: i32, tutor: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_day = db::days::table
+            .find(day)
+            .get_result::<db::Day>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_day.year)?;
+
+        let username: String = db::tutors::table
+            .find(tutor)
+            .select(db::tutors::username)
+            .get_result(&*conn)?;
+
+        diesel::delete(db::day_tutors::table
+            .find((day, tutor)))
+            .execute(&*conn)?;
+
+        add_audit_log(full_day.year, None, user.name(), &conn,
+            &format!("Unassign tutor {} (#{}) from day {} (#{})",
+                username, tutor, full_day.name, day))?;
 
         Ok(Status::NoContent)
     })
@@ -574,68 +1742,325 @@ fn insert_student(student: &db::NewStudent, conn: &PgConnection, user: &str) ->
     Ok(id)
 }
 
+// Names of rooms are unique within their year
+fn ensure_unique_room(year: i16, name: &str, room: Option<i32>, conn: &PgConnection) -> ApiResult<()> {
+    let mut query = db::rooms::table
+        .filter(db::rooms::year.eq(year))
+        .filter(db::rooms::name.eq(name))
+        .into_boxed();
+    if let Some(room) = room {
+        query = query.filter(db::rooms::id.ne(room));
+    }
+
+    match query.count().get_result::<i64>(conn)? {
+        0 => Ok(()),
+        _ => Err(ApiError::ConstraintViolation),
+    }
+}
+
+#[post("/room", data = "<room>")]
+pub fn post_room(room: Json<db::NewRoom>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    if room.name.trim().is_empty() || Layout::parse(&room.layout).is_err() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    conn.transaction(|| {
+        user.ensure_permission(Permission::Manage, room.year)?;
+        ensure_unique_room(room.year, &room.name, None, &conn)?;
+
+        let id: i32 = diesel::insert_into(db::rooms::table)
+            .values(&*room)
+            .returning(db::rooms::id)
+            .get_result(&*conn)?;
+
+        add_audit_log(room.year, None, user.name(), &conn,
+            &format!("Create new room {} (#{})", room.name, id))?;
+
+        Ok(Json(id))
+    })
+}
+
+/// Changed name or desk layout of a room
+#[derive(Deserialize)]
+pub struct RoomPatch {
+    name: Option<String>,
+    layout: Option<String>,
+}
+
+#[patch("/room/<room>", data = "<patch>")]
+pub fn patch_room(room: i32, patch: Json<RoomPatch>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let old = db::rooms::table
+            .find(room)
+            .get_result::<db::Room>(&*conn)?;
+        user.ensure_permission(Permission::Manage, old.year)?;
+
+        let name = patch.name.as_ref().map_or(old.name.as_str(), |name| name.trim());
+        let layout = patch.layout.as_ref().unwrap_or(&old.layout);
+        if name.is_empty() || Layout::parse(layout).is_err() {
+            return Err(ApiError::ConstraintViolation);
+        }
+        ensure_unique_room(old.year, name, Some(room), &conn)?;
+
+        diesel::update(db::rooms::table.find(room))
+            .set((
+                db::rooms::name.eq(name),
+                db::rooms::layout.eq(layout),
+            ))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(old.year, None, user.name(), &conn,
+            &format!("Update room {} (#{}) to {} with layout '{}'", old.name, room, name, layout))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[delete("/room/<room>")]
+pub fn delete_room(room: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_room = db::rooms::table
+            .find(room)
+            .get_result::<db::Room>(&*conn)?;
+        user.ensure_permission(Permission::Manage, full_room.year)?;
+
+        // Days of the room are no longer assigned to any room
+        diesel::delete(db::rooms::table.find(room))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(full_room.year, None, user.name(), &conn,
+            &format!("Delete room {} (#{}) with layout '{}'", full_room.name, room, full_room.layout))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
 #[post("/student", data = "<student>")]
 pub fn post_student(student: Json<db::NewStudent>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    let row = import::Row {
+        line: 0,
+        matrikel: student.matrikel.clone(),
+        given_name: student.given_name.clone(),
+        family_name: student.family_name.clone(),
+        username: student.username.clone(),
+        email: student.email.clone(),
+        partner: student.partner.clone(),
+    };
+    if !import::validate(&row).is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
     conn.transaction(|| {
-        user.ensure_admin_for(student.year)?;
+        user.ensure_permission(Permission::Students, student.year)?;
 
         Ok(Json(insert_student(&*student, &conn, user.name())?))
     })
 }
 
-#[post("/students/<year>", format = "text/csv", data = "<students>")]
-pub fn post_students_csv(year: i16, students: Data, conn: db::Conn, user: User) -> ApiResult<Status> {
-    #[derive(Debug, Deserialize)]
-    struct Student {
-        matrikel: String,
-        given_name: String,
-        family_name: String,
-        username: Option<String>,
+// Update a single student from an import and add an audit log without a transaction
+fn update_student(student: i32, row: &import::Row, conn: &PgConnection, user: &str) -> ApiResult<()> {
+    let old = db::students::table
+        .find(student)
+        .get_result::<db::Student>(conn)?;
+
+    let family_name = &row.family_name;
+    let username = row.username.clone().or_else(|| old.username.clone());
+    let email = row.email.clone().or_else(|| old.email.clone());
+    let partner = row.partner.clone().or_else(|| old.partner.clone());
+
+    diesel::update(db::students::table.find(student))
+        .set((
+            db::students::given_name.eq(&row.given_name),
+            db::students::family_name.eq(family_name),
+            db::students::username.eq(&username),
+            db::students::email.eq(&email),
+            db::students::partner.eq(&partner),
+        ))
+        .execute(conn)
+        .and_then(db::expect1)?;
+
+    add_audit_log(old.year, None, user, conn,
+        &format!("Update student {} ({}, {}, {}, partner {}, #{}) to {} {} ({}, {}, {}, partner {})",
+            old.name(), old.matrikel, old.username.as_ref().map_or("-", |s| s),
+            old.email.as_ref().map_or("-", |s| s), old.partner.as_ref().map_or("-", |s| s),
+            student, row.given_name, family_name, old.matrikel,
+            username.as_ref().map_or("-", |s| s), email.as_ref().map_or("-", |s| s),
+            partner.as_ref().map_or("-", |s| s)))?;
+
+    Ok(())
+}
+
+#[post("/students/<year>/preview?<options..>", data = "<students>")]
+pub fn post_students_import_preview(year: i16, options: Form<import::Options>, content_type: &ContentType, students: Data, conn: db::Conn, user: User) -> ApiResult<Json<import::Preview>> {
+    user.ensure_permission(Permission::Students, year)?;
+
+    let preview = match import::read_records(students.open(), content_type, &options)? {
+        Ok(records) => import::preview(year, records, &options, &conn)?,
+        Err(error) => import::Preview {
+            errors: vec![error],
+            ..Default::default()
+        },
+    };
+
+    Ok(Json(preview))
+}
+
+// Invalid files are not imported at all and the preview with their errors is
+// returned instead
+#[post("/students/<year>?<options..>", data = "<students>")]
+pub fn post_students_import(year: i16, options: Form<import::Options>, content_type: &ContentType, students: Data, conn: db::Conn, user: User) -> ApiResult<status::Custom<Json<import::Preview>>> {
+    user.ensure_permission(Permission::Students, year)?;
+
+    let records = match import::read_records(students.open(), content_type, &options)? {
+        Ok(records) => records,
+        Err(error) => return Ok(status::Custom(Status::UnprocessableEntity, Json(import::Preview {
+            errors: vec![error],
+            ..Default::default()
+        }))),
+    };
+
+    conn.transaction(|| {
+        let preview = import::preview(year, records, &options, &conn)?;
+        if !preview.is_valid() {
+            return Ok(status::Custom(Status::UnprocessableEntity, Json(preview)));
+        }
+
+        upsert_students(year, &preview, &conn, user.name())?;
+
+        Ok(status::Custom(Status::Ok, Json(preview)))
+    })
+}
+
+// Create or update the students of a valid import preview without a transaction
+fn upsert_students(year: i16, preview: &import::Preview, conn: &PgConnection, user: &str) -> ApiResult<()> {
+    for row in &preview.rows {
+        match (row.action, row.student) {
+            (import::Action::Create, _) => {
+                let student = db::NewStudent {
+                    matrikel: row.row.matrikel.clone(),
+                    given_name: row.row.given_name.clone(),
+                    family_name: row.row.family_name.clone(),
+                    year: year,
+                    username: row.row.username.clone(),
+                    email: row.row.email.clone(),
+                    partner: row.row.partner.clone(),
+                };
+
+                insert_student(&student, conn, user)?;
+            }
+            (import::Action::Update, Some(student)) => {
+                update_student(student, &row.row, conn, user)?;
+            }
+            _ => {}
+        }
     }
 
-    user.ensure_admin_for(year)?;
+    Ok(())
+}
 
-    let mut csv_reader = ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(students.open());
+/// Changed fields of a student, empty usernames, emails and partners are removed
+#[derive(Deserialize)]
+pub struct StudentPatch {
+    matrikel: Option<String>,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    username: Option<String>,
+    email: Option<String>,
+    partner: Option<String>,
+}
 
+#[patch("/student/<student>", data = "<patch>")]
+pub fn patch_student(student: i32, patch: Json<StudentPatch>, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
-        for student in csv_reader.deserialize() {
-            let student: Student = student?;
-            let student = db::NewStudent {
-                matrikel: student.matrikel,
-                given_name: student.given_name,
-                family_name: student.family_name,
-                year: year,
-                username: student.username,
-            };
+        let old = db::students::table
+            .find(student)
+            .get_result::<db::Student>(&*conn)?;
+        user.ensure_permission(Permission::Students, old.year)?;
+
+        let trimmed = |field: &Option<String>, old: &str| field.as_ref()
+            .map_or_else(|| old.to_string(), |value| value.trim().to_string());
+        let optional = |field: &Option<String>, old: &Option<String>| match field {
+            Some(value) if value.trim().is_empty() => None,
+            Some(value) => Some(value.trim().to_string()),
+            None => old.clone(),
+        };
+
+        let row = import::Row {
+            line: 0,
+            matrikel: trimmed(&patch.matrikel, &old.matrikel),
+            given_name: trimmed(&patch.given_name, &old.given_name),
+            family_name: trimmed(&patch.family_name, &old.family_name),
+            username: optional(&patch.username, &old.username),
+            email: optional(&patch.email, &old.email),
+            partner: optional(&patch.partner, &old.partner),
+        };
+        if !import::validate(&row).is_empty() {
+            return Err(ApiError::ConstraintViolation);
+        }
 
-            insert_student(&student, &conn, user.name())?;
+        // The matrikel identifies students in imports and exports
+        let duplicates: i64 = db::students::table
+            .filter(db::students::year.eq(old.year))
+            .filter(db::students::matrikel.eq(&row.matrikel))
+            .filter(db::students::id.ne(student))
+            .count()
+            .get_result(&*conn)?;
+        if duplicates > 0 {
+            return Err(ApiError::ConstraintViolation);
         }
 
+        diesel::update(db::students::table.find(student))
+            .set((
+                db::students::matrikel.eq(&row.matrikel),
+                db::students::given_name.eq(&row.given_name),
+                db::students::family_name.eq(&row.family_name),
+                db::students::username.eq(&row.username),
+                db::students::email.eq(&row.email),
+                db::students::partner.eq(&row.partner),
+            ))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(old.year, None, user.name(), &conn,
+            &format!("Update student {} ({}, {}, {}, partner {}, #{}) to {} {} ({}, {}, {}, partner {})",
+                old.name(), old.matrikel, old.username.as_ref().map_or("-", |s| s),
+                old.email.as_ref().map_or("-", |s| s), old.partner.as_ref().map_or("-", |s| s),
+                student, row.given_name, row.family_name, row.matrikel,
+                row.username.as_ref().map_or("-", |s| s), row.email.as_ref().map_or("-", |s| s),
+                row.partner.as_ref().map_or("-", |s| s)))?;
+
         Ok(Status::NoContent)
     })
 }
 
-#[delete("/student/<student>")]
-pub fn delete_student(student: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+#[delete("/student/<student>?<dry_run>")]
+pub fn delete_student(student: i32, dry_run: Option<bool>, conn: db::Conn, user: User) -> ApiResult<Json<db::Impact>> {
+    let dry_run = dry_run.unwrap_or(false);
+
     conn.transaction(|| {
         let full_student = db::students::table
             .find(student)
             .get_result::<db::Student>(&*conn)?;
-        user.ensure_admin_for(full_student.year)?;
-
-        diesel::delete(
-            db::students::table.find(student))
-            .execute(&*conn)
-            .and_then(db::expect1)?;
+        user.ensure_permission(Permission::Students, full_student.year)?;
+
+        // Fails with a constraint violation if a group of the student has
+        // already made progress
+        let impact = db::trash_student(&full_student, user.name(), dry_run, &conn).map_err(|e| match e {
+            Error(ErrorKind::InProgress, _) => ApiError::ConstraintViolation,
+            e => e.into(),
+        })?;
+        if dry_run {
+            return Ok(Json(impact));
+        }
 
         add_audit_log(full_student.year, None, user.name(), &conn,
-            &format!("Remove student {} ({}, {}, #{})",
+            &format!("Move student {} ({}, {}, #{}) to trash",
                 full_student.name(), full_student.matrikel,
                 full_student.username.as_ref().map_or("-", |s| s), student))?;
 
-        Ok(Status::NoContent)
+        Ok(Json(impact))
     })
 }
 
@@ -645,7 +2070,7 @@ pub fn put_student_instucted(student: i32, instructed: Json<bool>, conn: db::Con
         let full_student = db::students::table
             .find(student)
             .get_result::<db::Student>(&*conn)?;
-        user.ensure_admin_for(full_student.year)?;
+        user.ensure_permission(Permission::Students, full_student.year)?;
 
         diesel::update(db::students::table.find(student))
             .set(db::students::instructed.eq(*instructed))
@@ -665,7 +2090,10 @@ pub fn put_student_instucted(student: i32, instructed: Json<bool>, conn: db::Con
 }
 
 #[post("/tutor", data = "<tutor>")]
-pub fn post_tutor(tutor: Json<db::NewTutor>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Json<i32>> {
+pub fn post_tutor(tutor: Json<db::NewTutor>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    user.ensure_course_admin_for(tutor.year)?;
+    Role::from_id(&tutor.role).ok_or(ApiError::ConstraintViolation)?;
+
     conn.transaction(|| {
         let id = diesel::insert_into(db::tutors::table)
             .values(&*tutor)
@@ -674,19 +2102,23 @@ pub fn post_tutor(tutor: Json<db::NewTutor>, conn: db::Conn, user: SiteAdmin) ->
 
         add_audit_log(tutor.year, None, user.name(), &conn,
             &format!("Create new tutor {} (#{}, {})", tutor.username,
-            id, if tutor.is_admin { "admin" } else { "no admin" }))?;
+            id, tutor.role))?;
 
         Ok(Json(id))
     })
 }
 
 #[delete("/tutor/<tutor>")]
-pub fn delete_tutor(tutor: i32, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+pub fn delete_tutor(tutor: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let full_tutor = db::tutors::table
             .find(tutor)
             .get_result::<db::Tutor>(&*conn)?;
+        user.ensure_course_admin_for(full_tutor.year)?;
 
+        diesel::delete(db::day_tutors::table
+            .filter(db::day_tutors::tutor_id.eq(tutor)))
+            .execute(&*conn)?;
         diesel::delete(
             db::tutors::table.find(tutor))
             .execute(&*conn)
@@ -694,27 +2126,144 @@ pub fn delete_tutor(tutor: i32, conn: db::Conn, user: SiteAdmin) -> ApiResult<St
 
         add_audit_log(full_tutor.year, None, user.name(), &conn,
             &format!("Remove tutor {} (#{}, {})", full_tutor.username,
-            tutor, if full_tutor.is_admin { "admin" } else { "no admin" }))?;
+            tutor, full_tutor.role))?;
 
         Ok(Status::NoContent)
     })
 }
 
-#[put("/tutor/<tutor>/is_admin", data = "<is_admin>")]
-pub fn put_tutor_admin(tutor: i32, is_admin: Json<bool>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+#[put("/tutor/<tutor>/role", data = "<role>")]
+pub fn put_tutor_role(tutor: i32, role: Json<String>, conn: db::Conn, user: User) -> ApiResult<Status> {
+    let role = Role::from_id(&role).ok_or(ApiError::ConstraintViolation)?;
+
     conn.transaction(|| {
         let full_tutor = db::tutors::table
             .find(tutor)
             .get_result::<db::Tutor>(&*conn)?;
+        user.ensure_course_admin_for(full_tutor.year)?;
 
         diesel::update(db::tutors::table.find(tutor))
-            .set(db::tutors::is_admin.eq(*is_admin))
+            .set(db::tutors::role.eq(role.id()))
             .execute(&*conn)
             .and_then(db::expect1)?;
 
         add_audit_log(full_tutor.year, None, user.name(), &conn,
-            &format!("Tutor {} (#{}) is {} admin", full_tutor.username,
-            tutor, if *is_admin { "now" } else { "no longer" }))?;
+            &format!("Change role of tutor {} (#{}) from {} to {}", full_tutor.username,
+            tutor, full_tutor.role, role.id()))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[post("/year/<year>/course-admin", data = "<username>")]
+pub fn post_course_admin(year: i16, username: Json<String>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Json<i32>> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    conn.transaction(|| {
+        let course = db::years::table.find(year)
+            .select(db::years::course)
+            .get_result::<String>(&*conn)?;
+
+        let exists: i64 = db::course_admins::table
+            .filter(db::course_admins::course.eq(&course))
+            .filter(db::course_admins::username.eq(username))
+            .count()
+            .get_result(&*conn)?;
+        if exists > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        let id = diesel::insert_into(db::course_admins::table)
+            .values((
+                db::course_admins::course.eq(&course),
+                db::course_admins::username.eq(username),
+            ))
+            .returning(db::course_admins::id)
+            .get_result(&*conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Add course admin {} (#{}) for course {}", username, id, course))?;
+
+        Ok(Json(id))
+    })
+}
+
+#[delete("/year/<year>/course-admin/<admin>")]
+pub fn delete_course_admin(year: i16, admin: i32, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+    conn.transaction(|| {
+        // Only admins of the course of the term can be removed there
+        let course = db::years::table.find(year)
+            .select(db::years::course)
+            .get_result::<String>(&*conn)?;
+
+        let full_admin = diesel::delete(
+            db::course_admins::table.find(admin)
+                .filter(db::course_admins::course.eq(&course)))
+            .get_result::<db::CourseAdmin>(&*conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Remove course admin {} (#{}) for course {}",
+            full_admin.username, admin, full_admin.course))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[post("/year/<year>/site-admin", data = "<username>")]
+pub fn post_site_admin(year: i16, username: Json<String>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Json<i32>> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    conn.transaction(|| {
+        let exists: i64 = db::site_admins::table
+            .filter(db::site_admins::username.eq(username))
+            .count()
+            .get_result(&*conn)?;
+        if exists > 0 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        let id = diesel::insert_into(db::site_admins::table)
+            .values(db::site_admins::username.eq(username))
+            .returning(db::site_admins::id)
+            .get_result(&*conn)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Add site admin {} (#{})", username, id))?;
+
+        Ok(Json(id))
+    })
+}
+
+#[delete("/year/<year>/site-admin/<admin>")]
+pub fn delete_site_admin(year: i16, admin: i32, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+    conn.transaction(|| {
+        // Lock all site admins to prevent concurrent removal of the
+        // remaining ones
+        let site_admins = db::site_admins::table
+            .for_update()
+            .load::<db::SiteAdmin>(&*conn)?;
+
+        let full_admin = site_admins.iter()
+            .find(|site_admin| site_admin.id == admin)
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        // Nobody could manage the installation without a site admin
+        if site_admins.len() <= 1 {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        diesel::delete(db::site_admins::table.find(admin))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(year, None, user.name(), &conn,
+            &format!("Remove site admin {} (#{})", full_admin.username, admin))?;
 
         Ok(Status::NoContent)
     })
@@ -727,7 +2276,9 @@ pub struct NewIpWhitelistEntry {
 }
 
 #[post("/ip-whitelist", data = "<entry>")]
-pub fn post_ip_whitelist(entry: Json<NewIpWhitelistEntry>, conn: db::Conn, user: SiteAdmin) -> ApiResult<Json<i32>> {
+pub fn post_ip_whitelist(entry: Json<NewIpWhitelistEntry>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    user.ensure_course_admin_for(entry.year)?;
+
     conn.transaction(|| {
         let entry = entry.into_inner();
         let (id, ipnet) = diesel::insert_into(db::ip_whitelist::table)
@@ -749,7 +2300,7 @@ pub fn post_ip_whitelist(entry: Json<NewIpWhitelistEntry>, conn: db::Conn, user:
 }
 
 #[delete("/ip-whitelist/<entry>")]
-pub fn delete_ip_whitelist(entry: i32, conn: db::Conn, user: SiteAdmin) -> ApiResult<Status> {
+pub fn delete_ip_whitelist(entry: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
     conn.transaction(|| {
         let (year, ipnet) = diesel::delete(
             db::ip_whitelist::table.find(entry))
@@ -758,6 +2309,7 @@ pub fn delete_ip_whitelist(entry: i32, conn: db::Conn, user: SiteAdmin) -> ApiRe
                 db::ip_whitelist::ipnet.abbrev(),
             ))
             .get_result::<(i16, String)>(&*conn)?;
+        user.ensure_course_admin_for(year)?;
 
         add_audit_log(year, None, user.name(), &conn,
             &format!("Remove ip whitelist entry {} (#{})", ipnet, entry))?;
@@ -765,3 +2317,67 @@ pub fn delete_ip_whitelist(entry: i32, conn: db::Conn, user: SiteAdmin) -> ApiRe
         Ok(Status::NoContent)
     })
 }
+
+#[put("/trash/<entry>/restored")]
+pub fn put_trash_restored(entry: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_entry = db::trash::table
+            .find(entry)
+            .get_result::<db::TrashEntry>(&*conn)?;
+
+        // Restoring requires the permission needed for deleting the entry
+        user.ensure_permission(trash_permission(&full_entry.kind), full_entry.year)?;
+
+        if !db::restore_trash(&full_entry, &conn)? {
+            return Err(ApiError::ConstraintViolation);
+        }
+
+        add_audit_log(full_entry.year, None, user.name(), &conn,
+            &format!("Restore {} {} from trash (#{})", full_entry.kind, full_entry.name, entry))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[delete("/trash/<entry>")]
+pub fn delete_trash(entry: i32, conn: db::Conn, user: User) -> ApiResult<Status> {
+    conn.transaction(|| {
+        let full_entry = db::trash::table
+            .find(entry)
+            .get_result::<db::TrashEntry>(&*conn)?;
+        user.ensure_permission(trash_permission(&full_entry.kind), full_entry.year)?;
+
+        diesel::delete(db::trash::table.find(entry))
+            .execute(&*conn)
+            .and_then(db::expect1)?;
+
+        add_audit_log(full_entry.year, None, user.name(), &conn,
+            &format!("Purge {} {} from trash (#{})", full_entry.kind, full_entry.name, entry))?;
+
+        Ok(Status::NoContent)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ordered_names;
+
+    fn entries() -> Vec<(i32, String)> {
+        vec![(1, "Pendel".into()), (4, "Linsen".into()), (2, "Optik".into())]
+    }
+
+    #[test]
+    fn ordered_names_follow_order() {
+        assert_eq!(ordered_names(&[2, 1, 4], entries()).unwrap(), vec!["Optik", "Pendel", "Linsen"]);
+        assert_eq!(ordered_names(&[1, 4, 2], entries()).unwrap(), vec!["Pendel", "Linsen", "Optik"]);
+        assert!(ordered_names(&[], vec![]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ordered_names_need_all_entries_once() {
+        assert!(ordered_names(&[2, 1], entries()).is_err());
+        assert!(ordered_names(&[2, 1, 4, 3], entries()).is_err());
+        assert!(ordered_names(&[2, 1, 1], entries()).is_err());
+        assert!(ordered_names(&[2, 1, 3], entries()).is_err());
+    }
+}
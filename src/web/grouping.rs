@@ -0,0 +1,199 @@
+use crate::db;
+use crate::errors::{ApiError, ApiResult};
+use crate::web::room::Layout;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use std::collections::{HashMap, HashSet};
+
+/// Groups proposed for the students of a year that are not in any group yet
+#[derive(Debug, Default, Serialize)]
+pub struct Formation {
+    pub groups: Vec<Proposal>,
+    /// Students whose preferred partner is not in their proposed group
+    pub unmatched: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Proposal {
+    pub desk: i32,
+    pub students: Vec<i32>,
+    pub names: Vec<String>,
+}
+
+// Representative of the cluster of preferred partners the student belongs to
+fn find(parents: &mut Vec<usize>, i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Split students into groups of at most the given size, given the index of
+/// the preferred partner of every student
+///
+/// Clusters of preferred partners are kept together as long as they fit into
+/// one group, only the last groups may be smaller than the given size.
+fn cluster(partners: &[Option<usize>], size: usize) -> Vec<Vec<usize>> {
+    let mut parents: Vec<_> = (0..partners.len()).collect();
+    for (i, partner) in partners.iter().enumerate() {
+        if let Some(&partner) = partner.as_ref() {
+            let (a, b) = (find(&mut parents, i), find(&mut parents, partner));
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+
+    // Clusters in the order of their first student, too large ones are split
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    let mut cluster_of_root = HashMap::new();
+    for i in 0..partners.len() {
+        let root = find(&mut parents, i);
+        let cluster = *cluster_of_root.entry(root).or_insert_with(|| {
+            clusters.push(vec![]);
+            clusters.len() - 1
+        });
+        clusters[cluster].push(i);
+    }
+    let mut clusters: Vec<_> = clusters.into_iter()
+        .flat_map(|cluster| cluster.chunks(size).map(<[usize]>::to_vec).collect::<Vec<_>>())
+        .collect();
+
+    // First fit of the largest clusters keeps the number of groups small
+    clusters.sort_by_key(|cluster| ::std::cmp::Reverse(cluster.len()));
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for cluster in clusters {
+        match groups.iter_mut().find(|group| group.len() + cluster.len() <= size) {
+            Some(group) => group.extend(cluster),
+            None => groups.push(cluster),
+        }
+    }
+
+    groups
+}
+
+/// Desks of the day that are neither used by a group that has not ended nor
+/// missing in the layout of its room, in the order of the layout or ascending
+fn free_desks(day: &db::Day, count: usize, conn: &PgConnection) -> ApiResult<Vec<i32>> {
+    let occupied: HashSet<i32> = db::groups::table
+        .filter(db::groups::day_id.eq(day.id))
+        .filter(db::groups::comment.not_like("%(ENDE)%"))
+        .select(db::groups::desk)
+        .load(conn)?
+        .into_iter()
+        .collect();
+
+    let layout = match day.room_id {
+        Some(room) => {
+            let layout: String = db::rooms::table
+                .find(room)
+                .select(db::rooms::layout)
+                .get_result(conn)?;
+            Layout::parse(&layout).unwrap_or_default()
+        }
+        None => Layout::default(),
+    };
+
+    let desks: Vec<_> = match layout.rows.is_empty() {
+        true => (1..)
+            .filter(|desk| !occupied.contains(desk))
+            .take(count)
+            .collect(),
+        false => layout.rows.iter()
+            .flat_map(|row| row.iter().filter_map(|desk| *desk))
+            .filter(|desk| !occupied.contains(desk))
+            .take(count)
+            .collect(),
+    };
+
+    // The room is too small for the new groups
+    if desks.len() < count {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    Ok(desks)
+}
+
+/// Propose groups of at most the given size on the day for all students of
+/// its year that are not in any group yet, regardless of the day they attend
+///
+/// Students who registered each other as preferred partners, also across
+/// several students, are kept together as long as they fit into one group.
+pub fn propose(day: &db::Day, size: usize, conn: &PgConnection) -> ApiResult<Formation> {
+    if size == 0 {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    let grouped: HashSet<i32> = db::group_mappings::table
+        .inner_join(db::students::table)
+        .filter(db::students::year.eq(day.year))
+        .select(db::group_mappings::student_id)
+        .load(conn)?
+        .into_iter()
+        .collect();
+    let students: Vec<_> = db::students::table
+        .filter(db::students::year.eq(day.year))
+        .order((db::students::family_name.asc(), db::students::given_name.asc(), db::students::id.asc()))
+        .load::<db::Student>(conn)?
+        .into_iter()
+        .filter(|student| !grouped.contains(&student.id))
+        .collect();
+
+    let indices: HashMap<_, _> = students.iter()
+        .enumerate()
+        .map(|(i, student)| (student.matrikel.as_str(), i))
+        .collect();
+    let partners: Vec<_> = students.iter()
+        .map(|student| student.partner.as_ref().and_then(|p| indices.get(p.as_str()).cloned()))
+        .collect();
+    let groups = cluster(&partners, size);
+
+    let desks = free_desks(day, groups.len(), conn)?;
+
+    let mut formation = Formation::default();
+    for (group, desk) in groups.into_iter().zip(desks) {
+        for &i in &group {
+            let partner = students[i].partner.as_ref();
+            if partner.is_some() && !group.iter().any(|&j| Some(&students[j].matrikel) == partner) {
+                formation.unmatched.push(students[i].name());
+            }
+        }
+
+        formation.groups.push(Proposal {
+            desk: desk,
+            students: group.iter().map(|&i| students[i].id).collect(),
+            names: group.iter().map(|&i| students[i].name()).collect(),
+        });
+    }
+
+    Ok(formation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cluster;
+
+    #[test]
+    fn cluster_without_partners() {
+        assert_eq!(cluster(&[None; 5], 2), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(cluster(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn cluster_keeps_partners_together() {
+        let partners = [None, Some(3), None, None, Some(0)];
+        assert_eq!(cluster(&partners, 2), vec![vec![0, 4], vec![1, 3], vec![2]]);
+    }
+
+    #[test]
+    fn cluster_joins_chains_of_partners() {
+        let partners = [Some(2), None, Some(4), None, None, None];
+        assert_eq!(cluster(&partners, 3), vec![vec![0, 2, 4], vec![1, 3, 5]]);
+    }
+
+    #[test]
+    fn cluster_splits_too_large_clusters() {
+        let partners = [Some(1), Some(2), Some(0), None];
+        assert_eq!(cluster(&partners, 2), vec![vec![0, 1], vec![2, 3]]);
+    }
+}
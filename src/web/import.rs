@@ -0,0 +1,437 @@
+use calamine::{Ods, Reader, Xlsx};
+use crate::db;
+use crate::errors::*;
+use csv::ReaderBuilder;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use rocket::http::ContentType;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+
+// Known header names (lowercase) of the supported columns, including the
+// ones used by the exports of the campus management system
+const MATRIKEL_HEADERS: &[&str] = &["matrikel", "matrikelnummer", "matrikelnr", "matrikel-nr."];
+const GIVEN_NAME_HEADERS: &[&str] = &["vorname", "given_name", "given name"];
+const FAMILY_NAME_HEADERS: &[&str] = &["familienname", "nachname", "family_name", "family name"];
+const USERNAME_HEADERS: &[&str] = &["benutzername", "username", "login", "kennung", "nutzerkennung"];
+const EMAIL_HEADERS: &[&str] = &["e-mail", "email", "e-mail-adresse", "mail"];
+const PARTNER_HEADERS: &[&str] = &["partner", "wunschpartner", "partner-matrikel", "partnerwunsch"];
+
+// Number of leading records that are searched for a header row, because
+// exports often start with a title or description of the course
+const HEADER_SEARCH_LIMIT: usize = 10;
+
+// Maximum size of uploaded spreadsheets, which have to be read completely
+const SPREADSHEET_SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+
+/// Options for interpreting an uploaded table of students
+///
+/// Columns can be given either as 1-based index or as header name. Columns
+/// that are not given are detected using their header name or default to
+/// the order matrikel, given name, family name, username, email and the
+/// matrikel of the preferred partner.
+#[derive(Debug, Default, FromForm)]
+pub struct Options {
+    delimiter: Option<String>,
+    headers: Option<bool>,
+    matrikel: Option<String>,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    username: Option<String>,
+    email: Option<String>,
+    partner: Option<String>,
+}
+
+impl Options {
+    fn delimiter(&self) -> ::std::result::Result<u8, String> {
+        match self.delimiter.as_ref().map(String::as_str) {
+            None | Some("") => Ok(b','),
+            Some("tab") | Some("\t") => Ok(b'\t'),
+            Some(delimiter) if delimiter.len() == 1 && delimiter.is_ascii() => {
+                Ok(delimiter.as_bytes()[0])
+            }
+            Some(delimiter) => Err(format!("Ungültiges Trennzeichen '{}'", delimiter)),
+        }
+    }
+}
+
+/// A single student read from the uploaded table
+///
+/// Empty optional fields never overwrite existing values.
+#[derive(Debug, Serialize)]
+pub struct Row {
+    pub line: usize,
+    pub matrikel: String,
+    pub given_name: String,
+    pub family_name: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub partner: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Update,
+    Unchanged,
+    Invalid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowPreview {
+    pub row: Row,
+    pub action: Action,
+    pub student: Option<i32>,
+    pub errors: Vec<String>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Preview {
+    pub has_headers: bool,
+    pub headers: Vec<String>,
+    pub errors: Vec<String>,
+    pub rows: Vec<RowPreview>,
+}
+
+impl Preview {
+    /// Only import if neither the table nor any of the rows has errors
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty() && self.rows.iter().all(|row| row.errors.is_empty())
+    }
+}
+
+/// Read all records from an uploaded csv, xlsx or ods file, depending on
+/// the content type
+pub fn read_records<R: Read>(reader: R, content_type: &ContentType, options: &Options)
+                             -> Result<::std::result::Result<Vec<Vec<String>>, String>> {
+    match (content_type.top().as_str(), content_type.sub().as_str()) {
+        ("text", "csv") | ("text", "plain") => read_csv(reader, options),
+        ("application", "vnd.openxmlformats-officedocument.spreadsheetml.sheet") => {
+            let data = match read_spreadsheet_data(reader)? {
+                Ok(data) => data,
+                Err(error) => return Ok(Err(error)),
+            };
+            Ok(Xlsx::new(Cursor::new(data))
+                .map_err(|e| e.to_string())
+                .and_then(read_first_sheet))
+        }
+        ("application", "vnd.oasis.opendocument.spreadsheet") => {
+            let data = match read_spreadsheet_data(reader)? {
+                Ok(data) => data,
+                Err(error) => return Ok(Err(error)),
+            };
+            Ok(Ods::new(Cursor::new(data))
+                .map_err(|e| e.to_string())
+                .and_then(read_first_sheet))
+        }
+        _ => Ok(Err(format!("Nicht unterstütztes Dateiformat {}", content_type))),
+    }
+}
+
+// Read the spreadsheet up to the size limit, larger files are rejected with
+// an error message for the user instead of being truncated
+fn read_spreadsheet_data<R: Read>(reader: R) -> Result<::std::result::Result<Vec<u8>, String>> {
+    let mut data = vec![];
+    reader.take(SPREADSHEET_SIZE_LIMIT + 1).read_to_end(&mut data)
+        .chain_err(|| "Could not read uploaded spreadsheet")?;
+
+    if data.len() as u64 > SPREADSHEET_SIZE_LIMIT {
+        return Ok(Err(format!("Die Datei ist größer als {} MiB",
+            SPREADSHEET_SIZE_LIMIT / 1024 / 1024)));
+    }
+
+    Ok(Ok(data))
+}
+
+// Read all non-empty rows of the first sheet as text
+fn read_first_sheet<R: Reader>(mut workbook: R) -> ::std::result::Result<Vec<Vec<String>>, String>
+where R::Error: ::std::fmt::Display {
+    let name = workbook.sheet_names().first().cloned()
+        .ok_or("Die Datei enthält keine Tabelle")?;
+    let range = workbook.worksheet_range(&name)
+        .ok_or("Die Datei enthält keine Tabelle")?
+        .map_err(|e| e.to_string())?;
+
+    Ok(range.rows()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>())
+        .filter(|row| row.iter().any(|field| !field.trim().is_empty()))
+        .collect())
+}
+
+/// Read all records from a csv file without interpreting them
+fn read_csv<R: Read>(reader: R, options: &Options) -> Result<::std::result::Result<Vec<Vec<String>>, String>> {
+    let delimiter = match options.delimiter() {
+        Ok(delimiter) => delimiter,
+        Err(error) => return Ok(Err(error)),
+    };
+
+    let mut csv_reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(reader);
+
+    // Invalid UTF-8 is kept as replacement character, so it is reported as
+    // error of its row instead of failing the whole file
+    let mut records = vec![];
+    for record in csv_reader.byte_records() {
+        records.push(record?.iter().map(|field| String::from_utf8_lossy(field).into_owned()).collect());
+    }
+
+    Ok(Ok(records))
+}
+
+/// Validate the given records and compare them with the existing students
+/// of the year
+pub fn preview(year: i16, mut records: Vec<Vec<String>>, options: &Options, conn: &PgConnection) -> Result<Preview> {
+    let mut preview = Preview::default();
+
+    // Skip everything before the header row when detecting the header
+    let header_index = match options.headers {
+        Some(true) if !records.is_empty() => Some(0),
+        Some(_) => None,
+        None => records.iter()
+            .take(HEADER_SEARCH_LIMIT)
+            .position(|record| is_header(record)),
+    };
+    let has_headers = header_index.is_some();
+    let headers = header_index.map(|index| {
+        records.drain(..=index).last().expect("header index is valid")
+    });
+
+    let columns = find_columns(options, headers.as_ref().map(Vec::as_slice));
+    preview.has_headers = has_headers;
+    preview.headers = headers.unwrap_or_default();
+    let columns = match columns {
+        Ok(columns) => columns,
+        Err(error) => {
+            preview.errors.push(error);
+            return Ok(preview);
+        }
+    };
+
+    if records.is_empty() {
+        preview.errors.push("Die Datei enthält keine Teilnehmer".into());
+        return Ok(preview);
+    }
+
+    let mut existing: HashMap<String, Vec<db::Student>> = HashMap::new();
+    for student in db::students::table
+        .filter(db::students::year.eq(year))
+        .load::<db::Student>(conn)? {
+        existing.entry(student.matrikel.clone()).or_default().push(student);
+    }
+
+    let first_line = header_index.map_or(1, |index| index + 2);
+    let mut seen_matrikel = HashSet::new();
+
+    for (i, record) in records.into_iter().enumerate() {
+        let row = columns.read(first_line + i, &record);
+        let mut errors = validate(&row);
+
+        if !row.matrikel.is_empty() && !seen_matrikel.insert(row.matrikel.clone()) {
+            errors.push(format!("Matrikelnummer {} ist mehrfach in der Datei enthalten", row.matrikel));
+        }
+
+        let (student, changes) = match existing.get(&row.matrikel).map(Vec::as_slice) {
+            Some([student]) => (Some(student.id), compare(student, &row)),
+            Some(_) => {
+                errors.push(format!("Matrikelnummer {} existiert bereits mehrfach", row.matrikel));
+                (None, vec![])
+            }
+            None => (None, vec![]),
+        };
+
+        let action = match (errors.is_empty(), student, changes.is_empty()) {
+            (false, _, _) => Action::Invalid,
+            (true, None, _) => Action::Create,
+            (true, Some(_), false) => Action::Update,
+            (true, Some(_), true) => Action::Unchanged,
+        };
+
+        preview.rows.push(RowPreview { row, action, student, errors, changes });
+    }
+
+    Ok(preview)
+}
+
+fn is_header(record: &[String]) -> bool {
+    record.iter().any(|field| {
+        let field = field.trim().to_lowercase();
+        [MATRIKEL_HEADERS, GIVEN_NAME_HEADERS, FAMILY_NAME_HEADERS, USERNAME_HEADERS, EMAIL_HEADERS, PARTNER_HEADERS]
+            .iter()
+            .any(|headers| headers.contains(&field.as_str()))
+    })
+}
+
+struct Columns {
+    matrikel: usize,
+    given_name: usize,
+    family_name: usize,
+    username: Option<usize>,
+    email: Option<usize>,
+    partner: Option<usize>,
+}
+
+impl Columns {
+    fn read(&self, line: usize, record: &[String]) -> Row {
+        let field = |column: usize| {
+            record.get(column).map_or("", |field| field.trim()).to_string()
+        };
+        let optional_field = |column: Option<usize>| {
+            column.map(|column| field(column)).filter(|field| !field.is_empty())
+        };
+
+        Row {
+            line: line,
+            matrikel: field(self.matrikel),
+            given_name: field(self.given_name),
+            family_name: field(self.family_name),
+            username: optional_field(self.username),
+            email: optional_field(self.email),
+            partner: optional_field(self.partner),
+        }
+    }
+}
+
+fn find_columns(options: &Options, headers: Option<&[String]>) -> ::std::result::Result<Columns, String> {
+    let matrikel = find_column(&options.matrikel, MATRIKEL_HEADERS, 0, headers)?
+        .ok_or("Spalte für die Matrikelnummer nicht gefunden")?;
+    let given_name = find_column(&options.given_name, GIVEN_NAME_HEADERS, 1, headers)?
+        .ok_or("Spalte für den Vornamen nicht gefunden")?;
+    let family_name = find_column(&options.family_name, FAMILY_NAME_HEADERS, 2, headers)?
+        .ok_or("Spalte für den Familiennamen nicht gefunden")?;
+
+    Ok(Columns {
+        matrikel: matrikel,
+        given_name: given_name,
+        family_name: family_name,
+        username: find_column(&options.username, USERNAME_HEADERS, 3, headers)?,
+        email: find_column(&options.email, EMAIL_HEADERS, 4, headers)?,
+        partner: find_column(&options.partner, PARTNER_HEADERS, 5, headers)?,
+    })
+}
+
+// Find a column by its explicit specification (index or header name), by its
+// known header names or fall back to the default index without headers
+fn find_column(spec: &Option<String>, known: &[&str], default: usize, headers: Option<&[String]>)
+               -> ::std::result::Result<Option<usize>, String> {
+    let matches = |header: &String, name: &str| header.trim().to_lowercase() == name.trim().to_lowercase();
+
+    match (spec, headers) {
+        (Some(spec), headers) => {
+            if let Ok(index) = spec.trim().parse::<usize>() {
+                return match index {
+                    0 => Err(format!("Ungültige Spalte '{}'", spec)),
+                    index => Ok(Some(index - 1)),
+                };
+            }
+
+            headers.and_then(|headers| headers.iter().position(|h| matches(h, spec.as_str())))
+                .map(Some)
+                .ok_or_else(|| format!("Spalte '{}' nicht gefunden", spec))
+        }
+        (None, Some(headers)) => Ok(headers.iter()
+            .position(|h| known.iter().any(|name| matches(h, *name)))),
+        (None, None) => Ok(Some(default)),
+    }
+}
+
+/// Check a single row and return messages for all invalid fields
+pub fn validate(row: &Row) -> Vec<String> {
+    let mut errors = vec![];
+
+    let fields = [Some(&row.matrikel), Some(&row.given_name), Some(&row.family_name),
+                  row.username.as_ref(), row.email.as_ref(), row.partner.as_ref()];
+    if fields.iter().flatten().any(|field| field.contains(::std::char::REPLACEMENT_CHARACTER)) {
+        errors.push("Ungültige Zeichen, die Datei ist nicht UTF-8-codiert".into());
+    }
+
+    if row.matrikel.is_empty() {
+        errors.push("Matrikelnummer fehlt".into());
+    } else if row.matrikel.contains(char::is_whitespace) {
+        errors.push(format!("Ungültige Matrikelnummer '{}'", row.matrikel));
+    }
+    if row.given_name.is_empty() {
+        errors.push("Vorname fehlt".into());
+    }
+    if row.family_name.is_empty() {
+        errors.push("Familienname fehlt".into());
+    }
+    if let Some(username) = row.username.as_ref() {
+        if username.contains(char::is_whitespace) {
+            errors.push(format!("Ungültiger Benutzername '{}'", username));
+        }
+    }
+    if let Some(email) = row.email.as_ref() {
+        if !email.contains('@') || email.contains(char::is_whitespace) {
+            errors.push(format!("Ungültige E-Mail-Adresse '{}'", email));
+        }
+    }
+    if let Some(partner) = row.partner.as_ref() {
+        if partner.contains(char::is_whitespace) {
+            errors.push(format!("Ungültige Matrikelnummer des Partners '{}'", partner));
+        } else if partner == &row.matrikel {
+            errors.push("Partner und Teilnehmer sind identisch".into());
+        }
+    }
+
+    errors
+}
+
+fn compare(student: &db::Student, row: &Row) -> Vec<Change> {
+    let mut changes = vec![];
+
+    if student.given_name != row.given_name {
+        changes.push(Change {
+            field: "given_name",
+            old: student.given_name.clone(),
+            new: row.given_name.clone(),
+        });
+    }
+    if student.family_name != row.family_name {
+        changes.push(Change {
+            field: "family_name",
+            old: student.family_name.clone(),
+            new: row.family_name.clone(),
+        });
+    }
+    if let Some(username) = row.username.as_ref() {
+        if student.username.as_ref() != Some(username) {
+            changes.push(Change {
+                field: "username",
+                old: student.username.clone().unwrap_or_default(),
+                new: username.clone(),
+            });
+        }
+    }
+    if let Some(email) = row.email.as_ref() {
+        if student.email.as_ref() != Some(email) {
+            changes.push(Change {
+                field: "email",
+                old: student.email.clone().unwrap_or_default(),
+                new: email.clone(),
+            });
+        }
+    }
+    if let Some(partner) = row.partner.as_ref() {
+        if student.partner.as_ref() != Some(partner) {
+            changes.push(Change {
+                field: "partner",
+                old: student.partner.clone().unwrap_or_default(),
+                new: partner.clone(),
+            });
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,82 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+/// Render a description written in markdown to html
+///
+/// Embedded html is escaped and shown as text. Links and images whose
+/// destination is neither relative nor uses http or https are dropped and
+/// only their text is kept.
+pub fn render(text: &str) -> String {
+    let parser = Parser::new_ext(text, Options::ENABLE_TABLES)
+        .filter(|event| match event {
+            Event::Start(Tag::Link(_, dest, _)) | Event::End(Tag::Link(_, dest, _))
+            | Event::Start(Tag::Image(_, dest, _)) | Event::End(Tag::Image(_, dest, _)) =>
+                is_safe_destination(dest),
+            _ => true,
+        })
+        .map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+// Browsers ignore whitespace and control characters in the scheme of a url,
+// so they are skipped before looking for it
+fn is_safe_destination(dest: &str) -> bool {
+    let dest: String = dest.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+
+    match dest.find(|c| c == ':' || c == '/' || c == '?' || c == '#') {
+        Some(i) if dest[i..].starts_with(':') => {
+            let scheme = dest[..i].to_lowercase();
+            scheme == "http" || scheme == "https"
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(render("<script>alert(1)</script>"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;\n");
+        assert_eq!(render("a <b>b</b>"), "<p>a &lt;b&gt;b&lt;/b&gt;</p>\n");
+    }
+
+    #[test]
+    fn keeps_safe_links() {
+        assert_eq!(render("[a](https://example.com/a)"),
+            "<p><a href=\"https://example.com/a\">a</a></p>\n");
+        assert_eq!(render("[a](HTTP://example.com)"),
+            "<p><a href=\"HTTP://example.com\">a</a></p>\n");
+        assert_eq!(render("[a](/api/attachment/1)"),
+            "<p><a href=\"/api/attachment/1\">a</a></p>\n");
+        assert_eq!(render("[a](b?c=d:e)"), "<p><a href=\"b?c=d:e\">a</a></p>\n");
+        assert_eq!(render("![a](a.png)"), "<p><img src=\"a.png\" alt=\"a\" /></p>\n");
+    }
+
+    #[test]
+    fn drops_unsafe_links() {
+        assert_eq!(render("[a](javascript:alert(1))"), "<p>a</p>\n");
+        assert_eq!(render("[a](JavaScript:alert(1))"), "<p>a</p>\n");
+        assert_eq!(render("[a](&#106;avascript:alert(1))"), "<p>a</p>\n");
+        assert_eq!(render("[a](<java\tscript:alert(1)>)"), "<p>a</p>\n");
+        assert_eq!(render("<javascript:alert(1)>"), "<p>javascript:alert(1)</p>\n");
+        assert_eq!(render("[a]\n\n[a]: data:text/html,b"), "<p>a</p>\n");
+        assert_eq!(render("![a](data:image/png,b)"), "<p>a</p>\n");
+    }
+
+    #[test]
+    fn renders_tables() {
+        assert_eq!(render("a | b\n--|--\nc | d"),
+            "<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody>\n\
+             <tr><td>c</td><td>d</td></tr>\n</tbody></table>\n");
+    }
+}
@@ -1,12 +1,21 @@
 pub mod admin;
 pub mod analysis;
 pub mod api;
+pub mod import;
+mod grouping;
+mod markdown;
 mod models;
+pub mod permissions;
+pub mod portal;
 pub mod push;
+mod regroup;
+mod room;
 pub mod session;
+pub mod upload;
 
 use crate::db;
 use crate::errors::*;
+use crate::web::permissions::Permission;
 use crate::web::session::User;
 use rocket::State;
 use rocket::http::RawStr;
@@ -35,10 +44,15 @@ impl Deref for Date {
 }
 
 #[get("/")]
-pub fn index(conn: db::Conn, user: User) -> Result<Template> {
+pub fn index(conn: db::Conn, user: User) -> Result<::std::result::Result<Template, Redirect>> {
+    // Students can only access their own records
+    if user.is_student_only() {
+        return Ok(Err(Redirect::to("/portal")));
+    }
+
     let filtered_years = models::find_years(&conn)?
         .into_iter()
-        .filter(|year| user.is_tutor_for(year.name))
+        .filter(|year| user.is_tutor_for(year.id))
         .collect();
 
     let context = models::Index {
@@ -47,18 +61,22 @@ pub fn index(conn: db::Conn, user: User) -> Result<Template> {
         commit_id: include_str!(concat!(env!("OUT_DIR"), "/commit-id")),
     };
 
-    Ok(Template::render("index", &context))
+    Ok(Ok(Template::render("index", &context)))
 }
 
-#[get("/<year>", rank = 2)]
-pub fn overview(year: i16, conn: db::Conn, user: User) -> Result<Template> {
-    user.ensure_tutor_for(year)?;
+#[get("/<course>/<year>", rank = 2)]
+pub fn overview(course: String, year: i16, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::View, year)?;
+    models::ensure_course(&course, year, &conn)?;
 
+    let supervised_days = models::find_supervised_days(year, user.name(), &conn)?;
     let context = models::Overview {
+        course: course,
         year: year,
+        title: models::year_title(year, &conn)?,
         read_only: !models::is_writable_year(year, &conn)?,
         is_admin: user.is_admin_for(year),
-        experiments: models::find_events(year, &conn)?,
+        experiments: models::find_events(year, &supervised_days, &conn)?,
     };
 
     Ok(Template::render("overview", &context))
@@ -66,29 +84,40 @@ pub fn overview(year: i16, conn: db::Conn, user: User) -> Result<Template> {
 
 #[get("/<date>")]
 pub fn event_finder(date: Date, conn: db::Conn, _user: User) -> Result<Redirect> {
-    let day = models::find_event_day_by_date(&date, &conn)?;
+    let (course, year, day) = models::find_event_day_by_date(&date, &conn)?;
 
-    Ok(Redirect::to(format!("/{}/{}", *date, day)))
+    Ok(Redirect::to(format!("/{}/{}/{}/{}", course, year, *date, day)))
 }
 
-#[get("/<date>/<day>", rank = 2)]
-pub fn event(date: Date, day: String, push_url: State<push::Url>, conn: db::Conn, user: User) -> Result<Template> {
-    let context = models::load_event(&date, &day, &push_url.0, &conn)?;
+#[get("/<course>/<year>/<date>/<day>", rank = 2)]
+pub fn event(course: String, year: i16, date: Date, day: String, push_url: State<push::Url>, conn: db::Conn, user: User) -> Result<Template> {
+    user.ensure_permission(Permission::View, year)?;
+    models::ensure_course(&course, year, &conn)?;
 
-    user.ensure_tutor_for(context.year)?;
+    let mut context = models::load_event(&date, &day, year, &push_url.0, &conn)?;
+    context.supervised = models::supervises_day(&user, context.year, context.day_id, &conn)?;
+    context.read_only |= is_observer(&user, context.year) || !context.supervised;
 
     Ok(Template::render("event", &context))
 }
 
 #[get("/group/<group>")]
-pub fn group(group: i32, push_url: State<push::Url>, conn: db::Conn, user: User) -> Result<Template> {
-    let context = models::load_group(group, &push_url.0, &conn)?;
+pub fn group(group: i32, push_url: State<push::Url>, uploads: State<upload::Uploads>, conn: db::Conn, user: User) -> Result<Template> {
+    let mut context = models::load_group(group, &push_url.0, &conn)?;
 
-    user.ensure_tutor_for(context.year)?;
+    user.ensure_permission(Permission::View, context.year)?;
+    context.read_only |= is_observer(&user, context.year);
+    context.uploads = uploads.is_enabled();
 
     Ok(Template::render("group", &context))
 }
 
+// Observers see events and groups like in a closed year
+fn is_observer(user: &User, year: i16) -> bool {
+    ![Permission::Completions, Permission::Elaborations, Permission::Groups].iter()
+        .any(|&permission| user.has_permission(permission, year))
+}
+
 #[get("/static/<path..>")]
 pub fn static_file(path: PathBuf) -> Option<NamedFile> {
     NamedFile::open(Path::new("templates/static/").join(path)).ok()
@@ -1,7 +1,11 @@
 use chrono::NaiveDate;
 use crate::db;
 use crate::errors::*;
+use crate::web::markdown;
+use crate::web::permissions::Permission;
 use crate::web::push;
+use crate::web::room::Layout;
+use crate::web::session::User;
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
 use itertools::Itertools;
@@ -9,7 +13,12 @@ use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize)]
 pub struct Year {
-    pub name: i16,
+    pub id: i16,
+    pub label: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub course: String,
+    pub course_name: String,
     pub read_only: bool,
 }
 
@@ -22,7 +31,9 @@ pub struct Index {
 
 #[derive(Serialize)]
 pub struct Overview {
+    pub course: String,
     pub year: i16,
+    pub title: String,
     pub read_only: bool,
     pub is_admin: bool,
     pub experiments: Vec<Experiment>,
@@ -37,30 +48,332 @@ pub struct Experiment {
 
 #[derive(Serialize)]
 pub struct Event {
+    pub course: String,
     pub year: i16,
     pub read_only: bool,
+    pub supervised: bool,
     pub date: String,
     pub day_id: i32,
     pub day: String,
     pub experiment_id: i32,
     pub experiment: String,
     pub groups: Vec<EventGroup>,
+    pub days: Vec<Day>,
+    pub seat_map: Option<SeatMap>,
+    pub instructions: Option<Instructions>,
     pub prev_event: Option<String>,
     pub next_event: Option<String>,
     pub push: PushEndpoint,
 }
 
+/// Day of the year a group can be moved to
+#[derive(Serialize)]
+pub struct Day {
+    pub id: i32,
+    pub name: String,
+}
+
+/// Desks of the room of a day and the groups sitting there
+#[derive(Serialize)]
+pub struct SeatMap {
+    pub room: String,
+    pub rows: Vec<Vec<Seat>>,
+    /// Groups at desks that do not exist in the room
+    pub unseated: Vec<Seat>,
+}
+
+#[derive(Serialize)]
+pub struct Seat {
+    /// None for gaps between desks
+    pub desk: Option<i32>,
+    pub group: Option<i32>,
+    pub percent: i32,
+    /// Whether more than one group sits at the desk
+    pub conflict: bool,
+}
+
+/// Place the groups at the desks of the room of the day, which is None if the
+/// day has no room with a layout
+///
+/// Groups that ended are not placed.
+pub fn load_seat_map(day: &db::Day, groups: &[EventGroup], conn: &PgConnection) -> Result<Option<SeatMap>> {
+    let room = match day.room_id {
+        Some(room) => db::rooms::table.find(room).get_result::<db::Room>(conn)?,
+        None => return Ok(None),
+    };
+    let layout = match Layout::parse(&room.layout) {
+        Ok(ref layout) if layout.rows.is_empty() => return Ok(None),
+        Ok(layout) => layout,
+        Err(_) => return Ok(None),
+    };
+
+    let mut desks: HashMap<_, Vec<_>> = HashMap::new();
+    for group in groups.iter().filter(|group| !group.disqualified) {
+        desks.entry(group.desk).or_default().push(group);
+    }
+
+    fn seat(desk: Option<i32>, desks: &mut HashMap<i32, Vec<&EventGroup>>) -> Seat {
+        let groups = desk.and_then(|desk| desks.remove(&desk)).unwrap_or_default();
+        Seat {
+            desk: desk,
+            group: groups.first().map(|group| group.id),
+            percent: groups.first().map_or(0, |group| group.score.percent),
+            conflict: groups.len() > 1,
+        }
+    }
+
+    let rows = layout.rows.iter()
+        .map(|row| row.iter().map(|&desk| seat(desk, &mut desks)).collect())
+        .collect();
+    let unseated = desks.keys().cloned().sorted().into_iter()
+        .map(|desk| seat(Some(desk), &mut desks))
+        .collect();
+
+    Ok(Some(SeatMap {
+        room: room.name,
+        rows: rows,
+        unseated: unseated,
+    }))
+}
+
+/// Descriptions and attachments of the experiment and its tasks for tutors
+#[derive(Serialize)]
+pub struct Instructions {
+    pub description: String,
+    pub attachments: Vec<Attachment>,
+    pub tasks: Vec<TaskInstructions>,
+}
+
+#[derive(Serialize)]
+pub struct TaskInstructions {
+    pub name: String,
+    pub description: String,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    pub image: bool,
+}
+
+impl From<db::Attachment> for Attachment {
+    fn from(attachment: db::Attachment) -> Attachment {
+        let id = attachment.id;
+
+        Attachment {
+            id: id,
+            title: attachment.title,
+            url: attachment.url.unwrap_or_else(|| format!("/api/attachment/{}", id)),
+            image: attachment.content_type.map_or(false, |t| t.starts_with("image/")),
+        }
+    }
+}
+
+/// Load the descriptions rendered as html and the attachments of the
+/// experiment and its tasks, which is None if there are none at all
+pub fn load_instructions(experiment: &db::Experiment, tasks: &[db::Task], conn: &PgConnection) -> Result<Option<Instructions>> {
+    let mut attachments: HashMap<_, Vec<_>> = HashMap::new();
+    for attachment in db::attachments::table
+        .filter(db::attachments::experiment_id.eq(experiment.id))
+        .order(db::attachments::id.asc())
+        .load::<db::Attachment>(conn)? {
+        attachments.entry(attachment.task_id)
+            .or_default()
+            .push(Attachment::from(attachment));
+    }
+
+    let task_instructions: Vec<_> = tasks.iter()
+        .filter_map(|task| {
+            let task_attachments = attachments.remove(&Some(task.id)).unwrap_or_default();
+            if task.description.trim().is_empty() && task_attachments.is_empty() {
+                return None;
+            }
+
+            Some(TaskInstructions {
+                name: task.name.clone(),
+                description: markdown::render(&task.description),
+                attachments: task_attachments,
+            })
+        })
+        .collect();
+    let experiment_attachments = attachments.remove(&None).unwrap_or_default();
+
+    if experiment.description.trim().is_empty() && experiment_attachments.is_empty()
+        && task_instructions.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Instructions {
+        description: markdown::render(&experiment.description),
+        attachments: experiment_attachments,
+        tasks: task_instructions,
+    }))
+}
+
 #[derive(Serialize)]
 pub struct EventGroup {
     pub id: i32,
     pub desk: i32,
     pub students: Vec<Student>,
-    pub tasks: Vec<(i32, String, bool)>,
+    pub tasks: Vec<Task>,
+    pub score: Score,
     pub elaboration: Option<(bool, bool)>,
+    pub grading: Option<Grading>,
     pub disqualified: bool,
     pub comment: String,
 }
 
+#[derive(Serialize)]
+pub struct Task {
+    pub id: i32,
+    pub name: String,
+    pub completed: bool,
+    // Partial credit, all points if None
+    pub points: Option<i16>,
+    pub max_points: i16,
+    pub weight: i16,
+}
+
+impl Task {
+    fn new(task: &db::Task, completion: Option<Option<i16>>) -> Task {
+        Task {
+            id: task.id,
+            name: task.name.clone(),
+            completed: completion.is_some(),
+            points: completion.and_then(|points| points),
+            max_points: task.points,
+            weight: task.weight,
+        }
+    }
+}
+
+/// Weighted points of the tasks of an experiment
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Score {
+    pub reached: i32,
+    pub maximum: i32,
+    pub percent: i32,
+}
+
+impl Score {
+    /// Add the task, which may be completed with partial credit
+    pub fn add(&mut self, task: &db::Task, completion: Option<Option<i16>>) {
+        let weight = i32::from(task.weight);
+        self.maximum += weight * i32::from(task.points);
+        if let Some(points) = completion {
+            self.reached += weight * i32::from(points.unwrap_or(task.points));
+        }
+
+        // Experiments without any weighted tasks are always fully scored
+        self.percent = match self.maximum {
+            0 => 100,
+            maximum => self.reached * 100 / maximum,
+        };
+    }
+
+    /// Whether at least the given percentage of the points is reached
+    pub fn reaches(&self, percent: i32) -> bool {
+        self.reached * 100 >= percent * self.maximum
+    }
+}
+
+#[derive(Serialize)]
+pub struct Grading {
+    pub points: Option<i16>,
+    pub grade: Option<String>,
+    pub feedback: String,
+    pub grader: Option<String>,
+    pub submitted_on: Option<String>,
+    pub rework_due: Option<String>,
+    pub overdue: bool,
+}
+
+impl From<db::Elaboration> for Grading {
+    fn from(elaboration: db::Elaboration) -> Grading {
+        let needs_rework = elaboration.rework_required && !elaboration.accepted;
+        let today = chrono::Utc::today().naive_utc();
+
+        Grading {
+            overdue: needs_rework && elaboration.rework_due.map_or(false, |due| due < today),
+            points: elaboration.points,
+            grade: elaboration.grade,
+            feedback: elaboration.feedback,
+            grader: elaboration.grader,
+            submitted_on: elaboration.submitted_on.map(|date| date.to_string()),
+            rework_due: elaboration.rework_due.map(|date| date.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ElaborationFile {
+    pub id: i32,
+    pub version: i32,
+    pub filename: String,
+    pub uploaded_at: String,
+    pub uploader: String,
+}
+
+impl From<db::ElaborationFile> for ElaborationFile {
+    fn from(file: db::ElaborationFile) -> ElaborationFile {
+        ElaborationFile {
+            id: file.id,
+            version: file.version,
+            filename: file.filename,
+            uploaded_at: file.uploaded_at.format("%Y-%m-%d %H:%M").to_string(),
+            uploader: file.uploader,
+        }
+    }
+}
+
+/// Load all uploaded elaborations of the group by experiment, newest first
+pub fn load_elaboration_files(group: i32, conn: &PgConnection) -> Result<HashMap<i32, Vec<ElaborationFile>>> {
+    let mut files: HashMap<_, Vec<_>> = HashMap::new();
+    for file in db::elaboration_files::table
+        .filter(db::elaboration_files::group_id.eq(group))
+        .order(db::elaboration_files::version.desc())
+        .load::<db::ElaborationFile>(conn)? {
+        files.entry(file.experiment_id)
+            .or_default()
+            .push(ElaborationFile::from(file));
+    }
+
+    Ok(files)
+}
+
+#[derive(Serialize)]
+pub struct Submission {
+    pub created_at: String,
+    pub status: &'static str,
+    pub points: Option<i16>,
+    pub grade: Option<String>,
+    pub feedback: String,
+    pub grader: String,
+}
+
+impl From<db::ElaborationSubmission> for Submission {
+    fn from(submission: db::ElaborationSubmission) -> Submission {
+        let status = match (submission.rework_required, submission.accepted) {
+            (false, false) => "Ausarbeitung abgegeben",
+            (false,  true) => "Ausarbeitung akzeptiert",
+            ( true, false) => "Nachbesserung nötig",
+            ( true,  true) => "Nachbesserung akzeptiert",
+        };
+
+        Submission {
+            created_at: submission.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            status: status,
+            points: submission.points,
+            grade: submission.grade,
+            feedback: submission.feedback,
+            grader: submission.grader,
+        }
+    }
+}
+
 #[derive(Default, Serialize)]
 pub struct PushEndpoint {
     pub url: String,
@@ -71,12 +384,14 @@ pub struct PushEndpoint {
 pub struct GroupOverview {
     pub id: i32,
     pub desk: i32,
+    pub course: String,
     pub year: i16,
     pub read_only: bool,
     pub day: String,
     pub comment: String,
     pub students: Vec<Student>,
     pub events: Vec<GroupOverviewEvent>,
+    pub uploads: bool,
     pub push: PushEndpoint,
 }
 
@@ -91,8 +406,12 @@ pub struct GroupOverviewEvent {
 pub struct GroupOverviewGroup {
     pub id: i32,
     pub disqualified: bool,
-    pub tasks: Vec<(i32, String, bool)>,
+    pub tasks: Vec<Task>,
+    pub score: Score,
     pub elaboration: Option<(bool, bool)>,
+    pub grading: Option<Grading>,
+    pub submissions: Vec<Submission>,
+    pub files: Vec<ElaborationFile>,
 }
 
 #[derive(Serialize)]
@@ -112,11 +431,17 @@ pub struct SearchGroup {
 
 pub fn find_years(conn: &PgConnection) -> Result<Vec<Year>> {
     let years = db::years::table
-        .order(db::years::id.desc())
-        .load::<db::Year>(conn)?
+        .inner_join(db::courses::table)
+        .order((db::courses::name.asc(), db::years::start_date.desc()))
+        .load::<(db::Year, db::Course)>(conn)?
         .into_iter()
-        .map(|year| Year {
-            name: year.id,
+        .map(|(year, course)| Year {
+            id: year.id,
+            label: year.label,
+            start_date: year.start_date.to_string(),
+            end_date: year.end_date.to_string(),
+            course: course.id,
+            course_name: course.name,
             read_only: !year.writable
         })
         .collect();
@@ -124,6 +449,34 @@ pub fn find_years(conn: &PgConnection) -> Result<Vec<Year>> {
     Ok(years)
 }
 
+/// Human readable title of the term including the name of its course,
+/// e.g. "Hardwarepraktikum Sommersemester 2019"
+pub fn year_title(year: i16, conn: &PgConnection) -> Result<String> {
+    let (label, course_name) = db::years::table
+        .inner_join(db::courses::table)
+        .filter(db::years::id.eq(year))
+        .select((db::years::label, db::courses::name))
+        .get_result::<(String, String)>(conn)?;
+
+    Ok(format!("{} {}", course_name, label))
+}
+
+/// Load the id of the course the term belongs to
+pub fn year_course(year: i16, conn: &PgConnection) -> Result<String> {
+    Ok(db::years::table
+        .find(year)
+        .select(db::years::course)
+        .get_result(conn)?)
+}
+
+/// Fails if the term does not belong to the course given in the url
+pub fn ensure_course(course: &str, year: i16, conn: &PgConnection) -> Result<()> {
+    match year_course(year, conn)? == course {
+        true => Ok(()),
+        false => Err(format!("Term {} does not belong to course {}", year, course).into()),
+    }
+}
+
 pub fn find_writable_year(group: i32, conn: &PgConnection) -> ApiResult<i16> {
     match db::groups::table
         .inner_join(db::days::table
@@ -138,6 +491,83 @@ pub fn find_writable_year(group: i32, conn: &PgConnection) -> ApiResult<i16> {
     }
 }
 
+/// Ensure that the desk exists in the room of the day and is not taken by
+/// another group of the day
+///
+/// Groups that ended ("(ENDE)" in their comment) do not take their desk.
+pub fn ensure_free_desk(day: i32, desk: i32, group: Option<i32>, conn: &PgConnection) -> ApiResult<()> {
+    let layout: Option<String> = db::days::table
+        .left_join(db::rooms::table)
+        .filter(db::days::id.eq(day))
+        .select(db::rooms::layout.nullable())
+        .get_result(conn)?;
+    let layout = layout.and_then(|layout| Layout::parse(&layout).ok()).unwrap_or_default();
+    if !layout.rows.is_empty() && !layout.contains(desk) {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    let mut query = db::groups::table
+        .filter(db::groups::day_id.eq(day))
+        .filter(db::groups::desk.eq(desk))
+        .filter(diesel::dsl::not(db::groups::comment.like("%(ENDE)%")))
+        .into_boxed();
+    if let Some(group) = group {
+        query = query.filter(db::groups::id.ne(group));
+    }
+    let taken: i64 = query.count().get_result(conn)?;
+    match taken {
+        0 => Ok(()),
+        _ => Err(ApiError::ConstraintViolation),
+    }
+}
+
+/// Load the ids of all days of the given year supervised by the given tutor
+pub fn find_supervised_days(year: i16, username: &str, conn: &PgConnection) -> Result<HashSet<i32>> {
+    let days = db::day_tutors::table
+        .inner_join(db::tutors::table)
+        .filter(db::tutors::year.eq(year))
+        .filter(db::tutors::username.eq(username))
+        .select(db::day_tutors::day_id)
+        .load::<i32>(conn)?;
+
+    Ok(days.into_iter().collect())
+}
+
+/// Whether the user may change completions and elaborations on the given day
+///
+/// Only tutors assigned to the day may do so, users who can manage the year
+/// are exempt.
+pub fn supervises_day(user: &User, year: i16, day: i32, conn: &PgConnection) -> Result<bool> {
+    if user.has_permission(Permission::Manage, year) {
+        return Ok(true);
+    }
+
+    let count: i64 = db::day_tutors::table
+        .inner_join(db::tutors::table)
+        .filter(db::day_tutors::day_id.eq(day))
+        .filter(db::tutors::username.eq(user.name()))
+        .count()
+        .get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+/// Ensure that the user supervises the day of the group
+///
+/// Should be run inside a transaction.
+pub fn ensure_group_tutor(user: &User, year: i16, group: i32, conn: &PgConnection) -> Result<()> {
+    let day: i32 = db::groups::table
+        .find(group)
+        .select(db::groups::day_id)
+        .get_result(conn)?;
+
+    match supervises_day(user, year, day, conn)? {
+        true => Ok(()),
+        false => Err(format!("User {} does not supervise day #{} of group {}",
+            user.name(), day, group).into()),
+    }
+}
+
 pub fn is_writable_year(year: i16, conn: &PgConnection) -> Result<bool> {
     let count: i64 = db::years::table
         .filter(db::years::id.eq(year))
@@ -148,8 +578,11 @@ pub fn is_writable_year(year: i16, conn: &PgConnection) -> Result<bool> {
     Ok(count > 0)
 }
 
-pub fn find_events(year: i16, conn: &PgConnection) -> Result<Vec<Experiment>> {
+/// Load all events of the given year grouped by experiment and mark those on
+/// the given supervised days
+pub fn find_events(year: i16, supervised_days: &HashSet<i32>, conn: &PgConnection) -> Result<Vec<Experiment>> {
     let writable_year = is_writable_year(year, conn)?;
+    let course = year_course(year, conn)?;
     let days_this_year = db::days::table
         .filter(db::days::year.eq(year))
         .select(db::days::id)
@@ -157,19 +590,25 @@ pub fn find_events(year: i16, conn: &PgConnection) -> Result<Vec<Experiment>> {
 
     let events = db::events::table
         .filter(db::events::day_id.eq_any(days_this_year))
-        .order((db::events::experiment_id.asc(), db::events::date.asc()))
         .inner_join(db::days::table)
         .inner_join(db::experiments::table)
+        .order((db::experiments::position.asc(), db::experiments::name.asc(),
+                db::events::experiment_id.asc(), db::events::date.asc()))
         .load::<(db::Event, db::Day, db::Experiment)>(conn)?
         .into_iter().map(|(event, day, experiment)| Event {
+            course: course.clone(),
             year: year,
             read_only: !writable_year,
             date: format!("{}", event.date),
+            supervised: supervised_days.contains(&day.id),
             day_id: day.id,
             day: day.name,
             experiment_id: experiment.id,
             experiment: experiment.name,
             groups: vec![],
+            days: vec![],
+            seat_map: None,
+            instructions: None,
             prev_event: None,
             next_event: None,
             push: PushEndpoint::default(),
@@ -195,14 +634,15 @@ pub fn find_events(year: i16, conn: &PgConnection) -> Result<Vec<Experiment>> {
     Ok(result)
 }
 
-pub fn find_event_day_by_date(date: &NaiveDate, conn: &PgConnection) -> Result<String> {
-    use db::{days, events};
+/// Find the course, term and name of the day of the only event at the date
+pub fn find_event_day_by_date(date: &NaiveDate, conn: &PgConnection) -> Result<(String, i16, String)> {
+    use db::{days, events, years};
 
     let mut events = events::table
-        .inner_join(db::days::table)
+        .inner_join(days::table.inner_join(years::table))
         .filter(events::date.eq(date))
-        .select(days::name)
-        .load::<(String)>(conn)?;
+        .select((years::course, days::year, days::name))
+        .load::<(String, i16, String)>(conn)?;
 
     match events.len() {
         0 => Err("No event found".into()),
@@ -211,7 +651,8 @@ pub fn find_event_day_by_date(date: &NaiveDate, conn: &PgConnection) -> Result<S
     }
 }
 
-pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnection) -> Result<Event> {
+/// Load the event of the given day of the term at the given date
+pub fn load_event(date: &NaiveDate, day: &str, year: i16, push_url: &str, conn: &PgConnection) -> Result<Event> {
     use db::{completions, days, elaborations, events, groups, tasks};
 
     let (event, day, experiment) = events::table
@@ -219,10 +660,12 @@ pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnecti
         .inner_join(db::experiments::table)
         .filter(events::date.eq(date))
         .filter(days::name.eq(day))
+        .filter(days::year.eq(year))
         .first::<(db::Event, db::Day, db::Experiment)>(conn)?;
+    let course = year_course(day.year, conn)?;
 
     let tasks = tasks::table.filter(tasks::experiment_id.eq(&event.experiment_id))
-        .order(tasks::name.asc()).load::<db::Task>(conn)?;
+        .order((tasks::position.asc(), tasks::name.asc())).load::<db::Task>(conn)?;
     let groups = groups::table
         .filter(groups::day_id.eq(&event.day_id))
         .order((groups::comment.like("%(ENDE)%".to_string()).asc(), groups::desk.asc()))
@@ -240,12 +683,12 @@ pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnecti
         .filter(elaborations::group_id.eq_any(&group_ids))
         .load::<db::Elaboration>(conn)?;
 
-    // build set with all groups that completed a task and a map for the status
-    // of the elaboration of a specific group
-    let completions: HashSet<_> = completions.into_iter()
-        .map(|c| (c.group_id, c.task_id)).collect();
-    let elaborations: HashMap<_,_> = elaborations.into_iter()
-        .map(|e| (e.group_id, (e.rework_required, e.accepted))).collect();
+    // build map with the partial credit of all groups that completed a task and
+    // a map for the status of the elaboration of a specific group
+    let completions: HashMap<_,_> = completions.into_iter()
+        .map(|c| ((c.group_id, c.task_id), c.points)).collect();
+    let mut elaborations: HashMap<_,_> = elaborations.into_iter()
+        .map(|e| (e.group_id, e)).collect();
 
     let mut web_groups = vec![];
 
@@ -259,14 +702,18 @@ pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnecti
                 instructed: s.instructed,
             }).collect(),
             tasks: Vec::with_capacity(tasks.len()),
-            elaboration: elaborations.get(&group.id).cloned(),
+            score: Score::default(),
+            elaboration: elaborations.get(&group.id)
+                .map(|e| (e.rework_required, e.accepted)),
+            grading: elaborations.remove(&group.id).map(Grading::from),
             disqualified: group.comment.contains("(ENDE)"),
             comment: group.comment,
         };
 
         for task in &tasks {
-            let completed = completions.contains(&(group.id, task.id));
-            web_group.tasks.push((task.id, task.name.clone(), completed));
+            let completion = completions.get(&(group.id, task.id)).cloned();
+            web_group.tasks.push(Task::new(task, completion));
+            web_group.score.add(task, completion);
         }
 
         web_groups.push(web_group)
@@ -285,18 +732,35 @@ pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnecti
         .filter(events::date.gt(&event.date))
         .order(events::date.asc())
         .first(conn).optional()?;
+    let days = db::days::table
+        .filter(db::days::year.eq(day.year))
+        .order(db::days::name.asc())
+        .load::<db::Day>(conn)?
+        .into_iter()
+        .map(|day| Day {
+            id: day.id,
+            name: day.name,
+        })
+        .collect();
+    let seat_map = load_seat_map(&day, &web_groups, conn)?;
+    let instructions = load_instructions(&experiment, &tasks, conn)?;
 
     Ok(Event {
         year: day.year,
         read_only: !is_writable_year(day.year, conn)?,
+        supervised: false,
         date: format!("{}", date),
         day_id: day.id,
         day: day.name,
         experiment_id: experiment.id,
         experiment: experiment.name,
         groups: web_groups,
-        prev_event: prev_event.map(|(e, d)| format!("{}/{}", e.date, d.name)),
-        next_event: next_event.map(|(e, d)| format!("{}/{}", e.date, d.name)),
+        days: days,
+        seat_map: seat_map,
+        instructions: instructions,
+        prev_event: prev_event.map(|(e, d)| format!("{}/{}/{}/{}", course, d.year, e.date, d.name)),
+        next_event: next_event.map(|(e, d)| format!("{}/{}/{}/{}", course, d.year, e.date, d.name)),
+        course: course,
         push: PushEndpoint {
             url: push_url.into(),
             auth_token: push::SERVER.generate_auth_token(Some(day.year))?,
@@ -305,7 +769,7 @@ pub fn load_event(date: &NaiveDate, day: &str, push_url: &str, conn: &PgConnecti
 }
 
 pub fn load_group(group: i32, push_url: &str, conn: &PgConnection) -> Result<GroupOverview> {
-    use db::{completions, elaborations, groups, tasks};
+    use db::{completions, elaboration_submissions, elaborations, groups, tasks};
 
     let (group, day) = groups::table
         .inner_join(db::days::table)
@@ -317,9 +781,10 @@ pub fn load_group(group: i32, push_url: &str, conn: &PgConnection) -> Result<Gro
     let tasks: Vec<(_, Vec<_>)> = tasks::table
         .inner_join(db::experiments::table)
         .filter(db::experiments::year.eq(day.year))
-        .order((db::experiments::name.asc(), tasks::name.asc()))
+        .order((db::experiments::position.asc(), db::experiments::name.asc(),
+                tasks::position.asc(), tasks::name.asc()))
         .load::<(db::Task, db::Experiment)>(conn)?.into_iter()
-        .group_by(|&(_, ref experiment)| experiment.name.clone()).into_iter()
+        .group_by(|&(_, ref experiment)| experiment.id).into_iter()
         .map(|(_, grouped_values)| {
             let (tasks, mut experiments): (Vec<_>, Vec<_>) = grouped_values.unzip();
             let experiment = experiments.pop().expect("all groups are non-empty");
@@ -328,22 +793,34 @@ pub fn load_group(group: i32, push_url: &str, conn: &PgConnection) -> Result<Gro
         .collect();
 
     // Load all completions and elaborations for the group
-    let completions: HashSet<_> = completions::table
+    let completions: HashMap<_,_> = completions::table
         .filter(completions::group_id.eq(group.id))
         .load::<db::Completion>(conn)?.into_iter()
-        .map(|c| c.task_id)
+        .map(|c| (c.task_id, c.points))
         .collect();
-    let elaborations: HashMap<_,_> = elaborations::table
+    let mut elaborations: HashMap<_,_> = elaborations::table
         .filter(elaborations::group_id.eq(group.id))
         .load::<db::Elaboration>(conn)?.into_iter()
-        .map(|e| (e.experiment_id, (e.rework_required, e.accepted)))
+        .map(|e| (e.experiment_id, e))
         .collect();
+    let mut submissions: HashMap<_, Vec<_>> = HashMap::new();
+    for submission in elaboration_submissions::table
+        .filter(elaboration_submissions::group_id.eq(group.id))
+        .order(elaboration_submissions::id.desc())
+        .load::<db::ElaborationSubmission>(conn)? {
+        submissions.entry(submission.experiment_id)
+            .or_default()
+            .push(Submission::from(submission));
+    }
+    let mut files = load_elaboration_files(group.id, conn)?;
 
     let events = tasks.into_iter().map(|(experiment, tasks)| {
         // Check which tasks the the group has completed
-        let tasks = tasks.into_iter().map(|task| {
-            let completed = completions.contains(&task.id);
-            (task.id, task.name, completed)
+        let mut score = Score::default();
+        let tasks = tasks.iter().map(|task| {
+            let completion = completions.get(&task.id).cloned();
+            score.add(task, completion);
+            Task::new(task, completion)
         }).collect();
 
         GroupOverviewEvent {
@@ -351,7 +828,12 @@ pub fn load_group(group: i32, push_url: &str, conn: &PgConnection) -> Result<Gro
                 id: group.id,
                 disqualified: disqualified,
                 tasks: tasks,
-                elaboration: elaborations.get(&experiment.id).cloned(),
+                score: score,
+                elaboration: elaborations.get(&experiment.id)
+                    .map(|e| (e.rework_required, e.accepted)),
+                grading: elaborations.remove(&experiment.id).map(Grading::from),
+                submissions: submissions.remove(&experiment.id).unwrap_or_default(),
+                files: files.remove(&experiment.id).unwrap_or_default(),
             },
             experiment_id: experiment.id,
             experiment: experiment.name,
@@ -371,12 +853,14 @@ pub fn load_group(group: i32, push_url: &str, conn: &PgConnection) -> Result<Gro
     Ok(GroupOverview {
         id: group.id,
         desk: group.desk,
+        course: year_course(day.year, conn)?,
         year: day.year,
         read_only: !is_writable_year(day.year, conn)?,
         day: day.name,
         comment: group.comment,
         students: students,
         events: events,
+        uploads: false,
         push: PushEndpoint {
             url: push_url.into(),
             auth_token: push::SERVER.generate_auth_token(Some(day.year))?,
@@ -466,7 +950,7 @@ pub fn find_groups<T: AsRef<str>>(terms: &[T], year: i16, conn: &PgConnection) -
     Ok(search_groups)
 }
 
-fn load_students_for_groups(groups: Vec<db::Group>, conn: &PgConnection) -> Result<Vec<(db::Group, Vec<db::Student>)>> {
+pub fn load_students_for_groups(groups: Vec<db::Group>, conn: &PgConnection) -> Result<Vec<(db::Group, Vec<db::Student>)>> {
     use db::students;
 
     let mappings = db::GroupMapping::belonging_to(&groups)
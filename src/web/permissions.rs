@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+/// Single action a user may perform within a term
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Permission {
+    /// Read events, groups and analyses
+    View,
+    /// Mark tasks as completed
+    Completions,
+    /// Record and grade elaborations
+    Elaborations,
+    /// Create groups and change their members, desk and comment
+    Groups,
+    /// Manage students and import them
+    Students,
+    /// Manage experiments, tasks, days and events as well as the trash
+    Manage,
+}
+
+/// Named set of permissions that can be assigned to a tutor of a term
+///
+/// Roles are stored by their id in the `tutors` table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    Observer,
+    Grader,
+    EventTutor,
+    StudentManager,
+    Tutor,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 6] = [Role::Observer, Role::Grader, Role::EventTutor,
+                                Role::StudentManager, Role::Tutor, Role::Admin];
+
+    pub fn from_id(id: &str) -> Option<Role> {
+        Role::ALL.iter().cloned().find(|role| role.id() == id)
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Role::Observer => "observer",
+            Role::Grader => "grader",
+            Role::EventTutor => "event-tutor",
+            Role::StudentManager => "student-manager",
+            Role::Tutor => "tutor",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// German name shown in the user interface
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Observer => "Beobachter (nur lesen)",
+            Role::Grader => "Korrektor",
+            Role::EventTutor => "Terminbetreuer",
+            Role::StudentManager => "Teilnehmerverwaltung",
+            Role::Tutor => "Betreuer",
+            Role::Admin => "Verwalter",
+        }
+    }
+
+    pub fn permissions(self) -> HashSet<Permission> {
+        use self::Permission::*;
+
+        let permissions: &[Permission] = match self {
+            Role::Observer => &[View],
+            Role::Grader => &[View, Elaborations],
+            Role::EventTutor => &[View, Completions, Groups],
+            Role::StudentManager => &[View, Students],
+            Role::Tutor => &[View, Completions, Elaborations, Groups],
+            Role::Admin => &[View, Completions, Elaborations, Groups, Students, Manage],
+        };
+
+        permissions.iter().cloned().collect()
+    }
+}
@@ -0,0 +1,184 @@
+use crate::db;
+use crate::errors::*;
+use crate::web::{analysis, api};
+use crate::web::models;
+use crate::web::session::User;
+use crate::web::upload::{self, Uploads};
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use rocket::{Data, State};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize)]
+struct Context {
+    name: String,
+    uploads: bool,
+    records: Vec<Record>,
+}
+
+#[derive(Serialize)]
+struct Record {
+    year: i16,
+    title: String,
+    read_only: bool,
+    matrikel: String,
+    instructed: bool,
+    passed: bool,
+    groups: Vec<Group>,
+}
+
+#[derive(Serialize)]
+struct Group {
+    id: i32,
+    desk: i32,
+    day: String,
+    students: Vec<String>,
+    experiments: Vec<Experiment>,
+}
+
+#[derive(Serialize)]
+struct Experiment {
+    id: i32,
+    name: String,
+    date: Option<String>,
+    tasks: Vec<(String, bool)>,
+    elaboration: Option<(bool, bool)>,
+    grading: Option<models::Grading>,
+    files: Vec<models::ElaborationFile>,
+}
+
+/// Read-only overview for students about their own progress
+#[get("/")]
+pub fn index(uploads: State<Uploads>, conn: db::Conn, user: User) -> Result<Template> {
+    let mut records = user.student_ids().iter()
+        .map(|&student| load_record(student, &conn))
+        .collect::<Result<Vec<_>>>()?;
+    records.sort_by(|a, b| b.year.cmp(&a.year));
+
+    let context = Context {
+        name: user.name().into(),
+        uploads: uploads.is_enabled(),
+        records: records,
+    };
+
+    Ok(Template::render("portal", &context))
+}
+
+fn load_record(student: i32, conn: &PgConnection) -> Result<Record> {
+    use db::{completions, elaborations, events, experiments, group_mappings, groups, tasks};
+
+    let student = db::students::table
+        .find(student)
+        .get_result::<db::Student>(conn)?;
+
+    let passed = analysis::load_passed_students(student.year, analysis::PassCondition::Elaborations, conn)?
+        .iter()
+        .any(|passed| passed.id == student.id);
+
+    let experiments = experiments::table
+        .filter(experiments::year.eq(student.year))
+        .order((experiments::position.asc(), experiments::name.asc()))
+        .load::<db::Experiment>(conn)?;
+    let tasks = db::Task::belonging_to(&experiments)
+        .order((tasks::position.asc(), tasks::name.asc()))
+        .load::<db::Task>(conn)?
+        .grouped_by(&experiments);
+
+    let student_groups = groups::table
+        .inner_join(db::days::table)
+        .filter(groups::id.eq_any(group_mappings::table
+            .filter(group_mappings::student_id.eq(student.id))
+            .select(group_mappings::group_id)))
+        .order(groups::id.asc())
+        .load::<(db::Group, db::Day)>(conn)?;
+
+    let mut web_groups = Vec::with_capacity(student_groups.len());
+    for (group, day) in student_groups {
+        let completions: HashSet<_> = completions::table
+            .filter(completions::group_id.eq(group.id))
+            .select(completions::task_id)
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+        let mut elaborations: HashMap<_,_> = elaborations::table
+            .filter(elaborations::group_id.eq(group.id))
+            .load::<db::Elaboration>(conn)?
+            .into_iter()
+            .map(|e| (e.experiment_id, e))
+            .collect();
+        let mut files = models::load_elaboration_files(group.id, conn)?;
+        let dates: HashMap<_,_> = events::table
+            .filter(events::day_id.eq(day.id))
+            .load::<db::Event>(conn)?
+            .into_iter()
+            .map(|e| (e.experiment_id, e.date.format("%Y-%m-%d").to_string()))
+            .collect();
+
+        let students = db::students::table
+            .inner_join(group_mappings::table)
+            .filter(group_mappings::group_id.eq(group.id))
+            .order((db::students::given_name.asc(), db::students::family_name.asc()))
+            .select(db::students::all_columns)
+            .load::<db::Student>(conn)?
+            .into_iter()
+            .map(|s| s.name())
+            .collect();
+
+        let web_experiments = experiments.iter()
+            .zip(&tasks)
+            .map(|(experiment, tasks)| Experiment {
+                id: experiment.id,
+                name: experiment.name.clone(),
+                date: dates.get(&experiment.id).cloned(),
+                tasks: tasks.iter()
+                    .map(|task| (task.name.clone(), completions.contains(&task.id)))
+                    .collect(),
+                elaboration: elaborations.get(&experiment.id)
+                    .map(|e| (e.rework_required, e.accepted)),
+                grading: elaborations.remove(&experiment.id).map(models::Grading::from),
+                files: files.remove(&experiment.id).unwrap_or_default(),
+            })
+            .collect();
+
+        web_groups.push(Group {
+            id: group.id,
+            desk: group.desk,
+            day: day.name,
+            students: students,
+            experiments: web_experiments,
+        });
+    }
+
+    Ok(Record {
+        year: student.year,
+        title: models::year_title(student.year, conn)?,
+        read_only: !models::is_writable_year(student.year, conn)?,
+        matrikel: student.matrikel,
+        instructed: student.instructed,
+        passed: passed,
+        groups: web_groups,
+    })
+}
+
+/// Upload an elaboration for one of the own groups
+#[post("/group/<group>/elaboration/<experiment>/file?<filename>", data = "<file>")]
+pub fn post_elaboration_file(group: i32, experiment: i32, filename: String, file: Data, uploads: State<Uploads>, conn: db::Conn, user: User) -> ApiResult<Json<i32>> {
+    // The file is only read for members of the group
+    let year = models::find_writable_year(group, &conn)?;
+    if !upload::is_group_member(&user, group, &conn)? {
+        return Err(ApiError::Other(format!("User {} is not a member of group {}",
+            user.name(), group).into()));
+    }
+
+    let data = uploads.read(file.open())?
+        .map_err(ApiError::Invalid)?;
+    let filename = upload::sanitize_filename(&filename);
+
+    conn.transaction(|| {
+        let file = api::store_elaboration_file(year, group, experiment, &filename, &data, &uploads, user.name(), &conn)?;
+
+        Ok(Json(file))
+    })
+}
@@ -33,6 +33,7 @@ pub struct Completion {
     pub group: i32,
     pub task: i32,
     pub completed: bool,
+    pub points: Option<i16>,
 }
 
 #[derive(Serialize)]
@@ -49,6 +50,7 @@ pub struct Elaboration {
 pub enum Group {
     New { day: i32 },
     Change { group: i32 },
+    Move { group: i32, from: i32, to: i32 },
 }
 
 #[derive(Serialize)]
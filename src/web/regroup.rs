@@ -0,0 +1,327 @@
+use crate::db;
+use crate::errors::{ApiError, ApiResult};
+use crate::web::models::ensure_free_desk;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use std::collections::{HashMap, HashSet};
+
+/// Changes of a merge or split, which are returned as preview for dry runs
+#[derive(Debug, Default, Serialize)]
+pub struct Preview {
+    /// Students of the merged group or of the new group of a split
+    pub students: Vec<String>,
+    /// Completions taken over from the merged group or copied to the new group
+    pub completions: usize,
+    /// Elaborations taken over from the merged group or copied to the new group
+    pub elaborations: usize,
+    /// Progress recorded for both merged groups and which one was kept
+    pub conflicts: Vec<Conflict>,
+    /// Uploaded elaboration files, which are taken over on merges but stay
+    /// with the group on splits
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictKind {
+    Completion,
+    Elaboration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    /// Name of the task or experiment
+    pub name: String,
+    /// Group whose progress is kept
+    pub kept: i32,
+}
+
+// Fully completed tasks (without points) are better than any partial credit
+fn credit(completion: &db::Completion) -> i16 {
+    completion.points.unwrap_or(i16::max_value())
+}
+
+// Accepted elaborations are better than handed in ones, which are better than
+// those that require rework, and more points are better than less
+fn rank(elaboration: &db::Elaboration) -> (bool, bool, Option<i16>) {
+    (elaboration.accepted, !elaboration.rework_required, elaboration.points)
+}
+
+fn load_students(group: i32, conn: &PgConnection) -> ApiResult<Vec<db::Student>> {
+    Ok(db::group_mappings::table
+        .inner_join(db::students::table)
+        .filter(db::group_mappings::group_id.eq(group))
+        .select(db::students::all_columns)
+        .order(db::students::id.asc())
+        .load::<db::Student>(conn)?)
+}
+
+fn load_completions(group: i32, conn: &PgConnection) -> ApiResult<Vec<(db::Completion, String)>> {
+    Ok(db::completions::table
+        .inner_join(db::tasks::table)
+        .filter(db::completions::group_id.eq(group))
+        .select((db::completions::all_columns, db::tasks::name))
+        .load(conn)?)
+}
+
+fn load_elaborations(group: i32, conn: &PgConnection) -> ApiResult<Vec<(db::Elaboration, String)>> {
+    Ok(db::elaborations::table
+        .inner_join(db::experiments::table)
+        .filter(db::elaborations::group_id.eq(group))
+        .select((db::elaborations::all_columns, db::experiments::name))
+        .load(conn)?)
+}
+
+/// Merge the other group into the group, which then contains the students of
+/// both groups and the best progress of both for every task and experiment
+///
+/// The history and files of elaborations are kept and the other group is
+/// deleted. Should be run inside a transaction.
+pub fn merge(group: i32, other: i32, dry_run: bool, conn: &PgConnection) -> ApiResult<Preview> {
+    let group_comment = db::groups::table
+        .find(group)
+        .select(db::groups::comment)
+        .get_result::<String>(conn)?;
+    let other_comment = db::groups::table
+        .find(other)
+        .select(db::groups::comment)
+        .get_result::<String>(conn)?;
+
+    let students = load_students(group, conn)?;
+    let other_students = load_students(other, conn)?;
+    let members: HashSet<_> = students.iter().map(|s| s.id).collect();
+
+    let mut preview = Preview::default();
+    preview.students = students.iter()
+        .chain(other_students.iter().filter(|s| !members.contains(&s.id)))
+        .map(db::Student::name)
+        .collect();
+
+    let completions: HashMap<_, _> = load_completions(group, conn)?.into_iter()
+        .map(|(completion, _)| (completion.task_id, completion))
+        .collect();
+    let mut taken_completions = vec![];
+    for (completion, task) in load_completions(other, conn)? {
+        match completions.get(&completion.task_id) {
+            None => taken_completions.push(completion),
+            Some(existing) => {
+                let kept = if credit(&completion) > credit(existing) { other } else { group };
+                preview.conflicts.push(Conflict {
+                    kind: ConflictKind::Completion,
+                    name: task,
+                    kept: kept,
+                });
+                if kept == other {
+                    taken_completions.push(completion);
+                }
+            }
+        }
+    }
+    preview.completions = taken_completions.len();
+
+    let elaborations: HashMap<_, _> = load_elaborations(group, conn)?.into_iter()
+        .map(|(elaboration, _)| (elaboration.experiment_id, elaboration))
+        .collect();
+    let mut taken_elaborations = vec![];
+    let mut dropped_elaborations = vec![];
+    for (elaboration, experiment) in load_elaborations(other, conn)? {
+        match elaborations.get(&elaboration.experiment_id) {
+            None => taken_elaborations.push(elaboration.experiment_id),
+            Some(existing) => {
+                let kept = if rank(&elaboration) > rank(existing) { other } else { group };
+                preview.conflicts.push(Conflict {
+                    kind: ConflictKind::Elaboration,
+                    name: experiment,
+                    kept: kept,
+                });
+                if kept == other {
+                    dropped_elaborations.push(elaboration.experiment_id);
+                    taken_elaborations.push(elaboration.experiment_id);
+                }
+            }
+        }
+    }
+    preview.elaborations = taken_elaborations.len();
+    preview.files = db::elaboration_files::table
+        .filter(db::elaboration_files::group_id.eq(other))
+        .count()
+        .get_result::<i64>(conn)? as usize;
+
+    if dry_run {
+        return Ok(preview);
+    }
+
+    for completion in taken_completions {
+        diesel::insert_into(db::completions::table)
+            .values(&db::Completion {
+                group_id: group,
+                ..completion
+            })
+            .on_conflict((db::completions::group_id, db::completions::task_id))
+                .do_update()
+                .set(db::completions::points.eq(completion.points))
+            .execute(conn)?;
+    }
+
+    diesel::delete(db::elaborations::table
+        .filter(db::elaborations::group_id.eq(group))
+        .filter(db::elaborations::experiment_id.eq_any(&dropped_elaborations)))
+        .execute(conn)?;
+    diesel::update(db::elaborations::table
+        .filter(db::elaborations::group_id.eq(other))
+        .filter(db::elaborations::experiment_id.eq_any(&taken_elaborations)))
+        .set(db::elaborations::group_id.eq(group))
+        .execute(conn)?;
+
+    // The history of both groups is kept, versions of files are continued
+    diesel::update(db::elaboration_submissions::table
+        .filter(db::elaboration_submissions::group_id.eq(other)))
+        .set(db::elaboration_submissions::group_id.eq(group))
+        .execute(conn)?;
+    let experiments: Vec<i32> = db::elaboration_files::table
+        .filter(db::elaboration_files::group_id.eq(other))
+        .select(db::elaboration_files::experiment_id)
+        .distinct()
+        .load(conn)?;
+    for experiment in experiments {
+        let last_version: Option<i32> = db::elaboration_files::table
+            .filter(db::elaboration_files::group_id.eq(group))
+            .filter(db::elaboration_files::experiment_id.eq(experiment))
+            .select(diesel::dsl::max(db::elaboration_files::version))
+            .get_result(conn)?;
+
+        diesel::update(db::elaboration_files::table
+            .filter(db::elaboration_files::group_id.eq(other))
+            .filter(db::elaboration_files::experiment_id.eq(experiment)))
+            .set((
+                db::elaboration_files::group_id.eq(group),
+                db::elaboration_files::version.eq(db::elaboration_files::version + last_version.unwrap_or(0)),
+            ))
+            .execute(conn)?;
+    }
+
+    for student in other_students.iter().filter(|s| !members.contains(&s.id)) {
+        diesel::insert_into(db::group_mappings::table)
+            .values(&db::GroupMapping {
+                student_id: student.id,
+                group_id: group,
+            })
+            .execute(conn)?;
+    }
+
+    if !other_comment.trim().is_empty() {
+        let comment = match group_comment.trim().is_empty() {
+            true => other_comment,
+            false => format!("{}\n{}", group_comment, other_comment),
+        };
+        diesel::update(db::groups::table.find(group))
+            .set(db::groups::comment.eq(comment))
+            .execute(conn)?;
+    }
+
+    db::delete_group(other, conn)?;
+
+    Ok(preview)
+}
+
+/// Split the given students off into a new group at the desk on the same day,
+/// which gets a copy of the progress of the group
+///
+/// Uploaded elaboration files stay with the group. Returns the id of the new
+/// group, which is None for dry runs. Should be run inside a transaction.
+pub fn split(group: i32, students: &[i32], desk: i32, dry_run: bool, conn: &PgConnection) -> ApiResult<(Preview, Option<i32>)> {
+    let full_group = db::groups::table
+        .find(group)
+        .get_result::<db::Group>(conn)?;
+
+    // At least one student has to stay in the group
+    let members = load_students(group, conn)?;
+    let split_off: Vec<_> = members.iter()
+        .filter(|s| students.contains(&s.id))
+        .collect();
+    if split_off.is_empty() || split_off.len() != students.len() || split_off.len() == members.len() {
+        return Err(ApiError::ConstraintViolation);
+    }
+
+    ensure_free_desk(full_group.day_id, desk, None, conn)?;
+
+    let completions = db::completions::table
+        .filter(db::completions::group_id.eq(group))
+        .load::<db::Completion>(conn)?;
+    let elaborations = db::elaborations::table
+        .filter(db::elaborations::group_id.eq(group))
+        .load::<db::Elaboration>(conn)?;
+    let files: i64 = db::elaboration_files::table
+        .filter(db::elaboration_files::group_id.eq(group))
+        .count()
+        .get_result(conn)?;
+
+    let preview = Preview {
+        students: split_off.iter().map(|s| s.name()).collect(),
+        completions: completions.len(),
+        elaborations: elaborations.len(),
+        conflicts: vec![],
+        files: files as usize,
+    };
+
+    if dry_run {
+        return Ok((preview, None));
+    }
+
+    let new_group: i32 = diesel::insert_into(db::groups::table)
+        .values(&db::NewGroup {
+            desk: desk,
+            day_id: full_group.day_id,
+            comment: full_group.comment.clone(),
+        })
+        .returning(db::groups::id)
+        .get_result(conn)?;
+
+    let completions: Vec<_> = completions.into_iter()
+        .map(|completion| db::Completion {
+            group_id: new_group,
+            ..completion
+        })
+        .collect();
+    diesel::insert_into(db::completions::table)
+        .values(&completions)
+        .execute(conn)?;
+
+    let elaborations: Vec<_> = elaborations.into_iter()
+        .map(|elaboration| db::Elaboration {
+            group_id: new_group,
+            ..elaboration
+        })
+        .collect();
+    diesel::insert_into(db::elaborations::table)
+        .values(&elaborations)
+        .execute(conn)?;
+
+    let submissions = db::elaboration_submissions::table
+        .filter(db::elaboration_submissions::group_id.eq(group))
+        .load::<db::ElaborationSubmission>(conn)?;
+    for submission in submissions {
+        diesel::insert_into(db::elaboration_submissions::table)
+            .values((
+                db::elaboration_submissions::group_id.eq(new_group),
+                db::elaboration_submissions::experiment_id.eq(submission.experiment_id),
+                db::elaboration_submissions::created_at.eq(submission.created_at),
+                db::elaboration_submissions::rework_required.eq(submission.rework_required),
+                db::elaboration_submissions::accepted.eq(submission.accepted),
+                db::elaboration_submissions::points.eq(submission.points),
+                db::elaboration_submissions::grade.eq(submission.grade),
+                db::elaboration_submissions::feedback.eq(submission.feedback),
+                db::elaboration_submissions::grader.eq(submission.grader),
+            ))
+            .execute(conn)?;
+    }
+
+    diesel::update(db::group_mappings::table
+        .filter(db::group_mappings::group_id.eq(group))
+        .filter(db::group_mappings::student_id.eq_any(students)))
+        .set(db::group_mappings::group_id.eq(new_group))
+        .execute(conn)?;
+
+    Ok((preview, Some(new_group)))
+}
@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+/// Desks of a room arranged in rows, gaps between desks are None
+#[derive(Debug, Default)]
+pub struct Layout {
+    pub rows: Vec<Vec<Option<i32>>>,
+}
+
+impl Layout {
+    /// Parse a layout with one line per row, desk numbers separated by spaces
+    /// and dots for gaps
+    ///
+    /// Returns an error message for the user if a desk is invalid or used
+    /// more than once.
+    pub fn parse(layout: &str) -> Result<Layout, String> {
+        let mut desks = HashSet::new();
+        let mut rows = vec![];
+
+        for line in layout.lines().filter(|line| !line.trim().is_empty()) {
+            let mut row = vec![];
+            for field in line.split_whitespace() {
+                if field.chars().all(|c| c == '.') {
+                    row.extend((0..field.len()).map(|_| None));
+                    continue;
+                }
+
+                let desk = match field.parse::<i32>() {
+                    Ok(desk) if desk > 0 => desk,
+                    _ => return Err(format!("Ungültige Tischnummer '{}'", field)),
+                };
+                if !desks.insert(desk) {
+                    return Err(format!("Tisch {} ist mehrfach enthalten", desk));
+                }
+                row.push(Some(desk));
+            }
+            rows.push(row);
+        }
+
+        Ok(Layout {
+            rows: rows,
+        })
+    }
+
+    pub fn contains(&self, desk: i32) -> bool {
+        self.rows.iter().any(|row| row.contains(&Some(desk)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+
+    #[test]
+    fn parse_rows_with_gaps() {
+        let layout = Layout::parse("1 2 .. 3\n\n  4 . 5  \n").unwrap();
+        assert_eq!(layout.rows, vec![
+            vec![Some(1), Some(2), None, None, Some(3)],
+            vec![Some(4), None, Some(5)],
+        ]);
+        assert!(layout.contains(5));
+        assert!(!layout.contains(6));
+    }
+
+    #[test]
+    fn parse_empty_layout() {
+        assert!(Layout::parse("").unwrap().rows.is_empty());
+        assert!(Layout::parse(" \n\t\n").unwrap().rows.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_desks() {
+        assert_eq!(Layout::parse("1 a").unwrap_err(), "Ungültige Tischnummer 'a'");
+        assert_eq!(Layout::parse("0").unwrap_err(), "Ungültige Tischnummer '0'");
+        assert_eq!(Layout::parse("1 .2").unwrap_err(), "Ungültige Tischnummer '.2'");
+        assert_eq!(Layout::parse("1 2\n3 1").unwrap_err(), "Tisch 1 ist mehrfach enthalten");
+    }
+}
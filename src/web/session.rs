@@ -1,7 +1,9 @@
 use crate::db::{self, PgInetExpressionMethods};
 use crate::errors::{self, ResultExt};
 use crate::user;
+use crate::web::permissions::{Permission, Role};
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use rocket::{Config, Outcome, State};
 use rocket::http::{Cookie, Cookies, Status};
 use rocket::http::uri::{Origin, Uri};
@@ -13,35 +15,50 @@ use std::net::SocketAddr;
 use std::ops::Deref;
 use std::path::PathBuf;
 
-pub struct SiteAdmins(HashSet<String>);
-
-impl SiteAdmins {
-    pub fn new<'a, I: IntoIterator<Item = &'a str>>(users: I) -> Self {
-        SiteAdmins(users.into_iter().map(|s| s.to_string()).collect())
+/// Add the site admins from the configuration if there are none yet
+///
+/// Site admins are managed in the admin interface afterwards, so the
+/// configured list is only needed for the first start.
+pub fn bootstrap_site_admins(config: &Config, conn: &PgConnection) -> errors::Result<()> {
+    let num_site_admins: i64 = db::site_admins::table
+        .count()
+        .get_result(conn)?;
+    if num_site_admins > 0 {
+        return Ok(());
     }
-}
 
-pub fn load_site_admins(config: &Config) -> errors::Result<SiteAdmins> {
-    let site_admins = config
+    let site_admins: Vec<_> = config
         .get_slice("site_admins")
         .chain_err(|| "No site_admins configured.")?
         .iter()
-        .filter_map(|u| u.as_str());
-    let site_admins = SiteAdmins::new(site_admins);
+        .filter_map(|u| u.as_str())
+        .map(|username| db::site_admins::username.eq(username))
+        .collect();
 
-    if site_admins.0.is_empty() {
+    if site_admins.is_empty() {
         return Err("You must configure at least one site_admin.".into())
     }
 
-    Ok(site_admins)
+    diesel::insert_into(db::site_admins::table)
+        .values(&site_admins)
+        .execute(conn)?;
+
+    Ok(())
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct User {
     name: String,
     site_admin: bool,
-    tutor_years: HashSet<i16>,
-    admin_years: HashSet<i16>,
+    // Loaded from the tutors on every request instead of the session cookie
+    #[serde(skip)]
+    permissions: HashMap<i16, HashSet<Permission>>,
+    #[serde(default)]
+    student_ids: HashSet<i32>,
+    #[serde(default)]
+    admin_courses: HashSet<String>,
+    #[serde(default, alias = "course_admin_years")]
+    course_admin_terms: HashSet<i16>,
 }
 
 impl User {
@@ -49,40 +66,131 @@ impl User {
         &self.name
     }
 
+    /// Tutors have at least one role in the term
     pub fn is_tutor_for(&self, year: i16) -> bool {
-        self.site_admin || self.tutor_years.contains(&year)
+        self.is_course_admin_for(year) || self.permissions.contains_key(&year)
+    }
+
+    /// Course admins have all permissions in the terms of their courses
+    pub fn has_permission(&self, permission: Permission, year: i16) -> bool {
+        self.is_course_admin_for(year) || self.permissions.get(&year)
+            .map_or(false, |permissions| permissions.contains(&permission))
     }
 
+    /// Admin pages are shown to everyone who can manage anything
     pub fn is_admin_for(&self, year: i16) -> bool {
-        self.site_admin || self.admin_years.contains(&year)
+        self.has_permission(Permission::Manage, year)
+            || self.has_permission(Permission::Students, year)
     }
 
     pub fn is_site_admin(&self) -> bool {
         self.site_admin
     }
 
-    pub fn ensure_tutor_for(&self, year: i16) -> errors::Result<()> {
-        match self.is_tutor_for(year) {
+    /// Course admins can manage all terms of their course like site admins
+    pub fn is_course_admin(&self, course: &str) -> bool {
+        self.site_admin || self.admin_courses.contains(course)
+    }
+
+    pub fn is_course_admin_for(&self, year: i16) -> bool {
+        self.site_admin || self.course_admin_terms.contains(&year)
+    }
+
+    /// Students only have read access to their own records
+    pub fn is_student_only(&self) -> bool {
+        !self.site_admin && self.permissions.is_empty()
+            && self.admin_courses.is_empty() && !self.student_ids.is_empty()
+    }
+
+    pub fn student_ids(&self) -> &HashSet<i32> {
+        &self.student_ids
+    }
+
+    pub fn ensure_permission(&self, permission: Permission, year: i16) -> errors::Result<()> {
+        match self.has_permission(permission, year) {
             true => Ok(()),
-            false => Err(format!("User {} is not a tutor for {}",
-                self.name(), year).into()),
+            false => Err(format!("User {} lacks permission {:?} for term {}",
+                self.name(), permission, year).into()),
         }
     }
 
     pub fn ensure_admin_for(&self, year: i16) -> errors::Result<()> {
         match self.is_admin_for(year) {
             true => Ok(()),
-            false => Err(format!("User {} is not an admin for {}",
+            false => Err(format!("User {} is not an admin for term {}",
+                self.name(), year).into()),
+        }
+    }
+
+    pub fn ensure_course_admin(&self, course: &str) -> errors::Result<()> {
+        match self.is_course_admin(course) {
+            true => Ok(()),
+            false => Err(format!("User {} is not an admin for course {}",
+                self.name(), course).into()),
+        }
+    }
+
+    pub fn ensure_course_admin_for(&self, year: i16) -> errors::Result<()> {
+        match self.is_course_admin_for(year) {
+            true => Ok(()),
+            false => Err(format!("User {} is not a course admin for term {}",
                 self.name(), year).into()),
         }
     }
+
+    // Site admin status is checked against the database on every request, so
+    // removed site admins lose their rights immediately
+    fn load_site_admin(&mut self, conn: &PgConnection) -> errors::Result<()> {
+        let num_site_admins: i64 = db::site_admins::table
+            .filter(db::site_admins::username.eq(&self.name))
+            .count()
+            .get_result(conn)?;
+        self.site_admin = num_site_admins > 0;
+
+        Ok(())
+    }
+
+    // Permissions are loaded from the roles of the tutor on every request, so
+    // changed roles and removed tutors take effect immediately
+    fn load_permissions(&mut self, conn: &PgConnection) -> errors::Result<()> {
+        self.permissions.clear();
+        for tutor in db::tutors::table
+            .filter(db::tutors::username.eq(&self.name))
+            .load::<db::Tutor>(conn)? {
+            let role = Role::from_id(&tutor.role).unwrap_or(Role::Observer);
+            self.permissions.entry(tutor.year)
+                .or_insert_with(HashSet::new)
+                .extend(role.permissions());
+        }
+
+        Ok(())
+    }
+
+    // Course admin rights are loaded from the database on every request, so
+    // they include terms created after the login and end when revoked
+    fn load_course_admin_rights(&mut self, conn: &PgConnection) -> errors::Result<()> {
+        self.admin_courses = db::course_admins::table
+            .filter(db::course_admins::username.eq(&self.name))
+            .select(db::course_admins::course)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        self.course_admin_terms = db::years::table
+            .filter(db::years::course.eq_any(&self.admin_courses))
+            .select(db::years::id)
+            .load::<i16>(conn)?
+            .into_iter()
+            .collect();
+
+        Ok(())
+    }
 }
 
 fn load_user(cookies: &mut Cookies) -> request::Outcome<User, ()> {
     let user = cookies
         .get_private("user")
         .and_then(|cookie| {
-            serde_json::from_str(cookie.value()).ok()
+            serde_json::from_str::<User>(cookie.value()).ok()
         });
 
     match user {
@@ -94,7 +202,27 @@ fn load_user(cookies: &mut Cookies) -> request::Outcome<User, ()> {
 impl<'a, 'r> FromRequest<'a, 'r> for User {
     type Error = ();
     fn from_request(request: &'a Request<'r>) -> request::Outcome<User, ()> {
-        load_user(&mut request.cookies())
+        let mut user = match load_user(&mut request.cookies()) {
+            Outcome::Success(user) => user,
+            Outcome::Forward(()) => return Outcome::Forward(()),
+            Outcome::Failure(error) => return Outcome::Failure(error),
+        };
+
+        let conn = match request.guard::<db::Conn>() {
+            Outcome::Success(conn) => conn,
+            Outcome::Forward(()) => return Outcome::Forward(()),
+            Outcome::Failure(error) => return Outcome::Failure(error),
+        };
+        let loaded = user.load_site_admin(&conn)
+            .and_then(|()| match user.site_admin {
+                true => Ok(()),
+                false => user.load_permissions(&conn)
+                    .and_then(|()| user.load_course_admin_rights(&conn)),
+            });
+        match loaded {
+            Ok(()) => Outcome::Success(user),
+            Err(_) => Outcome::Failure((Status::InternalServerError, ())),
+        }
     }
 }
 
@@ -109,7 +237,7 @@ impl Deref for SiteAdmin {
 impl<'a, 'r> FromRequest<'a, 'r> for SiteAdmin {
     type Error = ();
     fn from_request(request: &'a Request<'r>) -> request::Outcome<SiteAdmin, ()> {
-        match load_user(&mut request.cookies()) {
+        match request.guard::<User>() {
             Outcome::Success(user) => {
                 if user.site_admin {
                     Outcome::Success(SiteAdmin(user))
@@ -182,44 +310,59 @@ pub struct LoginForm {
 
 #[post("/login", data = "<login>")]
 pub fn post_login(mut cookies: Cookies, login: Form<LoginForm>,
-        site_admins: State<SiteAdmins>, ip_whitelisting: State<IpWhitelisting>,
+        ip_whitelisting: State<IpWhitelisting>,
         address: SocketAddr, conn: db::Conn)
         -> errors::Result<Result<Redirect, Flash<Redirect>>> {
     let login = login.into_inner();
     let redirect = Uri::percent_decode_lossy(login.redirect.as_bytes());
 
+    let num_site_admins: i64 = db::site_admins::table
+        .filter(db::site_admins::username.eq(&login.username))
+        .count()
+        .get_result(&*conn)?;
+
     let mut user = User {
-        site_admin: site_admins.0.contains(&login.username),
+        site_admin: num_site_admins > 0,
         name: login.username,
-        tutor_years: HashSet::new(),
-        admin_years: HashSet::new(),
+        permissions: HashMap::new(),
+        student_ids: HashSet::new(),
+        admin_courses: HashSet::new(),
+        course_admin_terms: HashSet::new(),
     };
 
     if !user.site_admin {
-        db::tutors::table
-            .filter(db::tutors::username.eq(&user.name))
-            .load::<db::Tutor>(&*conn)?
-            .iter()
-            .for_each(|tutor| {
-                user.tutor_years.insert(tutor.year);
-                if tutor.is_admin {
-                    user.admin_years.insert(tutor.year);
-                }
-            });
+        user.load_permissions(&conn)?;
+
+        // Course admins are admins for all terms of their courses
+        user.load_course_admin_rights(&conn)?;
     }
 
-    if !user.site_admin && user.tutor_years.is_empty() {
+    // Students can log in to view their own records in the portal
+    user.student_ids = db::students::table
+        .filter(db::students::username.eq(&user.name))
+        .select(db::students::id)
+        .load::<i32>(&*conn)?
+        .into_iter()
+        .collect();
+
+    if !user.site_admin && user.permissions.is_empty() && user.admin_courses.is_empty()
+            && user.student_ids.is_empty() {
         let msg = "Ungültiger Benutzername!";
         return Ok(Err(Flash::error(redirect_to_login(&redirect), msg)))
     }
 
-    // Site admins can always login from any ip address
-    if ip_whitelisting.0 && !user.site_admin {
+    // Site admins can always login from any ip address and students are
+    // not restricted, because they cannot change anything
+    let tutor_years: Vec<_> = user.permissions.keys()
+        .chain(&user.course_admin_terms)
+        .cloned()
+        .collect();
+    if ip_whitelisting.0 && !user.site_admin && !tutor_years.is_empty() {
         let ip = db::to_inet(address.ip().to_string());
 
         let containing_nets: i64 = db::ip_whitelist::table
             .filter(db::ip_whitelist::ipnet.contains_or_equals(ip))
-            .filter(db::ip_whitelist::year.eq_any(&user.tutor_years))
+            .filter(db::ip_whitelist::year.eq_any(tutor_years))
             .count()
             .get_result(&*conn)?;
 
@@ -0,0 +1,305 @@
+use crate::db;
+use crate::errors::*;
+use crate::web::session::User;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Default maximum size of uploaded elaborations in MiB
+const DEFAULT_SIZE_LIMIT: u64 = 20;
+
+// Files younger than this may belong to a transaction that has not been
+// committed yet, so they are not purged
+const PURGE_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+// Attached files are stored with this prefix and their id
+const ATTACHMENT_PREFIX: &str = "attachment-";
+
+// Content types of attached files identified by their first bytes
+const ATTACHMENT_TYPES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG", "image/png"),
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+];
+
+/// Storage for uploaded elaborations on the local disk
+///
+/// Uploads are disabled if no `upload_directory` is configured.
+#[derive(Clone)]
+pub struct Uploads {
+    directory: Option<PathBuf>,
+    size_limit: u64,
+}
+
+/// Read the upload configuration from `Rocket.toml` and create the directory
+pub fn config(config: &rocket::Config) -> Result<Uploads> {
+    let size_limit = match config.get_int("upload_size_limit_mb") {
+        Ok(limit) if limit > 0 => limit as u64,
+        Ok(limit) => return Err(format!("Invalid upload_size_limit_mb {}", limit).into()),
+        Err(_) => DEFAULT_SIZE_LIMIT,
+    };
+
+    let directory = match config.get_str("upload_directory") {
+        Ok(directory) => {
+            fs::create_dir_all(directory)
+                .chain_err(|| format!("Could not create upload directory {}", directory))?;
+            Some(directory.into())
+        }
+        Err(_) => None,
+    };
+
+    Ok(Uploads {
+        directory: directory,
+        size_limit: size_limit * 1024 * 1024,
+    })
+}
+
+impl Uploads {
+    pub fn is_enabled(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    fn path(&self, file: i32) -> Result<PathBuf> {
+        match self.directory {
+            Some(ref directory) => Ok(directory.join(format!("{}.pdf", file))),
+            None => Err("Uploads are disabled".into()),
+        }
+    }
+
+    // Read the uploaded file up to the size limit
+    fn read_limited<R: Read>(&self, reader: R) -> Result<::std::result::Result<Vec<u8>, String>> {
+        let mut data = Vec::new();
+        reader.take(self.size_limit + 1).read_to_end(&mut data)
+            .chain_err(|| "Could not read uploaded file")?;
+
+        if data.len() as u64 > self.size_limit {
+            return Ok(Err(format!("Die Datei ist größer als {} MiB.",
+                self.size_limit / 1024 / 1024)));
+        }
+
+        Ok(Ok(data))
+    }
+
+    /// Read and check an uploaded elaboration
+    ///
+    /// Returns an error message for the user if the file is too large or not
+    /// a pdf file.
+    pub fn read<R: Read>(&self, reader: R) -> Result<::std::result::Result<Vec<u8>, String>> {
+        let data = match self.read_limited(reader)? {
+            Ok(data) => data,
+            Err(message) => return Ok(Err(message)),
+        };
+        if !data.starts_with(b"%PDF-") {
+            return Ok(Err("Es können nur PDF-Dateien hochgeladen werden.".into()));
+        }
+
+        Ok(Ok(data))
+    }
+
+    /// Read and check a file attached to an experiment or task
+    ///
+    /// Returns the content type or an error message for the user if the file
+    /// is too large or neither an image nor a pdf file.
+    pub fn read_attachment<R: Read>(&self, reader: R) -> Result<::std::result::Result<(Vec<u8>, &'static str), String>> {
+        let data = match self.read_limited(reader)? {
+            Ok(data) => data,
+            Err(message) => return Ok(Err(message)),
+        };
+
+        match ATTACHMENT_TYPES.iter().find(|(magic, _)| data.starts_with(magic)) {
+            Some(&(_, content_type)) => Ok(Ok((data, content_type))),
+            None => Ok(Err("Es können nur Bilder und PDF-Dateien angehängt werden.".into())),
+        }
+    }
+
+    /// Store the file of the attachment and return the name to reference it
+    pub fn store_attachment(&self, attachment: i32, data: &[u8]) -> Result<String> {
+        let name = format!("{}{}", ATTACHMENT_PREFIX, attachment);
+        let path = self.attachment_path(&name)?;
+        File::create(&path)
+            .and_then(|mut output| output.write_all(data))
+            .chain_err(|| format!("Could not write attached file {}", path.display()))?;
+
+        Ok(name)
+    }
+
+    /// Load the content of the attached file
+    pub fn load_attachment(&self, name: &str) -> Result<Vec<u8>> {
+        let path = self.attachment_path(name)?;
+        fs::read(&path)
+            .chain_err(|| format!("Could not read attached file {}", path.display()))
+    }
+
+    fn attachment_path(&self, name: &str) -> Result<PathBuf> {
+        // Names are generated by store_attachment, but never trust the database
+        if !name.starts_with(ATTACHMENT_PREFIX) || name.contains(|c: char| c == '/' || c == '\\') {
+            return Err(format!("Invalid attachment {}", name).into());
+        }
+
+        match self.directory {
+            Some(ref directory) => Ok(directory.join(name)),
+            None => Err("Uploads are disabled".into()),
+        }
+    }
+
+    /// Store the file as new version of the elaboration of the group
+    ///
+    /// Should be run inside a transaction. Files of failed transactions are
+    /// removed from disk by a later `purge`.
+    pub fn store(&self, group: i32, experiment: i32, filename: &str, data: &[u8], uploader: &str, conn: &PgConnection) -> Result<db::ElaborationFile> {
+        use db::elaboration_files;
+
+        let version: Option<i32> = elaboration_files::table
+            .filter(elaboration_files::group_id.eq(group))
+            .filter(elaboration_files::experiment_id.eq(experiment))
+            .select(diesel::dsl::max(elaboration_files::version))
+            .get_result(conn)?;
+
+        let file = diesel::insert_into(elaboration_files::table)
+            .values(&db::NewElaborationFile {
+                group_id: group,
+                experiment_id: experiment,
+                version: version.unwrap_or(0) + 1,
+                filename: filename,
+                size: data.len() as i32,
+                uploader: uploader,
+            })
+            .get_result::<db::ElaborationFile>(conn)?;
+
+        let path = self.path(file.id)?;
+        File::create(&path)
+            .and_then(|mut output| output.write_all(data))
+            .chain_err(|| format!("Could not write uploaded file {}", path.display()))?;
+
+        Ok(file)
+    }
+
+    /// Load the content of the stored file
+    pub fn load(&self, file: i32) -> Result<Vec<u8>> {
+        let path = self.path(file)?;
+        fs::read(&path)
+            .chain_err(|| format!("Could not read uploaded file {}", path.display()))
+    }
+
+    /// Remove all files and attachments from disk that are neither referenced
+    /// in the database nor in the trash anymore
+    ///
+    /// Files written within the last hour are kept, because the transaction
+    /// storing them may not be visible yet.
+    pub fn purge(&self, conn: &PgConnection) -> Result<usize> {
+        let directory = match self.directory {
+            Some(ref directory) => directory,
+            None => return Ok(0),
+        };
+
+        let mut referenced: HashSet<i32> = db::elaboration_files::table
+            .select(db::elaboration_files::id)
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+        referenced.extend(db::trashed_file_ids(conn)?);
+
+        let mut attachments: HashSet<String> = db::attachments::table
+            .filter(db::attachments::file.is_not_null())
+            .select(db::attachments::file)
+            .load::<Option<String>>(conn)?
+            .into_iter()
+            .flatten()
+            .collect();
+        attachments.extend(db::trashed_attachment_files(conn)?);
+
+        let mut purged = 0;
+        for entry in fs::read_dir(directory).chain_err(|| "Could not read upload directory")? {
+            let entry = entry.chain_err(|| "Could not read upload directory")?;
+            let recent = entry.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .map_or(true, |age| age < PURGE_GRACE_PERIOD);
+            if recent {
+                continue;
+            }
+
+            let path = entry.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let unreferenced = if name.starts_with(ATTACHMENT_PREFIX) {
+                !attachments.contains(name)
+            } else {
+                let id = path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<i32>().ok());
+                id.map_or(false, |id| !referenced.contains(&id))
+            };
+
+            if unreferenced {
+                fs::remove_file(&path)
+                    .chain_err(|| format!("Could not remove {}", path.display()))?;
+                purged += 1;
+            }
+        }
+
+        Ok(purged)
+    }
+}
+
+/// Whether the user is one of the students of the group
+pub fn is_group_member(user: &User, group: i32, conn: &PgConnection) -> Result<bool> {
+    let student_ids: Vec<_> = user.student_ids().iter().cloned().collect();
+    let count: i64 = db::group_mappings::table
+        .filter(db::group_mappings::group_id.eq(group))
+        .filter(db::group_mappings::student_id.eq_any(student_ids))
+        .count()
+        .get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+/// Reduce an uploaded filename to a safe name for downloads
+pub fn sanitize_filename(filename: &str) -> String {
+    let name: String = filename.rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(100)
+        .collect();
+
+    match name.trim() {
+        "" => "ausarbeitung.pdf".into(),
+        name => name.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sanitize_filename;
+
+    #[test]
+    fn sanitize_paths() {
+        assert_eq!(sanitize_filename("Versuch 1.pdf"), "Versuch 1.pdf");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("C:\\Users\\Anna\\Versuch 1.pdf"), "Versuch 1.pdf");
+    }
+
+    #[test]
+    fn sanitize_characters() {
+        assert_eq!(sanitize_filename("a\"b\r\nc.pdf"), "abc.pdf");
+        assert_eq!(sanitize_filename(&"a".repeat(150)), "a".repeat(100));
+    }
+
+    #[test]
+    fn sanitize_empty_names() {
+        assert_eq!(sanitize_filename(""), "ausarbeitung.pdf");
+        assert_eq!(sanitize_filename("dir/"), "ausarbeitung.pdf");
+        assert_eq!(sanitize_filename(" \t "), "ausarbeitung.pdf");
+    }
+}
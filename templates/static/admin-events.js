@@ -5,10 +5,32 @@ document.addEventListener("DOMContentLoaded", () => {
         input.addEventListener("change", onChangeDate);
     }
 
+    for(let input of document.querySelectorAll(".tutor input.assigned")) {
+        input.addEventListener("change", onChangeTutor);
+    }
+
     for(let day of document.querySelectorAll(".day h2")) {
         day.addEventListener("click", onDeleteDay);
     }
 
+    for(let rename of document.querySelectorAll(".day img.rename")) {
+        rename.addEventListener("click", onRenameDay);
+    }
+
+    for(let select of document.querySelectorAll(".day select.room")) {
+        select.dataset.prev_value = select.value;
+        select.addEventListener("change", onChangeRoom);
+    }
+
+    for(let room of document.querySelectorAll(".room.box")) {
+        room.querySelector("img.rename").addEventListener("click", onRenameRoom);
+        room.querySelector("button.save").addEventListener("click", onSaveLayout);
+        room.querySelector("button.delete").addEventListener("click", onDeleteRoom);
+    }
+
+    document.querySelector("#add-room")
+        .addEventListener("click", onNewRoom);
+
     document.querySelector("#add-day")
         .addEventListener("click", onNewDay);
 });
@@ -63,6 +85,25 @@ async function onChangeDate(event) {
     target.classList.remove("dirty");
 }
 
+async function onChangeTutor(event) {
+    let target = event.target;
+
+    let tutor = target.closest(".tutor").dataset.id;
+    let day = target.closest(".day").dataset.id;
+
+    try {
+        let url = "/api/day/" + day + "/tutor/" + tutor;
+
+        let response = await myfetch(url, {
+            method: target.checked ? "PUT" : "DELETE"
+        });
+        handleResponse(response);
+    } catch(e) {
+        toast("error", e);
+        target.checked = !target.checked;
+    }
+}
+
 async function onDeleteDay(event) {
     let target = event.target;
 
@@ -70,13 +111,13 @@ async function onDeleteDay(event) {
     let dayId = day.dataset.id;
     let dayName = day.querySelector("h2").textContent;
 
-    if(!confirm(dayName + " wirklich löschen?")) {
-        return;
-    }
-
     try {
         let url = "/api/day/" + dayId;
 
+        if(!await confirmDeletion(url, dayName)) {
+            return;
+        }
+
         let response = await myfetch(url, {
             method: "DELETE"
         });
@@ -88,6 +129,21 @@ async function onDeleteDay(event) {
     }
 }
 
+async function onRenameDay(event) {
+    let day = event.target.closest(".day");
+    let heading = day.querySelector("h2");
+
+    try {
+        let name = await promptRename("/api/day/" + day.dataset.id, heading.textContent,
+            "Neuer Name des Versuchstages " + heading.textContent + ":");
+        if(name !== null) {
+            heading.textContent = name;
+        }
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function onNewDay() {
     let day = prompt("Name des neuen Versuchstages (z. B. Di-A):");
     if(day === null || day.trim() === "") {
@@ -115,3 +171,118 @@ async function onNewDay() {
         toast("error", e);
     }
 }
+
+async function onChangeRoom(event) {
+    let target = event.target;
+    let day = target.closest(".day").dataset.id;
+    let room = target.value === "" ? null : parseInt(target.value);
+
+    try {
+        let url = "/api/day/" + day + "/room";
+
+        let response = await myfetch(url, {
+            method: "PUT",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify(room)
+        });
+        handleResponse(response);
+
+        target.dataset.prev_value = target.value;
+    } catch(e) {
+        toast("error", e);
+        target.value = target.dataset.prev_value;
+    }
+}
+
+async function onNewRoom() {
+    let room = prompt("Name des neuen Raumes (z. B. E 2.12):");
+    if(room === null || room.trim() === "") {
+        return;
+    }
+
+    let year = parseInt(document.body.dataset.year);
+
+    try {
+        let url = "/api/room";
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({
+                name: room.trim(),
+                year: year
+            })
+        });
+        handleResponse(response, {
+            422: "Der Name ist bereits vergeben."
+        });
+
+        // reload to avoid rendering complex structures on the client
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onRenameRoom(event) {
+    let room = event.target.closest(".room");
+    let heading = room.querySelector("h3");
+
+    try {
+        let name = await promptRename("/api/room/" + room.dataset.id, heading.textContent,
+            "Neuer Name des Raumes " + heading.textContent + ":");
+        if(name === null) {
+            return;
+        }
+
+        heading.textContent = name;
+        for(let option of document.querySelectorAll(`select.room > option[value="${room.dataset.id}"]`)) {
+            option.textContent = name;
+        }
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onSaveLayout(event) {
+    let room = event.target.closest(".room");
+
+    try {
+        let url = "/api/room/" + room.dataset.id;
+
+        let response = await myfetch(url, {
+            method: "PATCH",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({
+                layout: room.querySelector("textarea.layout").value
+            })
+        });
+        handleResponse(response, {
+            422: "Der Tischplan enthält ungültige oder doppelte Tischnummern."
+        });
+
+        toast("info", "Der Tischplan wurde gespeichert.");
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onDeleteRoom(event) {
+    let room = event.target.closest(".room");
+    let name = room.querySelector("h3").textContent;
+    if(!confirm("Raum " + name + " wirklich entfernen? Die Versuchstage in diesem Raum haben danach keinen Raum mehr.")) {
+        return;
+    }
+
+    try {
+        let response = await myfetch("/api/room/" + room.dataset.id, {
+            method: "DELETE"
+        });
+        handleResponse(response);
+
+        // reload to update the rooms of all days
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
@@ -7,13 +7,121 @@ document.addEventListener("DOMContentLoaded", () => {
         experiment.addEventListener("click", onDeleteExperiment);
     }
 
+    for(let rename of document.querySelectorAll(".experiment > img.rename")) {
+        rename.addEventListener("click", onRenameExperiment);
+    }
+
     document.querySelector("#add-experiment")
         .addEventListener("click", onNewExperiment);
+
+    for(let material of document.querySelectorAll("div.material")) {
+        material.querySelector(".save-description").addEventListener("click", onSaveDescription);
+        material.querySelector(".add-link").addEventListener("click", onAddLink);
+        material.querySelector("ul.attachments").addEventListener("click", onDeleteAttachment);
+
+        let file = material.querySelector("input[type=file]");
+        if(file !== null) {
+            file.addEventListener("change", onAttachFile);
+        }
+    }
+
+    // Experiments and tasks are reordered using drag and drop
+    document.addEventListener("dragstart", onDragStart);
+    document.addEventListener("dragover", onDragOver);
+    document.addEventListener("drop", (event) => event.preventDefault());
+    document.addEventListener("dragend", onDragEnd);
 });
 
+let dragged = null;
+
+function itemOrder(item) {
+    return Array.from(item.parentNode.children)
+        .filter((child) => child.draggable)
+        .map((child) => parseInt(child.dataset.id));
+}
+
+function onDragStart(event) {
+    if(!event.target.draggable) {
+        return;
+    }
+
+    dragged = {
+        item: event.target,
+        nextSibling: event.target.nextSibling,
+        order: itemOrder(event.target)
+    };
+
+    event.dataTransfer.effectAllowed = "move";
+    event.dataTransfer.setData("text/plain", event.target.dataset.id);
+}
+
+function onDragOver(event) {
+    if(dragged === null) {
+        return;
+    }
+
+    // Only move items between their siblings
+    let target = event.target.closest("[draggable=true]");
+    if(target === null || target === dragged.item
+        || target.parentNode !== dragged.item.parentNode) {
+        return;
+    }
+    event.preventDefault();
+
+    let rect = target.getBoundingClientRect();
+    if(event.clientX < rect.left + rect.width / 2) {
+        target.parentNode.insertBefore(dragged.item, target);
+    } else {
+        target.parentNode.insertBefore(dragged.item, target.nextSibling);
+    }
+}
+
+async function onDragEnd() {
+    if(dragged === null) {
+        return;
+    }
+
+    let {item, nextSibling, order: prevOrder} = dragged;
+    dragged = null;
+
+    let order = itemOrder(item);
+    if(JSON.stringify(order) == JSON.stringify(prevOrder)) {
+        return;
+    }
+
+    let url = null;
+    if(item.classList.contains("experiment")) {
+        url = "/api/experiments/" + document.body.dataset.year + "/order";
+    } else {
+        url = "/api/experiment/" + item.closest(".experiment").dataset.id + "/tasks/order";
+    }
+
+    try {
+        let response = await myfetch(url, {
+            method: "PUT",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify(order)
+        });
+        handleResponse(response);
+    } catch(e) {
+        toast("error", e);
+        item.parentNode.insertBefore(item, nextSibling);
+    }
+}
+
 async function onTaskClick(event) {
     let target = event.target;
 
+    if(target.classList.contains("scoring")) {
+        await onTaskScoring(target.closest("li"));
+        return;
+    }
+
+    if(target.classList.contains("rename")) {
+        await onRenameTask(target.closest("li"));
+        return;
+    }
+
     if(!(target instanceof HTMLLIElement)) {
         return;
     }
@@ -39,34 +147,143 @@ async function onTaskClick(event) {
 
             let id = await response.json();
 
+            // Extra tasks do not count towards the score by default
+            let weight = /^z/i.test(task.trim()) ? 0 : 1;
+
             let node = document.createElement("li");
-            node.textContent = task;
+            node.textContent = task.trim() + " ";
+            node.draggable = true;
             node.dataset.id = id;
-            insertSorted(parent, node, document.createTextNode("\n"));
+            node.dataset.name = task.trim();
+            node.dataset.points = 1;
+            node.dataset.weight = weight;
+
+            let rename = document.createElement("img");
+            rename.classList.add("rename");
+            rename.alt = "Umbenennen";
+            rename.title = "Aufgabe umbenennen";
+            rename.src = "/static/icons/edit.svg";
+            node.appendChild(rename);
+            node.appendChild(document.createTextNode(" "));
+
+            let scoring = document.createElement("span");
+            scoring.classList.add("scoring");
+            scoring.title = "Punkte und Gewichtung ändern";
+            scoring.textContent = `(1 P., ×${weight})`;
+            node.appendChild(scoring);
+
+            // New tasks are appended to the end of the experiment
+            parent.appendChild(document.createTextNode("\n"));
+            parent.appendChild(node);
         } catch(e) {
             toast("error", e);
         }
     } else {
         let taskId = target.dataset.id;
-        let taskName = target.textContent;
+        let taskName = target.dataset.name;
+        let nextSibling = target.nextSibling;
 
-        if(!confirm(taskName + " wirklich löschen?")) {
+        let url = "/api/experiment/" + experiment + "/task/" + taskId;
+        try {
+            if(!await confirmDeletion(url, taskName)) {
+                return;
+            }
+        } catch(e) {
+            toast("error", e);
             return;
         }
 
         parent.removeChild(target);
 
         try {
-            let url = "/api/experiment/" + experiment + "/task/" + taskId;
-
             let response = await myfetch(url, {
                 method: "DELETE"
             });
             handleResponse(response);
         } catch(e) {
             toast("error", e);
-            insertSorted(parent, target, document.createTextNode("\n"));
+            parent.insertBefore(target, nextSibling);
+        }
+    }
+}
+
+async function onTaskScoring(task) {
+    let experiment = task.closest(".experiment").dataset.id;
+
+    let points = prompt("Punkte der Aufgabe " + task.dataset.name + ":", task.dataset.points);
+    if(points === null) {
+        return;
+    }
+    let weight = prompt("Gewichtung der Aufgabe " + task.dataset.name
+        + " (0 bei Zusatzaufgaben, die nicht gewertet werden):", task.dataset.weight);
+    if(weight === null) {
+        return;
+    }
+
+    points = parseInt(points);
+    weight = parseInt(weight);
+    if(isNaN(points) || isNaN(weight) || points <= 0 || weight < 0) {
+        toast("error", "Die Punkte müssen größer als 0 und die Gewichtung mindestens 0 sein.");
+        return;
+    }
+
+    try {
+        let url = "/api/experiment/" + experiment + "/task/" + task.dataset.id + "/scoring";
+
+        let response = await myfetch(url, {
+            method: "PUT",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({
+                points: points,
+                weight: weight
+            })
+        });
+        handleResponse(response);
+
+        task.dataset.points = points;
+        task.dataset.weight = weight;
+        task.querySelector(".scoring").textContent = `(${points} P., ×${weight})`;
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onRenameTask(task) {
+    let experiment = task.closest(".experiment");
+
+    try {
+        let url = "/api/experiment/" + experiment.dataset.id + "/task/" + task.dataset.id;
+        let name = await promptRename(url, task.dataset.name,
+            "Neuer Name der Aufgabe " + task.dataset.name + ":");
+        if(name === null) {
+            return;
+        }
+
+        task.dataset.name = name;
+        task.firstChild.textContent = name + " ";
+
+        for(let heading of experiment.querySelectorAll("details.material h3")) {
+            if(heading.nextElementSibling.dataset.task === task.dataset.id) {
+                heading.textContent = "Aufgabe " + name;
+            }
         }
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onRenameExperiment(event) {
+    let experiment = event.target.closest(".experiment");
+    let heading = experiment.querySelector("h2");
+
+    try {
+        let name = await promptRename("/api/experiment/" + experiment.dataset.id,
+            heading.textContent, "Neuer Name des Versuches " + heading.textContent + ":");
+        if(name !== null) {
+            heading.textContent = name;
+        }
+    } catch(e) {
+        toast("error", e);
     }
 }
 
@@ -77,13 +294,13 @@ async function onDeleteExperiment(event) {
     let experimentId = experiment.dataset.id;
     let experimentName = experiment.querySelector("h2").textContent;
 
-    if(!confirm(experimentName + " wirklich löschen?")) {
-        return;
-    }
-
     try {
         let url = "/api/experiment/" + experimentId;
 
+        if(!await confirmDeletion(url, experimentName)) {
+            return;
+        }
+
         let response = await myfetch(url, {
             method: "DELETE"
         });
@@ -123,27 +340,140 @@ async function onNewExperiment() {
     }
 }
 
-function insertSorted(parent, node, whitespace, compare) {
-    if(compare === undefined) {
-        compare = (left, right) => {
-            return left.textContent.localeCompare(right.textContent);
-        };
+function materialUrl(material) {
+    let experiment = material.closest(".experiment").dataset.id;
+    let url = "/api/experiment/" + experiment;
+    if(material.dataset.task !== "") {
+        url += "/task/" + material.dataset.task;
     }
+    return url;
+}
 
-    // simple linear insertion sort
-    for(var i = 0; i < parent.children.length; ++i) {
-        if(compare(parent.children[i], node) > 0) {
-            parent.insertBefore(node, parent.children[i]);
-            if(whitespace !== undefined) {
-                parent.insertBefore(whitespace, node.nextSibling);
-            }
-            return;
-        }
+function appendAttachment(material, id, title, url) {
+    let node = document.createElement("li");
+    node.dataset.id = id;
+
+    let link = document.createElement("a");
+    link.href = url;
+    link.target = "_blank";
+    link.textContent = title;
+    node.appendChild(link);
+    node.appendChild(document.createTextNode(" "));
+
+    let remove = document.createElement("span");
+    remove.classList.add("delete");
+    remove.title = "Entfernen";
+    remove.textContent = "✕";
+    node.appendChild(remove);
+
+    material.querySelector("ul.attachments").appendChild(node);
+}
+
+async function onSaveDescription(event) {
+    let material = event.target.closest(".material");
+
+    try {
+        let response = await myfetch(materialUrl(material) + "/description", {
+            method: "PUT",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify(material.querySelector("textarea").value)
+        });
+        handleResponse(response);
+
+        toast("info", "Die Beschreibung wurde gespeichert.");
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onAddLink(event) {
+    let material = event.target.closest(".material");
+    let experiment = material.closest(".experiment").dataset.id;
+
+    let title = prompt("Titel des Links:");
+    if(title === null || title.trim() === "") {
+        return;
+    }
+    let url = prompt("Adresse des Links (https://…):");
+    if(url === null || url.trim() === "") {
+        return;
     }
 
-    // larger than all other elements
-    if(whitespace !== undefined) {
-        parent.appendChild(whitespace);
+    let task = material.dataset.task === "" ? null : parseInt(material.dataset.task);
+
+    try {
+        let response = await myfetch("/api/experiment/" + experiment + "/link", {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({
+                task: task,
+                title: title.trim(),
+                url: url.trim()
+            })
+        });
+        handleResponse(response, {
+            422: "Die Adresse muss mit http:// oder https:// beginnen."
+        });
+
+        let id = await response.json();
+        appendAttachment(material, id, title.trim(), url.trim());
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onAttachFile(event) {
+    let file = event.target.files[0];
+    if(file === undefined) {
+        return;
+    }
+
+    let material = event.target.closest(".material");
+    let experiment = material.closest(".experiment").dataset.id;
+
+    let url = "/api/experiment/" + experiment + "/attachment?filename=" + encodeURIComponent(file.name);
+    if(material.dataset.task !== "") {
+        url += "&task=" + material.dataset.task;
+    }
+
+    try {
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": file.type || "application/octet-stream"}),
+            body: file
+        });
+        await handleMessageResponse(response, {
+            422: "Es können nur Bilder und PDF-Dateien bis zur maximalen Größe angehängt werden."
+        });
+
+        let id = await response.json();
+        appendAttachment(material, id, file.name, "/api/attachment/" + id);
+    } catch(e) {
+        toast("error", e);
+    }
+
+    event.target.value = "";
+}
+
+async function onDeleteAttachment(event) {
+    if(!event.target.classList.contains("delete")) {
+        return;
+    }
+
+    let attachment = event.target.closest("li");
+    let title = attachment.querySelector("a").textContent;
+    if(!confirm("Soll " + title + " wirklich entfernt werden?")) {
+        return;
+    }
+
+    try {
+        let response = await myfetch("/api/attachment/" + attachment.dataset.id, {
+            method: "DELETE"
+        });
+        handleResponse(response);
+
+        attachment.parentNode.removeChild(attachment);
+    } catch(e) {
+        toast("error", e);
     }
-    parent.appendChild(node);
 }
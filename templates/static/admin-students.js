@@ -7,11 +7,20 @@ document.addEventListener("DOMContentLoaded", () => {
         .addEventListener("click", showOverlay);
 
     document.querySelector("#students-csv")
-        .addEventListener("change", importStudents);
+        .addEventListener("change", previewImport);
+    document.querySelector("#overlay .upload form.options")
+        .addEventListener("change", previewImport);
+    document.querySelector("#overlay .upload button.confirm")
+        .addEventListener("click", importStudents);
 
     document.querySelector("#add-student")
         .addEventListener("submit", onNewStudent);
 
+    let formGroups = document.querySelector("#form-groups");
+    if(formGroups) {
+        formGroups.addEventListener("submit", onFormGroups);
+    }
+
     for(let removeButton of document.querySelectorAll("table .button.remove")) {
         removeButton.addEventListener("click", onDeleteStudent)
     }
@@ -19,6 +28,13 @@ document.addEventListener("DOMContentLoaded", () => {
     for(let instructedCheckbox of document.querySelectorAll("td.instructed input")) {
         instructedCheckbox.addEventListener("change", onChangeInstructed);
     }
+
+    // Fields of students are edited inline after a double click
+    for(let cell of document.querySelectorAll("td.editable")) {
+        cell.addEventListener("dblclick", onEditStudent);
+        cell.addEventListener("keydown", onEditStudentKey);
+        cell.addEventListener("blur", onSaveStudent);
+    }
 });
 
 function hideOverlay(event) {
@@ -33,11 +49,46 @@ function hideOverlay(event) {
 
 function showOverlay() {
     document.querySelector("#students-csv").value = "";
+    clearPreview();
     document.querySelector("#overlay").classList.add("active");
 }
 
-async function importStudents(event) {
-    let files = event.target.files;
+function importOptions() {
+    let form = document.querySelector("#overlay .upload form.options");
+    let params = new URLSearchParams();
+
+    for(let element of form.elements) {
+        let value = element.value.trim();
+        if(value !== "") {
+            params.append(element.name, value);
+        }
+    }
+
+    return params.toString();
+}
+
+function importContentType(file) {
+    const types = {
+        csv: "text/csv",
+        txt: "text/csv",
+        xlsx: "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ods: "application/vnd.oasis.opendocument.spreadsheet"
+    };
+
+    let extension = file.name.split(".").pop().toLowerCase();
+    return types[extension] || "text/csv";
+}
+
+function clearPreview() {
+    let preview = document.querySelector("#overlay .upload .preview");
+    preview.classList.remove("active");
+    preview.querySelector("ul.errors").innerHTML = "";
+    preview.querySelector("tbody").innerHTML = "";
+    preview.querySelector("button.confirm").disabled = true;
+}
+
+async function previewImport() {
+    let files = document.querySelector("#students-csv").files;
     if(files.length === 0) {
         return;
     }
@@ -45,15 +96,98 @@ async function importStudents(event) {
     let year = parseInt(document.body.dataset.year);
 
     try {
-        let url = "/api/students/" + year;
+        let url = "/api/students/" + year + "/preview?" + importOptions();
 
         let response = await myfetch(url, {
             method: "POST",
-            headers: new Headers({"Content-Type": "text/csv"}),
+            headers: new Headers({"Content-Type": importContentType(files[0])}),
             body: files[0]
         });
         handleResponse(response);
 
+        renderPreview(await response.json());
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+function renderPreview(preview) {
+    clearPreview();
+
+    const actions = {
+        create: "Neu",
+        update: "Ändern",
+        unchanged: "Unverändert",
+        invalid: "Fehlerhaft"
+    };
+
+    let container = document.querySelector("#overlay .upload .preview");
+    let errors = container.querySelector("ul.errors");
+    let tbody = container.querySelector("tbody");
+
+    for(let error of preview.errors) {
+        let node = document.createElement("li");
+        node.textContent = error;
+        errors.appendChild(node);
+    }
+
+    for(let row of preview.rows) {
+        let tr = document.createElement("tr");
+        tr.classList.add(row.action);
+
+        let name = row.row.given_name;
+        if(row.row.family_name) {
+            name += " " + row.row.family_name;
+        }
+
+        let action = actions[row.action];
+        let details = row.errors.concat(row.changes.map(change => {
+            return change.field + ": " + change.old + " → " + change.new;
+        }));
+        if(details.length > 0) {
+            action += " (" + details.join(", ") + ")";
+        }
+
+        for(let text of [row.row.line, row.row.matrikel, name, row.row.username || "", action]) {
+            let td = document.createElement("td");
+            td.textContent = text;
+            tr.appendChild(td);
+        }
+
+        tbody.appendChild(tr);
+    }
+
+    let valid = preview.errors.length === 0
+        && preview.rows.every(row => row.errors.length === 0);
+    container.querySelector("button.confirm").disabled = !valid;
+    container.classList.add("active");
+}
+
+async function importStudents() {
+    let files = document.querySelector("#students-csv").files;
+    if(files.length === 0) {
+        return;
+    }
+
+    let year = parseInt(document.body.dataset.year);
+
+    try {
+        let url = "/api/students/" + year + "?" + importOptions();
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": importContentType(files[0])}),
+            body: files[0]
+        });
+
+        // Show the errors of the rejected file instead of importing it
+        if(response.status === 422) {
+            renderPreview(await response.json());
+            toast("error", "Die Datei enthält fehlerhafte Einträge!");
+            return;
+        }
+        handleResponse(response);
+
         // reload to avoid rendering on the client
         location.reload(true);
     } catch(e) {
@@ -69,9 +203,11 @@ async function onNewStudent(event) {
     let givenName = document.querySelector("#add-student input[name='given-name']").value.trim();
     let familyName = document.querySelector("#add-student input[name='family-name']").value.trim();
     let username = document.querySelector("#add-student input[name='username']").value;
+    let email = document.querySelector("#add-student input[name='email']").value.trim();
+    let partner = document.querySelector("#add-student input[name='partner']").value.trim();
     let year = parseInt(document.body.dataset.year);
 
-    if(matrikel.length == 0 || givenName.length == 0) {
+    if(matrikel.length == 0 || givenName.length == 0 || familyName.length == 0) {
         toast("error", "Ungültige Eingabe");
         return;
     }
@@ -91,6 +227,8 @@ async function onNewStudent(event) {
                 matrikel: matrikel,
                 year: year,
                 username: username,
+                email: email.length == 0 ? null : email,
+                partner: partner.length == 0 ? null : partner,
                 given_name: givenName,
                 family_name: familyName
             })
@@ -104,6 +242,57 @@ async function onNewStudent(event) {
     }
 }
 
+async function onFormGroups(event) {
+    event.preventDefault();
+
+    let form = event.target;
+    let day = form.elements["day"].value;
+    let size = parseInt(form.elements["size"].value, 10);
+    if(isNaN(size) || size < 1) {
+        toast("error", "Ungültige Gruppengröße");
+        return;
+    }
+
+    let url = "/api/day/" + day + "/groups";
+    let request = {
+        method: "POST",
+        headers: new Headers({"Content-Type": "application/json"}),
+        body: JSON.stringify({size: size})
+    };
+    const messages = {
+        422: "Im Raum des Versuchstages sind nicht genügend Tische frei."
+    };
+
+    try {
+        let response = await myfetch(url + "?dry_run=true", request);
+        handleResponse(response, messages);
+
+        let formation = await response.json();
+        if(formation.groups.length === 0) {
+            toast("info", "Alle Teilnehmer sind bereits in Gruppen eingeteilt.");
+            return;
+        }
+
+        let message = formation.groups
+            .map(group => "Tisch " + group.desk + ": " + group.names.join(", "))
+            .join("\n");
+        if(formation.unmatched.length > 0) {
+            message += "\n\nWunschpartner nicht berücksichtigt für: " + formation.unmatched.join(", ");
+        }
+        if(!confirm(formation.groups.length + " Gruppen aus allen Teilnehmern des Semesters ohne Gruppe bilden?\n\n" + message)) {
+            return;
+        }
+
+        response = await myfetch(url, request);
+        handleResponse(response, messages);
+
+        // reload to avoid rendering on the client
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function onDeleteStudent(event) {
     let targetRow = event.target.closest("tr");
 
@@ -111,13 +300,13 @@ async function onDeleteStudent(event) {
     let givenName = targetRow.querySelector("td:nth-of-type(2)").textContent;
     let familyName = targetRow.querySelector("td:nth-of-type(3)").textContent;
 
-    if(!confirm(givenName + " " + familyName + " wirklich löschen?")) {
-        return;
-    }
-
     try {
         let url = "/api/student/" + id;
 
+        if(!await confirmDeletion(url, givenName + " " + familyName)) {
+            return;
+        }
+
         let response = await myfetch(url, {
             method: "DELETE"
         });
@@ -150,3 +339,59 @@ async function onChangeInstructed(event) {
     }
 
 }
+
+function onEditStudent(event) {
+    let cell = event.target;
+    if(cell.isContentEditable) {
+        return;
+    }
+
+    cell.dataset.prev = cell.textContent;
+    cell.contentEditable = true;
+    cell.focus();
+}
+
+function onEditStudentKey(event) {
+    let cell = event.target;
+
+    if(event.key === "Enter") {
+        event.preventDefault();
+        cell.blur();
+    } else if(event.key === "Escape") {
+        cell.textContent = cell.dataset.prev;
+        cell.blur();
+    }
+}
+
+async function onSaveStudent(event) {
+    let cell = event.target;
+    if(!cell.isContentEditable) {
+        return;
+    }
+    cell.contentEditable = false;
+
+    let value = cell.textContent.trim();
+    let prev = cell.dataset.prev;
+    cell.textContent = value;
+    if(value === prev) {
+        return;
+    }
+
+    let id = cell.closest("tr").dataset.id;
+
+    try {
+        let url = "/api/student/" + id;
+
+        let response = await myfetch(url, {
+            method: "PATCH",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({[cell.dataset.field]: value})
+        });
+        handleResponse(response, {
+            422: "Die Änderung ist ungültig oder die Matrikelnummer ist bereits vergeben."
+        });
+    } catch(e) {
+        toast("error", e);
+        cell.textContent = prev;
+    }
+}
@@ -0,0 +1,51 @@
+document.addEventListener("DOMContentLoaded", () => {
+    for(let restoreButton of document.querySelectorAll("table .button.restore")) {
+        restoreButton.addEventListener("click", onRestoreEntry);
+    }
+    for(let removeButton of document.querySelectorAll("table .button.remove")) {
+        removeButton.addEventListener("click", onPurgeEntry);
+    }
+});
+
+async function onRestoreEntry(event) {
+    let id = event.target.closest("tr").dataset.id;
+
+    try {
+        let url = "/api/trash/" + id + "/restored";
+
+        let response = await myfetch(url, {
+            method: "PUT"
+        });
+        handleResponse(response, {
+            422: "Der Eintrag kann nicht wiederhergestellt werden, da der " +
+                 "zugehörige Versuch gelöscht oder eine gleichnamige Aufgabe " +
+                 "angelegt wurde."
+        });
+
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onPurgeEntry(event) {
+    let row = event.target.closest("tr");
+    let name = row.querySelector("td:nth-of-type(3)").textContent;
+
+    if(!confirm(name + " wirklich endgültig löschen?")) {
+        return;
+    }
+
+    try {
+        let url = "/api/trash/" + row.dataset.id;
+
+        let response = await myfetch(url, {
+            method: "DELETE"
+        });
+        handleResponse(response);
+
+        row.parentNode.removeChild(row);
+    } catch(e) {
+        toast("error", e);
+    }
+}
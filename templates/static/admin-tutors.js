@@ -3,6 +3,8 @@ document.addEventListener("DOMContentLoaded", () => {
         .addEventListener("submit", onNewTutor);
     document.querySelector("#add-ip-whitelist")
         .addEventListener("submit", onNewIpWhitelistEntry);
+    document.querySelector("#add-course-admin")
+        .addEventListener("submit", onNewCourseAdmin);
 
     for(let removeButton of document.querySelectorAll("#add-tutor .button.remove")) {
         removeButton.addEventListener("click", onDeleteTutor);
@@ -10,9 +12,21 @@ document.addEventListener("DOMContentLoaded", () => {
     for(let removeButton of document.querySelectorAll("#add-ip-whitelist .button.remove")) {
         removeButton.addEventListener("click", onDeleteIpWhitelistEntry);
     }
+    for(let removeButton of document.querySelectorAll("#add-course-admin .button.remove")) {
+        removeButton.addEventListener("click", onDeleteCourseAdmin);
+    }
+
+    // only shown to site admins
+    let addSiteAdmin = document.querySelector("#add-site-admin");
+    if(addSiteAdmin !== null) {
+        addSiteAdmin.addEventListener("submit", onNewSiteAdmin);
+        for(let removeButton of addSiteAdmin.querySelectorAll(".button.remove")) {
+            removeButton.addEventListener("click", onDeleteSiteAdmin);
+        }
+    }
 
-    for(let adminCheckbox of document.querySelectorAll("#add-tutor input.admin")) {
-        adminCheckbox.addEventListener("change", onChangeAdmin);
+    for(let roleSelect of document.querySelectorAll("#add-tutor select.role")) {
+        roleSelect.addEventListener("change", onChangeRole);
     }
 });
 
@@ -37,7 +51,7 @@ async function onNewTutor(event) {
             body: JSON.stringify({
                 username: username,
                 year: year,
-                is_admin: false
+                role: document.querySelector("#add-tutor select[name='role']").value
             })
         });
         handleResponse(response);
@@ -81,6 +95,116 @@ async function onNewIpWhitelistEntry(event) {
     }
 }
 
+async function onNewCourseAdmin(event) {
+    event.preventDefault();
+
+    let username = document.querySelector("#add-course-admin input[name='username']").value.trim();
+    if(username.length == 0) {
+        toast("error", "Ungültige Eingabe");
+        return;
+    }
+    let year = document.body.dataset.year;
+
+    try {
+        let url = "/api/year/" + year + "/course-admin";
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify(username)
+        });
+        handleResponse(response, {
+            422: "Der Benutzer ist bereits Kursverwalter"
+        });
+
+        // reload to avoid rendering on the client
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onDeleteCourseAdmin(event) {
+    let targetRow = event.target.closest("tr");
+
+    let id = targetRow.dataset.id;
+    let name = targetRow.querySelector("td:nth-of-type(1)").textContent;
+    let year = document.body.dataset.year;
+
+    if(!confirm(name + " wirklich als Kursverwalter entfernen?")) {
+        return;
+    }
+
+    try {
+        let url = "/api/year/" + year + "/course-admin/" + id;
+
+        let response = await myfetch(url, {
+            method: "DELETE"
+        });
+        handleResponse(response);
+
+        targetRow.parentNode.removeChild(targetRow);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onNewSiteAdmin(event) {
+    event.preventDefault();
+
+    let username = document.querySelector("#add-site-admin input[name='username']").value.trim();
+    if(username.length == 0) {
+        toast("error", "Ungültige Eingabe");
+        return;
+    }
+    let year = document.body.dataset.year;
+
+    try {
+        let url = "/api/year/" + year + "/site-admin";
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify(username)
+        });
+        handleResponse(response, {
+            422: "Der Benutzer ist bereits Site-Admin"
+        });
+
+        // reload to avoid rendering on the client
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onDeleteSiteAdmin(event) {
+    let targetRow = event.target.closest("tr");
+
+    let id = targetRow.dataset.id;
+    let name = targetRow.querySelector("td:nth-of-type(1)").textContent;
+    let year = document.body.dataset.year;
+
+    if(!confirm(name + " wirklich als Site-Admin entfernen?")) {
+        return;
+    }
+
+    try {
+        let url = "/api/year/" + year + "/site-admin/" + id;
+
+        let response = await myfetch(url, {
+            method: "DELETE"
+        });
+        handleResponse(response, {
+            422: "Der letzte Site-Admin kann nicht entfernt werden"
+        });
+
+        targetRow.parentNode.removeChild(targetRow);
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function onDeleteTutor(event) {
     let targetRow = event.target.closest("tr");
 
@@ -129,24 +253,26 @@ async function onDeleteIpWhitelistEntry(event) {
     }
 }
 
-async function onChangeAdmin(event) {
+async function onChangeRole(event) {
     let target = event.target;
 
     let id = target.closest("tr").dataset.id;
-    let is_admin = target.checked;
+    let role = target.value;
 
     try {
-        let url = "/api/tutor/" + id + "/is_admin";
+        let url = "/api/tutor/" + id + "/role";
 
         let response = await myfetch(url, {
             method: "PUT",
             headers: new Headers({"Content-Type": "application/json"}),
-            body: JSON.stringify(is_admin)
+            body: JSON.stringify(role)
         });
         handleResponse(response);
+
+        target.dataset.role = role;
     } catch(e) {
         toast("error", e);
-        target.checked = !is_admin;
+        target.value = target.dataset.role;
     }
 
 }
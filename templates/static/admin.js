@@ -6,56 +6,180 @@ document.addEventListener("DOMContentLoaded", () => {
     if(closeYear !== null) {
         closeYear.addEventListener("click", onCloseYear);
     }
+    let restoreArchive = document.querySelector("#restore-archive");
+    if(restoreArchive !== null) {
+        restoreArchive.addEventListener("change", onRestoreArchive);
+    }
+    let anonymizeYear = document.querySelector("#anonymize-year");
+    if(anonymizeYear !== null) {
+        anonymizeYear.addEventListener("click", onAnonymizeYear);
+    }
     let deleteYear = document.querySelector("#delete-year");
     if(deleteYear !== null) {
         deleteYear.addEventListener("click", onDeleteYear);
     }
 });
 
+// Ask for label and duration of a new term, returns null if cancelled
+function promptTerm() {
+    let label = prompt("Bezeichnung des neuen Semesters (z. B. Sommersemester 2020):");
+    if(label === null || label.trim() === "") {
+        return null;
+    }
+
+    let startDate = prompt("Beginn des Semesters (JJJJ-MM-TT):");
+    if(startDate === null) {
+        return null;
+    }
+
+    let endDate = prompt("Ende des Semesters (JJJJ-MM-TT):");
+    if(endDate === null) {
+        return null;
+    }
+
+    return {
+        label: label.trim(),
+        start_date: startDate.trim(),
+        end_date: endDate.trim()
+    };
+}
+
 async function onYearChange(event) {
     let target = event.target;
 
     if(target.value === "new-year") {
-        let year = prompt("Neues Jahr:");
-        if(year === null) {
-            // reset select to currently loaded year
-            target.value = document.body.dataset.year;
+        // reset select to currently loaded term, the new term is opened
+        // after creating it
+        let template = document.body.dataset.year;
+        target.value = document.body.dataset.course + "/" + template;
+
+        let term = promptTerm();
+        if(term === null) {
             return;
         }
 
-        year = parseInt(year);
-        if(isNaN(year)) {
-            toast("error", "Ungültiges Jahr");
-            // reset select to currently loaded year
-            target.value = document.body.dataset.year;
-            return;
+        let url = "/api/course/" + document.body.dataset.course + "/year";
+
+        let copyMessage =
+            "Sollen Versuche, Aufgaben, Tage, Termine, Betreuer und die " +
+            "IP-Whitelist aus dem Semester " + document.body.dataset.yearLabel +
+            " übernommen werden?";
+        if(confirm(copyMessage)) {
+            let weeks = prompt("Termine um wie viele Wochen verschieben?", "52");
+            if(weeks === null) {
+                return;
+            }
+
+            weeks = parseInt(weeks);
+            if(isNaN(weeks)) {
+                toast("error", "Ungültige Anzahl an Wochen");
+                return;
+            }
+
+            url += "?template=" + template + "&shift_weeks=" + weeks;
         }
 
         try {
-            let url = "/api/year/" + year;
-
             let response = await myfetch(url, {
-                method: "PUT"
+                method: "POST",
+                headers: {
+                    "Content-Type": "application/json"
+                },
+                body: JSON.stringify(term)
+            });
+            handleResponse(response, {
+                422: "Ungültige Eingabe, das Semester existiert bereits oder die " +
+                    "Termine lassen sich nicht so weit verschieben"
             });
-            handleResponse(response);
 
-            location = "/admin/" + year;
+            location = "/admin/" + document.body.dataset.course + "/" + await response.json();
         } catch(e) {
             toast("error", e);
         }
+    } else if(target.value === "new-course") {
+        // reset select to currently loaded term, the new course is
+        // opened after creating it
+        target.value = document.body.dataset.course + "/" + document.body.dataset.year;
+
+        let name = prompt("Name des neuen Praktikums:");
+        if(name === null || name.trim() === "") {
+            return;
+        }
+
+        let id = prompt("Kürzel für Adressen (nur Kleinbuchstaben, Ziffern und Bindestriche):");
+        if(id === null) {
+            return;
+        }
+
+        try {
+            let response = await myfetch("/api/course", {
+                method: "POST",
+                headers: {
+                    "Content-Type": "application/json"
+                },
+                body: JSON.stringify({id, name})
+            });
+            handleResponse(response, {
+                422: "Ungültiges Kürzel oder das Praktikum existiert bereits"
+            });
+
+            location = "/admin/" + id + "/" + await response.json();
+        } catch(e) {
+            toast("error", e);
+        }
+    } else if(target.value === "restore-year") {
+        // reset select to currently loaded term, the upload continues
+        // in onRestoreArchive after choosing a file
+        target.value = document.body.dataset.course + "/" + document.body.dataset.year;
+        document.querySelector("#restore-archive").click();
     } else {
         let site = document.body.dataset.site;
         location = "/admin/" + target.value + "/" + site;
     }
 }
 
+async function onRestoreArchive(event) {
+    let file = event.target.files[0];
+    event.target.value = "";
+    if(file === undefined) {
+        return;
+    }
+
+    let label = prompt("Semester, unter dem das Archiv wiederhergestellt werden soll:");
+    if(label === null || label.trim() === "") {
+        return;
+    }
+
+    try {
+        let url = "/api/course/" + document.body.dataset.course +
+            "/year/archive?label=" + encodeURIComponent(label.trim());
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json"
+            },
+            body: file,
+            deadline: 60000
+        });
+        await handleMessageResponse(response, {
+            422: "Das Archiv ist ungültig oder das Semester existiert bereits"
+        });
+
+        location = "/admin/" + document.body.dataset.course + "/" + await response.json();
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function onCloseYear() {
     let year = document.body.dataset.year;
 
     let confirmMessage =
-        "Möchten Sie das Jahr " + year + " wirklich abschließen und damit " +
+        "Möchten Sie das Semester " + document.body.dataset.yearLabel +
+        " wirklich abschließen und damit " +
         "weitere Änderungen unterbinden?\n\n" +
-        "Das Abschließen eines Jahres ist endgültig und sollte erst einige " +
+        "Das Abschließen eines Semesters ist endgültig und sollte erst einige " +
         "Zeit nach Abschluss des Praktikums erfolgen (z. B. nach Übermittlung " +
         "der zugelassenen Studenten an das Prüfungsamt).";
 
@@ -77,12 +201,46 @@ async function onCloseYear() {
     }
 }
 
+async function onAnonymizeYear() {
+    let year = document.body.dataset.year;
+
+    let confirmMessage =
+        "Möchten Sie die Teilnehmer des Semesters " + document.body.dataset.yearLabel +
+        " wirklich anonymisieren?\n\n" +
+        "Dabei werden Namen, Matrikelnummern, Nutzerkennungen und E-Mail-Adressen " +
+        "durch Pseudonyme ersetzt, auch in Kommentaren und im Audit-Log. " +
+        "Hochgeladene Ausarbeitungen werden gelöscht. " +
+        "Die Statistiken über den Fortschritt der Gruppen bleiben erhalten.\n\n" +
+        "Führen Sie vorher den abschließenden Export durch, da dies nicht " +
+        "rückgängig gemacht werden kann!";
+
+    if(!confirm(confirmMessage)) {
+        return;
+    }
+
+    try {
+        let url = "/api/year/" + year + "/anonymized";
+
+        let response = await myfetch(url, {
+            method: "PUT"
+        });
+        handleResponse(response, {
+            422: "Das Semester wurde noch nicht abgeschlossen oder ist bereits anonymisiert"
+        });
+
+        location.reload();
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function onDeleteYear() {
     let year = document.body.dataset.year;
 
     let confirmMessage =
-        "Möchten Sie das Jahr " + year + " wirklich endgültig löschen?\n\n" +
-        "Dabei werden sämtliche dieses Jahr betreffende Daten endgültig gelöscht, " +
+        "Möchten Sie das Semester " + document.body.dataset.yearLabel +
+        " wirklich endgültig löschen?\n\n" +
+        "Dabei werden sämtliche dieses Semester betreffende Daten endgültig gelöscht, " +
         "inklusive der Teilnehmer, Betreuer und des Audit-Logs.\n\n" +
         "Dies kann nicht rückgängig gemacht werden!";
 
@@ -103,3 +261,56 @@ async function onDeleteYear() {
         toast("error", e);
     }
 }
+
+// Ask for confirmation before deleting, showing everything that would be
+// deleted as well (the url must point to a delete route supporting dry runs)
+async function confirmDeletion(url, name) {
+    let response = await myfetch(url + "?dry_run=true", {
+        method: "DELETE"
+    });
+    handleResponse(response);
+    let impact = await response.json();
+
+    const labels = {
+        groups: "Gruppen",
+        group_mappings: "Gruppenzuordnungen",
+        tasks: "Aufgaben",
+        completions: "erledigte Aufgaben",
+        events: "Termine",
+        elaborations: "Ausarbeitungen"
+    };
+
+    let affected = Object.keys(labels)
+        .filter(key => impact[key] > 0)
+        .map(key => "  – " + impact[key] + " " + labels[key]);
+
+    let message = name + " wirklich in den Papierkorb verschieben?";
+    if(affected.length > 0) {
+        message += "\n\nDabei werden auch folgende Einträge entfernt:\n" +
+            affected.join("\n");
+    }
+
+    return confirm(message);
+}
+
+// Ask for a new name and rename the entry using a patch route, returns the new
+// name or null if nothing was changed
+async function promptRename(url, name, message) {
+    let newName = prompt(message, name);
+    if(newName === null || newName.trim() === "" || newName.trim() === name) {
+        return null;
+    }
+
+    let response = await myfetch(url, {
+        method: "PATCH",
+        headers: new Headers({"Content-Type": "application/json"}),
+        body: JSON.stringify({
+            name: newName.trim()
+        })
+    });
+    handleResponse(response, {
+        422: "Der Name ist bereits vergeben."
+    });
+
+    return newName.trim();
+}
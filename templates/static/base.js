@@ -74,6 +74,19 @@ function handleResponse(response, customErrors) {
     }
 }
 
+// Like handleResponse, but prefers the message for the user that is sent with
+// an unprocessable entity response
+async function handleMessageResponse(response, customErrors) {
+    if(response.status === 422) {
+        let message = await response.text();
+        if(message) {
+            throw message;
+        }
+    }
+
+    handleResponse(response, customErrors);
+}
+
 class SearchBox {
     constructor(element, searchCallback, successCallback) {
         this.box = element;
@@ -279,30 +292,98 @@ function handleTaskPush(event) {
     let data = JSON.parse(event.data);
 
     let selector = `.group[data-id="${data.group}"] > `
-         + `.task[data-id="${data.task}"] > input`;
-    let input = document.querySelector(selector);
+         + `.task[data-id="${data.task}"]`;
+    let task = document.querySelector(selector);
+    if(task) {
+        setTaskPoints(task, data.completed, data.points);
+    }
+}
+
+// Show the completion state and partial credit of the task and update the
+// score of its group
+function setTaskPoints(task, completed, points) {
+    task.querySelector("input[type=checkbox]").checked = completed;
+
+    let input = task.querySelector("input.points");
     if(input) {
-        input.checked = data.completed;
+        if(!completed) {
+            input.value = "";
+        } else if(points === null || points === undefined) {
+            input.value = task.dataset.maxPoints;
+        } else {
+            input.value = points;
+        }
+        // Remember the value for the reset logic on fetch failure
+        input.dataset.prev = input.value;
+    }
+
+    updateScore(task.closest(".group"));
+}
+
+function updateScore(group) {
+    let reached = 0;
+    let maximum = 0;
+    for(let task of group.querySelectorAll(".task")) {
+        let weight = parseInt(task.dataset.weight);
+        let maxPoints = parseInt(task.dataset.maxPoints);
+        maximum += weight * maxPoints;
+
+        if(task.querySelector("input[type=checkbox]").checked) {
+            let input = task.querySelector("input.points");
+            let points = input ? parseInt(input.value) : maxPoints;
+            reached += weight * (isNaN(points) ? maxPoints : points);
+        }
+    }
+
+    let percent = maximum == 0 ? 100 : Math.floor(reached * 100 / maximum);
+    let score = group.querySelector(".score");
+    if(score) {
+        score.textContent = `${reached} / ${maximum} Punkte (${percent} %)`;
+    }
+
+    let seat = document.querySelector(`.seat-map .seat[data-group="${group.dataset.id}"]`);
+    if(seat) {
+        seat.dataset.progress = percent >= 100 ? "complete" : (percent > 0 ? "partial" : "none");
     }
 }
 
 async function handleTaskChange(event) {
-    let checked = event.target.checked;
+    let task = event.target.closest(".task");
+    let checkbox = task.querySelector("input[type=checkbox]");
+    let pointsInput = task.querySelector("input.points");
+
+    // Entering points marks the task as completed with partial credit
+    let isPoints = event.target === pointsInput;
+    let checked = isPoints ? pointsInput.value !== "" : checkbox.checked;
+    let points = isPoints && checked ? parseInt(pointsInput.value) : null;
 
     let group = event.target.closest(".group").dataset.id;
-    let task = event.target.closest(".task").dataset.id;
+    let prevChecked = isPoints ? checkbox.checked : !checked;
+    let prevPoints = null;
+    if(pointsInput) {
+        prevPoints = pointsInput.dataset.prev !== undefined
+            ? pointsInput.dataset.prev : pointsInput.defaultValue;
+    }
 
     try {
-        let url = "/api/group/" + group + "/completed/" + task;
+        let url = "/api/group/" + group + "/completed/" + task.dataset.id;
         let options = {
             method: checked ? "PUT" : "DELETE"
         };
+        if(points !== null) {
+            options.headers = new Headers({"Content-Type": "application/json"});
+            options.body = JSON.stringify(points);
+        }
 
         let response = await myfetch(url, options);
-        handleResponse(response);
+        handleResponse(response, {
+            422: "Die Punkte müssen zwischen 0 und " + task.dataset.maxPoints + " liegen."
+        });
+
+        setTaskPoints(task, checked, points);
     } catch(e) {
         toast("error", e);
-        event.target.checked = !checked;
+        setTaskPoints(task, prevChecked, prevPoints === "" ? null : prevPoints);
     }
 }
 
@@ -343,6 +424,52 @@ async function handleElaborationChange(event) {
     }
 }
 
+async function handleGradingSave(event) {
+    let card = event.target.closest(".group");
+    let grading = event.target.closest(".grading");
+    let status = card.querySelector("select.elaboration").selectedOptions[0].dataset;
+
+    if(status.accepted === undefined) {
+        toast("error", "Bitte zuerst den Stand der Ausarbeitung auswählen.");
+        return;
+    }
+
+    let experiment = card.dataset.experiment;
+    if(experiment === undefined) {
+        experiment = event.target.closest(".experiment").dataset.id;
+    }
+
+    let points = grading.querySelector("input.points").value;
+    let grade = grading.querySelector("input.grade").value;
+    let reworkDue = grading.querySelector("input.rework-due").value;
+
+    try {
+        let url = "/api/group/" + card.dataset.id + "/elaboration/" + encodeURI(experiment);
+
+        let response = await myfetch(url, {
+            method: "PUT",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: JSON.stringify({
+                rework_required: status.rework == "1",
+                accepted: status.accepted == "1",
+                grading: {
+                    points: points === "" ? null : parseInt(points),
+                    grade: grade === "" ? null : grade,
+                    feedback: grading.querySelector("textarea").value,
+                    rework_due: reworkDue === "" ? null : reworkDue
+                }
+            })
+        });
+        handleResponse(response, {
+            422: "Die Punktzahl darf nicht negativ sein."
+        });
+
+        toast("info", "Die Bewertung wurde gespeichert.");
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
 async function handleCommentSave(event) {
     let group = event.target.closest(".group").dataset.id;
     let comment = event.target.closest(".comment").querySelector("textarea").value;
@@ -418,11 +545,9 @@ async function handleStudentClick(event) {
             studentName + " wirklich aus der Gruppe entfernen?\n" +
             "Dies ist nur möglich, falls die Gruppe noch keine Aufgaben " +
             "abgeschlossen oder Ausarbeitungen eingereicht hat!\n\n" +
-            "Bei Gruppenwechseln oder dem Auflösen einer Gruppe müssen für " +
-            "*ALLE* Teilnehmer der alten Gruppe neue Gruppen angelegt werden, " +
-            "damit der Bestanden-Status am Ende korrekt berechnet werden kann. " +
-            "Die alte Gruppe darf nicht weiter verwendet werden und kann mit " +
-            "\"(ENDE)\" im Kommentarfeld ausgegraut und ans Ende gestellt werden.";
+            "Bei Gruppenwechseln oder dem Auflösen einer Gruppe kann die " +
+            "Gruppe stattdessen aufgeteilt (⊘) oder mit einer anderen Gruppe " +
+            "zusammengelegt (⊕) werden, wobei der bisherige Fortschritt erhalten bleibt.";
         if(!confirm(warningMessage)) {
             return;
         }
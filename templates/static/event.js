@@ -7,6 +7,7 @@ document.addEventListener("DOMContentLoaded", () => {
     pushServer.addEventListener("comment", handleCommentPush);
     pushServer.addEventListener("completion", handleTaskPush);
     pushServer.addEventListener("elaboration", handleExperimentPush);
+    pushServer.addEventListener("group", handleGroupPush);
 
     for(input of document.querySelectorAll(".task input")) {
         input.addEventListener("change", handleTaskChange);
@@ -18,6 +19,10 @@ document.addEventListener("DOMContentLoaded", () => {
         select.addEventListener("change", handleElaborationChange);
     }
 
+    for(saveButton of document.querySelectorAll(".grading button.save")) {
+        saveButton.addEventListener("click", handleGradingSave);
+    }
+
     for(saveButton of document.querySelectorAll(".comment button.save")) {
         saveButton.addEventListener("click", handleCommentSave);
     }
@@ -45,12 +50,35 @@ document.addEventListener("DOMContentLoaded", () => {
         editButton.addEventListener("click", onGroupDeskChange);
     }
 
+    for(let moveButton of document.querySelectorAll(".group h2 a.change-day")) {
+        moveButton.addEventListener("click", onGroupDayChange);
+    }
+
+    for(let mergeButton of document.querySelectorAll(".group h2 a.merge")) {
+        mergeButton.addEventListener("click", onGroupMerge);
+    }
+
+    for(let splitButton of document.querySelectorAll(".group h2 a.split")) {
+        splitButton.addEventListener("click", onGroupSplit);
+    }
+
+    for(let seat of document.querySelectorAll(".seat-map .seat[data-group]")) {
+        seat.addEventListener("click", onSeatClick);
+    }
+
     document.querySelector("header .stats")
         .addEventListener("click", toggleCompactMode);
 
     restoreSessionState();
 });
 
+function onSeatClick(event) {
+    let group = document.querySelector(`.group[data-id="${event.target.dataset.group}"]`);
+    if(group) {
+        group.scrollIntoView({behavior: "smooth"});
+    }
+}
+
 function promptInt(message) {
     let input = prompt(message);
     if(input == null) {
@@ -86,7 +114,9 @@ async function onNewGroup(event) {
                 comment: ""
             })
         });
-        handleResponse(response);
+        handleResponse(response, {
+            422: "Der Tisch ist bereits belegt oder existiert nicht im Raum des Versuchstages."
+        });
 
         toast("reload", "Die neue Gruppe wurde hinzugefügt. Seite neuladen um sie anzuzeigen oder weitere Gruppen hinzufügen!")
     } catch(e) {
@@ -113,7 +143,9 @@ async function onGroupDeskChange(event) {
             headers: new Headers({"Content-Type": "application/json"}),
             body: JSON.stringify(desk)
         });
-        handleResponse(response);
+        handleResponse(response, {
+            422: "Der Tisch ist bereits belegt oder existiert nicht im Raum des Versuchstages."
+        });
 
         toast("reload", "Die Tischnummer der Gruppe wurde geändert. Seite neuladen um sie anzuzeigen!")
     } catch(e) {
@@ -121,6 +153,216 @@ async function onGroupDeskChange(event) {
     }
 }
 
+async function onGroupDayChange(event) {
+    // do not reload page (empty href)
+    event.preventDefault();
+
+    let experiment = document.querySelector(".experiment");
+    let currentDay = parseInt(experiment.dataset.day, 10);
+    let days = JSON.parse(experiment.dataset.days)
+        .filter((day) => day.id !== currentDay);
+    if(days.length === 0) {
+        toast("error", "Es gibt keinen anderen Versuchstag.");
+        return;
+    }
+
+    let name = prompt("Neuer Versuchstag der Gruppe ("
+        + days.map((day) => day.name).join(", ") + "):");
+    if(name === null) {
+        return;
+    }
+    let day = days.find((day) => day.name === name.trim());
+    if(day === undefined) {
+        toast("error", "Unbekannter Versuchstag " + name.trim() + ".");
+        return;
+    }
+
+    let group = event.target.closest(".group");
+    let desk = null;
+
+    // Ask for another desk if the desk is already taken on the other day
+    while(true) {
+        try {
+            let url = "/api/group/" + group.dataset.id + "/day";
+
+            let response = await myfetch(url, {
+                method: "PUT",
+                headers: new Headers({"Content-Type": "application/json"}),
+                body: JSON.stringify({
+                    day: day.id,
+                    desk: desk
+                })
+            });
+            if(response.status === 422) {
+                desk = promptInt("Der Tisch ist am " + day.name
+                    + " bereits belegt oder existiert dort nicht. Neue Tischnummer der Gruppe:");
+                if(desk === null) {
+                    return;
+                }
+                continue;
+            }
+            handleResponse(response);
+
+            group.parentNode.removeChild(group);
+            toast("info", "Die Gruppe wurde auf " + day.name + " verschoben.");
+        } catch(e) {
+            toast("error", e);
+        }
+        return;
+    }
+}
+
+// Describe the preview of a merge or split for a confirmation
+function describeRegrouping(preview, action) {
+    let message = "Teilnehmer: " + preview.students.join(", ") + "\n"
+        + preview.completions + " erledigte Aufgaben und "
+        + preview.elaborations + " Ausarbeitungen werden " + action + ".";
+
+    if(preview.conflicts.length > 0) {
+        message += "\n\nBei beiden Gruppen eingetragen, es wird der bessere Stand behalten:\n"
+            + preview.conflicts.map((conflict) => {
+                let group = document.querySelector(`.group[data-id="${conflict.kept}"]`);
+                let kept = group === null ? "#" + conflict.kept : "Gruppe " + group.dataset.desk;
+                let kind = conflict.kind === "completion" ? "Aufgabe" : "Ausarbeitung zu";
+                return "  – " + kind + " " + conflict.name + ": Stand von " + kept;
+            }).join("\n");
+    }
+
+    return message;
+}
+
+async function onGroupMerge(event) {
+    // do not reload page (empty href)
+    event.preventDefault();
+
+    let group = event.target.closest(".group");
+    let desk = promptInt("Tischnummer der Gruppe, die mit Gruppe "
+        + group.dataset.desk + " zusammengelegt werden soll:");
+    if(desk === null) {
+        return;
+    }
+
+    let other = document.querySelector(`.group[data-desk="${desk}"]`);
+    if(other === null || other === group) {
+        toast("error", "Keine andere Gruppe an Tisch " + desk + ".");
+        return;
+    }
+
+    try {
+        let url = "/api/group/" + group.dataset.id + "/merge/" + other.dataset.id;
+
+        let response = await myfetch(url + "?dry_run=true", {
+            method: "POST"
+        });
+        handleResponse(response);
+        let preview = await response.json();
+
+        if(!confirm("Gruppe " + desk + " wirklich mit Gruppe " + group.dataset.desk
+            + " zusammenlegen? Gruppe " + desk + " wird dabei aufgelöst.\n\n"
+            + describeRegrouping(preview, "übernommen")
+            + (preview.files > 0 ? "\n" + preview.files + " hochgeladene Dateien werden übernommen." : ""))) {
+            return;
+        }
+
+        response = await myfetch(url, {
+            method: "POST"
+        });
+        handleResponse(response);
+
+        other.parentNode.removeChild(other);
+        toast("reload", "Die Gruppen wurden zusammengelegt. Seite neuladen um den neuen Stand anzuzeigen!");
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+async function onGroupSplit(event) {
+    // do not reload page (empty href)
+    event.preventDefault();
+
+    let group = event.target.closest(".group");
+    let students = Array.from(group.querySelectorAll("ul.students > li[data-id]"));
+    if(students.length < 2) {
+        toast("error", "Nur Gruppen mit mindestens zwei Teilnehmern können aufgeteilt werden.");
+        return;
+    }
+
+    let input = prompt("Nummern der Teilnehmer, die in eine neue Gruppe wechseln (durch Kommas getrennt):\n"
+        + students.map((student, i) => (i + 1) + ": " + student.textContent).join("\n"));
+    if(input === null) {
+        return;
+    }
+
+    let chosen = input.split(",")
+        .map((number) => students[parseInt(number, 10) - 1])
+        .filter((student) => student !== undefined)
+        .map((student) => parseInt(student.dataset.id, 10));
+    if(chosen.length === 0 || chosen.length === students.length) {
+        toast("error", "Mindestens ein Teilnehmer muss wechseln und einer in der Gruppe bleiben.");
+        return;
+    }
+
+    let desk = promptInt("Tischnummer der neuen Gruppe:");
+    if(desk === null) {
+        return;
+    }
+
+    try {
+        let url = "/api/group/" + group.dataset.id + "/split";
+        let body = JSON.stringify({
+            students: chosen,
+            desk: desk
+        });
+
+        let response = await myfetch(url + "?dry_run=true", {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: body
+        });
+        handleResponse(response, {
+            422: "Der Tisch ist bereits belegt oder existiert nicht im Raum des Versuchstages."
+        });
+        let preview = await response.json();
+
+        if(!confirm("Gruppe " + group.dataset.desk + " wirklich aufteilen? Die neue Gruppe an Tisch "
+            + desk + " erhält eine Kopie des bisherigen Fortschritts.\n\n"
+            + describeRegrouping(preview, "kopiert")
+            + (preview.files > 0 ? "\n" + preview.files + " hochgeladene Dateien bleiben bei der bisherigen Gruppe." : ""))) {
+            return;
+        }
+
+        response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/json"}),
+            body: body
+        });
+        handleResponse(response, {
+            422: "Der Tisch ist bereits belegt oder existiert nicht im Raum des Versuchstages."
+        });
+
+        toast("reload", "Die Gruppe wurde aufgeteilt. Seite neuladen um die neue Gruppe anzuzeigen!");
+    } catch(e) {
+        toast("error", e);
+    }
+}
+
+function handleGroupPush(event) {
+    let data = JSON.parse(event.data);
+    if(data.type !== "Move") {
+        return;
+    }
+
+    let day = parseInt(document.querySelector(".experiment").dataset.day, 10);
+    let group = document.querySelector(`.group[data-id="${data.group}"]`);
+
+    if(data.from === day && data.to !== day && group !== null) {
+        group.parentNode.removeChild(group);
+        toast("info", "Eine Gruppe wurde auf einen anderen Tag verschoben.");
+    } else if(data.to === day && group === null) {
+        toast("reload", "Eine Gruppe wurde auf diesen Tag verschoben. Seite neuladen um sie anzuzeigen!");
+    }
+}
+
 function toggleCompactMode() {
     let experiment = document.querySelector("#main > .experiment");
     let compactActive = experiment.classList.contains("compact");
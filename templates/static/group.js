@@ -17,6 +17,14 @@ document.addEventListener("DOMContentLoaded", () => {
         select.addEventListener("change", handleElaborationChange);
     }
 
+    for(let input of document.querySelectorAll(".files input[type=file]")) {
+        input.addEventListener("change", handleFileUpload);
+    }
+
+    for(saveButton of document.querySelectorAll(".grading button.save")) {
+        saveButton.addEventListener("click", handleGradingSave);
+    }
+
     for(saveButton of document.querySelectorAll(".comment button.save")) {
         saveButton.addEventListener("click", handleCommentSave);
     }
@@ -38,3 +46,32 @@ document.addEventListener("DOMContentLoaded", () => {
     searchBox = new OverlaySearchBox(document.querySelector("#overlay .search"),
         searchStudents);
 });
+
+async function handleFileUpload(event) {
+    let file = event.target.files[0];
+    if(file === undefined) {
+        return;
+    }
+
+    let card = event.target.closest(".group");
+
+    try {
+        let url = "/api/group/" + card.dataset.id + "/elaboration/"
+            + card.dataset.experiment + "/file?filename=" + encodeURIComponent(file.name);
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/pdf"}),
+            body: file
+        });
+        await handleMessageResponse(response, {
+            422: "Es können nur PDF-Dateien bis zur maximalen Größe hochgeladen werden."
+        });
+
+        // reload to show the new version
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+        event.target.value = "";
+    }
+}
@@ -0,0 +1,34 @@
+document.addEventListener("DOMContentLoaded", () => {
+    for(let input of document.querySelectorAll(".files input[type=file]")) {
+        input.addEventListener("change", onUploadFile);
+    }
+});
+
+async function onUploadFile(event) {
+    let file = event.target.files[0];
+    if(file === undefined) {
+        return;
+    }
+
+    let upload = event.target.closest(".upload").dataset;
+
+    try {
+        let url = "/portal/group/" + upload.group + "/elaboration/"
+            + upload.experiment + "/file?filename=" + encodeURIComponent(file.name);
+
+        let response = await myfetch(url, {
+            method: "POST",
+            headers: new Headers({"Content-Type": "application/pdf"}),
+            body: file
+        });
+        await handleMessageResponse(response, {
+            422: "Es können nur PDF-Dateien bis zur maximalen Größe hochgeladen werden."
+        });
+
+        // reload to show the new version
+        location.reload(true);
+    } catch(e) {
+        toast("error", e);
+        event.target.value = "";
+    }
+}